@@ -8,13 +8,15 @@ use eframe::egui::{self, Color32, Layout};
 use eframe::wgpu::{CompareFunction, PrimitiveTopology};
 use wiew::instance::{Instance3d, Instance3dBuffer};
 use wiew::pipelines::flat::{self, FlatPipeline};
-use wiew::provided::Scene3d;
-use wiew::{Pass, Render, RenderContext, Res, VertexBuffer};
+use wiew::pipelines::object_id::ObjectIdPipeline;
+use wiew::pipelines::post::PostEffect;
+use wiew::provided::{Background, Scene3d};
+use wiew::{Aabb, ClipPlane, FogSettings, GridSettings, Lod, Pass, Render, RenderContext, Res, VertexBuffer};
 use wiew_eframe::{Eframe3dView, EframeWiewManager};
 use wiew::external::nalgebra;
 use wiew::external::rotation3::Rotation;
 
-use nalgebra::Vector3;
+use nalgebra::{Point3, Vector3};
 
 fn main() {
     let options = eframe::NativeOptions {
@@ -57,6 +59,7 @@ impl App {
 
         let settings = Arc::new(Mutex::new(Settings {
             grid: true,
+            sky: false,
             bg_top_left: Color32::from_rgba_premultiplied(14, 41, 29, 255),
             bg_tot_right: Color32::from_rgba_premultiplied(54, 22, 22, 255),
             bg_bottom_left: Color32::from_rgba_premultiplied(20, 17, 51, 255),
@@ -122,7 +125,46 @@ impl eframe::App for App {
                 ui.color_edit_button_srgba(&mut settings.bg_bottom_left);
                 ui.color_edit_button_srgba(&mut settings.bg_bottom_right);
             });
-            ui.checkbox(&mut self.settings.lock().unwrap().grid, "grid");
+            ui.horizontal(|ui| {
+                ui.checkbox(&mut self.settings.lock().unwrap().grid, "grid");
+                ui.checkbox(&mut self.settings.lock().unwrap().sky, "sky");
+                self.wiew.render_mode_ui(ui);
+
+                let mut view_settings = self.wiew.settings().lock().unwrap();
+                let mut ssao = view_settings.ssao.is_some();
+                if ui.checkbox(&mut ssao, "ambient occlusion").changed() {
+                    view_settings.ssao = ssao.then(Default::default);
+                }
+
+                ui.checkbox(&mut view_settings.infinite_grid, "infinite grid");
+
+                let mut selected = view_settings.selection.selected.contains(&MyShape::OBJECT_ID);
+                if ui.checkbox(&mut selected, "selected").changed() {
+                    view_settings.selection.selected = if selected { vec![MyShape::OBJECT_ID] } else { Vec::new() };
+                }
+
+                let mut fog = view_settings.fog.is_some();
+                if ui.checkbox(&mut fog, "fog").changed() {
+                    view_settings.fog = fog.then(|| FogSettings::exponential_squared(0.25));
+                }
+
+                let mut section = !view_settings.clip_planes.is_empty();
+                if ui.checkbox(&mut section, "section").changed() {
+                    view_settings.clip_planes = if section {
+                        vec![ClipPlane::new(Point3::origin(), -Vector3::y()).with_cap([0.9, 0.55, 0.2, 1.0])]
+                    } else {
+                        Vec::new()
+                    };
+                }
+
+                let mut fxaa = view_settings.post_effects.iter().any(|effect| effect.name == "fxaa");
+                if ui.checkbox(&mut fxaa, "fxaa").changed() {
+                    view_settings.post_effects.retain(|effect| effect.name != "fxaa");
+                    if fxaa {
+                        view_settings.post_effects.push(PostEffect::fxaa());
+                    }
+                }
+            });
             ui.with_layout(Layout::centered_and_justified(egui::Direction::TopDown), |ui| {
             //ui.with_layout(Layout::top_down_justified(egui::Align::Center), |ui| {
                 egui::Frame::canvas(ui.style()).show(ui, |ui| {
@@ -135,6 +177,7 @@ impl eframe::App for App {
 
 struct Settings {
     grid: bool,
+    sky: bool,
     bg_top_left: egui::Color32,
     bg_tot_right: egui::Color32,
     bg_bottom_left: egui::Color32,
@@ -143,14 +186,14 @@ struct Settings {
 
 struct MyScene {
     settings: Arc<Mutex<Settings>>,
-    triangle: Res<MyShape>,
+    triangle: Res<Lod<MyShape>>,
 }
 
 impl MyScene {
     fn new(settings: Arc<Mutex<Settings>>) -> Self {
         Self {
             settings,
-            triangle: Res::new(MyShape::new),
+            triangle: Res::new(MyShape::lod),
         }
     }
 }
@@ -165,26 +208,61 @@ impl Scene3d for MyScene {
         triangle.render(cx, pass);
     }
 
-    fn grid(&self) -> bool {
-        self.settings.lock().unwrap().grid
+    fn background(&self) -> Background {
+        if self.settings.lock().unwrap().sky {
+            Background::Sky(Default::default())
+        } else {
+            Background::Gradient(self.background_color())
+        }
+    }
+
+    fn grid(&self) -> Option<GridSettings> {
+        self.settings.lock().unwrap().grid.then(GridSettings::default)
+    }
+
+    fn raster_object_ids(
+        &mut self,
+        cx: &mut RenderContext,
+        pass: &mut Pass,
+    ) {
+        let triangle = cx.resource(&self.triangle);
+        if let Some(triangle) = triangle.level(pass) {
+            triangle.render_object_ids(cx, pass);
+        }
     }
 }
 
 struct MyShape {
     vb: VertexBuffer<flat::Vertex>,
     ib: Instance3dBuffer,
+    bounds: Aabb,
     pipeline: FlatPipeline,
+    id_pipeline: ObjectIdPipeline,
 }
 
 impl MyShape {
-    fn new(cx: &mut RenderContext) -> Self {
+    const OBJECT_ID: u32 = 1;
+}
+
+impl MyShape {
+    /// The surface with fewer triangles when it gets smaller on screen
+    fn lod(cx: &mut RenderContext) -> Lod<MyShape> {
+        let shape = Self::new(cx, 1000);
+
+        Lod::new(shape.bounds.bounding_sphere())
+            .with_level(0.5, shape)
+            .with_level(0.1, Self::new(cx, 250))
+            .with_level(0.0, Self::new(cx, 60))
+    }
+
+    fn new(cx: &mut RenderContext, div: usize) -> Self {
         use flat::Vertex;
 
         let vertices = {
             let mut vertices: Vec<Vertex> = Vec::new();
 
-            let div_a = 1000;
-            let div_b = 1000;
+            let div_a = div;
+            let div_b = div;
 
             let n = 3;
             let m = 4;
@@ -230,6 +308,9 @@ impl MyShape {
 
         println!("vertices: {} ({} triangles)", vertices.len(), vertices.len() / 3);
 
+        // the instance is at the origin, the bounds are the same in world space
+        let bounds = Aabb::from_points(vertices.iter().map(|v| v.position.into())).unwrap();
+
         let vb = VertexBuffer::from_slice(
             cx.device,
             &vertices,
@@ -243,7 +324,7 @@ impl MyShape {
 
         let ib = Instance3dBuffer::single(
             cx.device,
-            Instance3d::from_placement(&Default::default()),
+            Instance3d::from_placement(&Default::default()).with_object_id(MyShape::OBJECT_ID),
             None,
         );
 
@@ -253,7 +334,17 @@ impl MyShape {
             true,
         );
 
-        Self { vb, ib, pipeline }
+        let id_pipeline = ObjectIdPipeline::new(PrimitiveTopology::TriangleList);
+
+        Self { vb, ib, bounds, pipeline, id_pipeline }
+    }
+
+    fn render_object_ids(
+        &self,
+        cx: &mut RenderContext,
+        pass: &mut Pass,
+    ) {
+        self.id_pipeline.render(cx, pass, &self.vb, &self.ib);
     }
 }
 
@@ -8,7 +8,7 @@ use wiew_eframe::wiew::external::wgpu;
 use wiew_eframe::wiew::external::nalgebra::Vector3;
 use wiew_eframe::wiew::external::rotation3::Rotation;
 use wiew_eframe::wiew::instance::Instance3d;
-use wiew_eframe::wiew::{Pass, Render, Res};
+use wiew_eframe::wiew::{GridSettings, Pass, Render, Res};
 use wiew_eframe::wiew::{instance::Instance3dBuffer, pipelines::flat::FlatPipeline, RenderContext, VertexBuffer};
 use wiew_eframe::wiew::pipelines::flat;
 use wiew_eframe::{Eframe3dView, EframeWiewManager, Scene3d, Scene3dBackground};
@@ -144,8 +144,8 @@ impl Scene3d for MyScene {
         triangle.render(cx, pass);
     }
 
-    fn grid(&self) -> bool {
-        self.settings.lock().unwrap().grid
+    fn grid(&self) -> Option<GridSettings> {
+        self.settings.lock().unwrap().grid.then(GridSettings::default)
     }
 
     fn background_color(&self) -> wiew_eframe::Scene3dBackground {
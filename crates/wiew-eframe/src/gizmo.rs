@@ -0,0 +1,106 @@
+use eframe::egui::{self, Color32, Pos2, Rect, Sense, Stroke};
+
+use wiew::{ClipPlane, ProjectionCamera, MAX_CLIP_PLANES};
+use wiew::external::{cgmath, nalgebra};
+
+use nalgebra::{Point3, Vector3};
+
+/// Radius of the handle at the origin of a plane, in points
+const HANDLE_RADIUS: f32 = 6.0;
+
+/// Size of the squares drawn for the planes, relative to their distance to the camera
+const PLANE_SIZE: f32 = 0.15;
+
+const DEFAULT_COLOR: Color32 = Color32::from_rgb(230, 230, 230);
+
+/// The world to screen projection of a view
+struct Projection {
+    view_proj: cgmath::Matrix4<f32>,
+    rect: Rect,
+}
+
+impl Projection {
+    fn new(camera: &impl ProjectionCamera, rect: Rect) -> Self {
+        Self {
+            view_proj: camera.projection_matrix(rect.width() / rect.height()) * camera.view_matrix(),
+            rect,
+        }
+    }
+
+    /// `None` behind the camera
+    fn project(&self, p: &Point3<f32>) -> Option<Pos2> {
+        let clip = self.view_proj * cgmath::Vector4::new(p.x, p.y, p.z, 1.0);
+        if clip.w <= f32::EPSILON {
+            return None;
+        }
+
+        let (x, y) = (clip.x / clip.w, clip.y / clip.w);
+        Some(Pos2::new(
+            self.rect.min.x + (x + 1.0) * 0.5 * self.rect.width(),
+            self.rect.min.y + (1.0 - y) * 0.5 * self.rect.height(),
+        ))
+    }
+}
+
+/// Draw the clipping planes over the view, and move them along their normal by
+/// dragging the handle at their origin
+pub(crate) fn clip_plane_gizmos(
+    ui: &mut egui::Ui,
+    rect: Rect,
+    camera: &impl ProjectionCamera,
+    planes: &mut [ClipPlane],
+) {
+    let projection = Projection::new(camera, rect);
+    let painter = ui.painter_at(rect);
+    let eye = camera.view_point();
+
+    let count = planes.len().min(MAX_CLIP_PLANES);
+    for (i, plane) in planes[..count].iter_mut().enumerate() {
+        let normal = plane.unit_normal();
+        if normal.norm() < f32::EPSILON {
+            continue;
+        }
+
+        let size = PLANE_SIZE * (plane.origin - eye).norm();
+
+        // two axes of the plane
+        let u = if normal.x.abs() < 0.9 { Vector3::x() } else { Vector3::y() };
+        let u = normal.cross(&u).normalize() * size;
+        let v = normal.cross(&u);
+
+        let corners = [u + v, u - v, -u - v, -u + v]
+            .map(|offset| projection.project(&(plane.origin + offset)));
+        let (Some(center), Some(tip)) = (projection.project(&plane.origin), projection.project(&(plane.origin + normal * size * 0.5))) else {
+            continue;
+        };
+
+        let response = ui.interact(
+            Rect::from_center_size(center, egui::Vec2::splat(HANDLE_RADIUS * 3.0)),
+            ui.id().with(("clip plane gizmo", i)),
+            Sense::drag(),
+        );
+
+        // the drag along the projected normal moves the plane
+        if response.dragged() {
+            let axis = tip - center;
+            let length2 = axis.length_sq();
+            if length2 > 1.0 {
+                plane.translate(response.drag_delta().dot(axis) / length2 * size * 0.5);
+            }
+        }
+
+        let color = plane.cap.map_or(DEFAULT_COLOR, |[r, g, b, _]| Color32::from_rgb(
+            (r.clamp(0.0, 1.0) * 255.0) as u8,
+            (g.clamp(0.0, 1.0) * 255.0) as u8,
+            (b.clamp(0.0, 1.0) * 255.0) as u8,
+        ));
+        let active = response.hovered() || response.dragged();
+        let stroke = Stroke::new(if active { 2.5 } else { 1.5 }, color);
+
+        if let [Some(a), Some(b), Some(c), Some(d)] = corners {
+            painter.add(egui::Shape::convex_polygon(vec![a, b, c, d], color.gamma_multiply(0.15), stroke));
+        }
+        painter.arrow(center, tip - center, stroke);
+        painter.circle(center, HANDLE_RADIUS, if active { color } else { color.gamma_multiply(0.6) }, Stroke::new(1.0, Color32::BLACK));
+    }
+}
@@ -1,7 +1,7 @@
 
 use std::sync::{Arc, Mutex};
 
-use provided::{MyView3d, Scene3d};
+use provided::{MyView3d, Scene3d, View3dSettings};
 pub use wiew;
 
 use wiew::*;
@@ -13,6 +13,7 @@ use crate::{RenderContext, TrackballCamera, View};
 
 mod presentation;
 mod manager;
+mod gizmo;
 
 pub use presentation::*;
 pub use manager::*;
@@ -20,6 +21,8 @@ pub use manager::*;
 pub struct Eframe3dView {
     eframe_view: EframeView,
     camera: Arc<Mutex<TrackballCamera>>,
+    settings: Arc<Mutex<View3dSettings>>,
+    clip_plane_gizmos: bool,
 }
 
 impl Eframe3dView {
@@ -28,14 +31,55 @@ impl Eframe3dView {
         scene: impl Scene3d,
     ) -> Self {
         let camera = Arc::new(Mutex::new(TrackballCamera::new()));
+        let settings = Arc::new(Mutex::new(View3dSettings::default()));
 
-        let eframe_view = EframeView::new(MyView3d::new(scene, camera.clone()));
+        let eframe_view = EframeView::new(MyView3d::with_settings(scene, camera.clone(), settings.clone()));
         Self {
             eframe_view,
             camera,
+            settings,
+            clip_plane_gizmos: true,
         }
     }
 
+    /// The settings of the view, changes are applied on the next frame
+    pub fn settings(&self) -> &Arc<Mutex<View3dSettings>> {
+        &self.settings
+    }
+
+    pub fn render_mode(&self) -> RenderMode {
+        self.settings.lock().unwrap().render_mode
+    }
+
+    pub fn set_render_mode(&self, render_mode: RenderMode) {
+        self.settings.lock().unwrap().render_mode = render_mode;
+    }
+
+    /// Whether the [`View3dSettings::clip_planes`] are drawn over the view, with a
+    /// handle to drag them along their normal
+    pub fn clip_plane_gizmos(&self) -> bool {
+        self.clip_plane_gizmos
+    }
+
+    pub fn set_clip_plane_gizmos(&mut self, show: bool) {
+        self.clip_plane_gizmos = show;
+    }
+
+    /// A combo box to choose the [`RenderMode`]
+    pub fn render_mode_ui(&self, ui: &mut eframe::egui::Ui) {
+        let mut render_mode = self.render_mode();
+
+        eframe::egui::ComboBox::from_label("render mode")
+            .selected_text(render_mode.name())
+            .show_ui(ui, |ui| {
+                for mode in RenderMode::ALL {
+                    ui.selectable_value(&mut render_mode, mode, mode.name());
+                }
+            });
+
+        self.set_render_mode(render_mode);
+    }
+
     pub fn paint(&self, ui: &mut eframe::egui::Ui) {
         use eframe::egui;
 
@@ -95,6 +139,13 @@ impl Eframe3dView {
         }
 
         self.eframe_view.paint(ui, rect);
+
+        // over the view, the handles take the drags that start on them
+        if self.clip_plane_gizmos {
+            let camera = self.camera.lock().unwrap();
+            let mut settings = self.settings.lock().unwrap();
+            gizmo::clip_plane_gizmos(ui, rect, &*camera, &mut settings.clip_planes);
+        }
     }
 }
 
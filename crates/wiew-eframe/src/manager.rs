@@ -43,7 +43,10 @@ impl EframeWiewManager {
     }
 
     fn clean_resources(&mut self) {
-        self.resource_registry.lock().clean();
+        let mut registry = self.resource_registry.lock();
+        // the previous frame has been submitted, readbacks can be mapped
+        registry.map_readbacks();
+        registry.clean();
     }
 
     pub fn begin_frame(render_state: &eframe::egui_wgpu::RenderState) {
@@ -151,7 +154,7 @@ impl EframeWiewResources {
             sample_count: 1,
             dimension: wgpu::TextureDimension::D2,
             format: texture_format,
-            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_SRC,
             view_formats: &[texture_format],
         };
 
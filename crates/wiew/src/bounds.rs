@@ -0,0 +1,197 @@
+use nalgebra::{Matrix4, Point3, Vector3, Vector4};
+
+use crate::ProjectionCamera;
+
+/// An axis-aligned bounding box
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Aabb {
+    pub min: Point3<f32>,
+    pub max: Point3<f32>,
+}
+
+impl Aabb {
+    pub fn new(min: Point3<f32>, max: Point3<f32>) -> Self {
+        Self {
+            min,
+            max,
+        }
+    }
+
+    /// The smallest box containing all the points, `None` if there are none
+    pub fn from_points(points: impl IntoIterator<Item = Point3<f32>>) -> Option<Self> {
+        let mut points = points.into_iter();
+        let first = points.next()?;
+
+        Some(points.fold(Self::new(first, first), |aabb, p| Self {
+            min: aabb.min.inf(&p),
+            max: aabb.max.sup(&p),
+        }))
+    }
+
+    /// The smallest box containing both boxes
+    pub fn union(&self, other: &Aabb) -> Self {
+        Self {
+            min: self.min.inf(&other.min),
+            max: self.max.sup(&other.max),
+        }
+    }
+
+    pub fn center(&self) -> Point3<f32> {
+        nalgebra::center(&self.min, &self.max)
+    }
+
+    /// Half of the size of the box along each axis
+    pub fn half_extents(&self) -> Vector3<f32> {
+        (self.max - self.min) / 2.0
+    }
+
+    /// Radius of the sphere centered on the box that contains it
+    pub fn radius(&self) -> f32 {
+        self.half_extents().norm()
+    }
+
+    /// The sphere centered on the box that contains it
+    pub fn bounding_sphere(&self) -> BoundingSphere {
+        BoundingSphere::new(self.center(), self.radius())
+    }
+
+    pub fn contains_point(&self, p: &Point3<f32>) -> bool {
+        (0..3).all(|i| self.min[i] <= p[i] && p[i] <= self.max[i])
+    }
+
+    pub fn intersects(&self, other: &Aabb) -> bool {
+        (0..3).all(|i| self.min[i] <= other.max[i] && other.min[i] <= self.max[i])
+    }
+
+    /// The box containing the corners of this one transformed by `model`
+    pub fn transformed(&self, model: &Matrix4<f32>) -> Self {
+        let corners = (0..8).map(|i| model.transform_point(&Point3::new(
+            if i & 1 == 0 { self.min.x } else { self.max.x },
+            if i & 2 == 0 { self.min.y } else { self.max.y },
+            if i & 4 == 0 { self.min.z } else { self.max.z },
+        )));
+
+        Self::from_points(corners).unwrap()
+    }
+}
+
+/// A bounding sphere
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BoundingSphere {
+    pub center: Point3<f32>,
+    pub radius: f32,
+}
+
+impl BoundingSphere {
+    pub fn new(center: Point3<f32>, radius: f32) -> Self {
+        Self {
+            center,
+            radius,
+        }
+    }
+
+    /// The sphere containing this one transformed by `model`, its radius is scaled
+    /// by the largest scale of the matrix
+    pub fn transformed(&self, model: &Matrix4<f32>) -> Self {
+        let scale = (0..3)
+            .map(|i| model.fixed_view::<3, 1>(0, i).norm())
+            .fold(0.0, f32::max);
+
+        Self {
+            center: model.transform_point(&self.center),
+            radius: self.radius * scale,
+        }
+    }
+
+    pub fn contains_point(&self, p: &Point3<f32>) -> bool {
+        (p - self.center).norm_squared() <= self.radius * self.radius
+    }
+
+    pub fn intersects(&self, other: &BoundingSphere) -> bool {
+        let r = self.radius + other.radius;
+        (other.center - self.center).norm_squared() <= r * r
+    }
+}
+
+/// The volume seen by a camera, bounded by six planes
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Frustum {
+    /// Left, right, bottom, top, near and far planes, with normals `xyz` pointing
+    /// inside and distances `w`
+    pub planes: [Vector4<f32>; 6],
+}
+
+impl Frustum {
+    /// The frustum of a view-projection matrix, with a depth range of `[0, 1]` as in wgpu
+    pub fn from_matrix(view_proj: &Matrix4<f32>) -> Self {
+        let row = |i| view_proj.row(i).transpose();
+        let (r0, r1, r2, r3) = (row(0), row(1), row(2), row(3));
+
+        let planes = [r3 + r0, r3 - r0, r3 + r1, r3 - r1, r2, r3 - r2].map(|plane| {
+            let norm = plane.xyz().norm();
+            if norm > f32::EPSILON {
+                plane / norm
+            } else {
+                // the far plane of an infinite projection, contains everything
+                Vector4::new(0.0, 0.0, 0.0, 1.0)
+            }
+        });
+
+        Self {
+            planes,
+        }
+    }
+
+    pub fn new(view: cgmath::Matrix4<f32>, proj: cgmath::Matrix4<f32>) -> Self {
+        let view_proj: [[f32; 4]; 4] = (proj * view).into();
+        Self::from_matrix(&Matrix4::from(view_proj))
+    }
+
+    /// The frustum of `camera` for a viewport of the given aspect ratio (width / height)
+    pub fn from_camera(camera: &(impl ProjectionCamera + ?Sized), aspect: f32) -> Self {
+        Self::new(camera.view_matrix(), camera.projection_matrix(aspect))
+    }
+
+    fn distance(plane: &Vector4<f32>, p: &Point3<f32>) -> f32 {
+        plane.xyz().dot(&p.coords) + plane.w
+    }
+
+    pub fn contains_point(&self, p: &Point3<f32>) -> bool {
+        self.planes.iter().all(|plane| Self::distance(plane, p) >= 0.0)
+    }
+
+    /// Whether the sphere is at least partly inside, conservatively
+    pub fn intersects_sphere(&self, sphere: &BoundingSphere) -> bool {
+        self.planes.iter().all(|plane| Self::distance(plane, &sphere.center) >= -sphere.radius)
+    }
+
+    /// Whether the box is at least partly inside, conservatively
+    pub fn intersects_aabb(&self, aabb: &Aabb) -> bool {
+        self.planes.iter().all(|plane| {
+            // the corner that is the furthest along the normal
+            let p = Point3::new(
+                if plane.x >= 0.0 { aabb.max.x } else { aabb.min.x },
+                if plane.y >= 0.0 { aabb.max.y } else { aabb.min.y },
+                if plane.z >= 0.0 { aabb.max.z } else { aabb.min.z },
+            );
+            Self::distance(plane, &p) >= 0.0
+        })
+    }
+}
+
+/// A volume that can be tested against a [`Frustum`], see [`Pass::is_culled`](crate::Pass::is_culled)
+pub trait BoundingVolume {
+    fn intersects_frustum(&self, frustum: &Frustum) -> bool;
+}
+
+impl BoundingVolume for Aabb {
+    fn intersects_frustum(&self, frustum: &Frustum) -> bool {
+        frustum.intersects_aabb(self)
+    }
+}
+
+impl BoundingVolume for BoundingSphere {
+    fn intersects_frustum(&self, frustum: &Frustum) -> bool {
+        frustum.intersects_sphere(self)
+    }
+}
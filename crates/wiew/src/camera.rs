@@ -5,7 +5,7 @@ pub use trackball::*;
 mod identity; pub use identity::*;
 use wgpu::util::DeviceExt;
 
-use crate::{SingletonResource, RenderContext};
+use crate::{ClipPlane, FogFalloff, FogSettings, LightCamera, RenderContext, ShadowMap, ShadowMapCommon, ShadowSettings, SingletonResource, MAX_CLIP_PLANES, MAX_PCF_RADIUS, PUID};
 
 pub trait ProjectionCamera/*: Debug*/ {
     /// The view matrix of the camera.
@@ -59,14 +59,32 @@ impl SingletonResource for ProjectionCameraCommon {
             entries: &[
                 wgpu::BindGroupLayoutEntry {
                     binding: 0,
-                    visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
+                    // the compute commands of a pass cull with the camera, see `Pass::defer_compute`
+                    visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT | wgpu::ShaderStages::COMPUTE,
                     ty: wgpu::BindingType::Buffer {
                         ty: wgpu::BufferBindingType::Uniform,
                         has_dynamic_offset: false,
                         min_binding_size: None,
                     },
                     count: None,
-                }
+                },
+                // shadow map of the camera light
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Depth,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Comparison),
+                    count: None,
+                },
             ],
             label: Some("camera_bind_group_layout"),
         });
@@ -87,6 +105,8 @@ pub struct ProjectionCameraBuffer {
     pub uniform: CameraUniform, // TODO maybe remove this
     pub buffer: wgpu::Buffer,
     pub bind_group: wgpu::BindGroup,
+    /// The shadow map bound instead of the placeholder
+    shadow_map: Option<PUID>,
 }
 
 impl ProjectionCameraBuffer {
@@ -105,22 +125,60 @@ impl ProjectionCameraBuffer {
             }
         );
 
-        let camera_bind_group = cx.device.create_bind_group(&wgpu::BindGroupDescriptor {
+        let camera_bind_group = Self::create_bind_group(cx, &common, &buffer, None);
+
+        ProjectionCameraBuffer {
+            uniform,
+            buffer,
+            bind_group: camera_bind_group,
+            shadow_map: None,
+        }
+    }
+
+    fn create_bind_group(
+        cx: &mut RenderContext,
+        common: &ProjectionCameraCommon,
+        buffer: &wgpu::Buffer,
+        shadow_map: Option<&ShadowMap>,
+    ) -> wgpu::BindGroup {
+        let shadow_common = cx.singleton::<ShadowMapCommon>();
+
+        cx.device.create_bind_group(&wgpu::BindGroupDescriptor {
             layout: &common.bind_group_layout,
             entries: &[
                 wgpu::BindGroupEntry {
                     binding: 0,
                     resource: buffer.as_entire_binding(),
-                }
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(shadow_map.map_or(shadow_common.placeholder(), ShadowMap::view)),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::Sampler(shadow_common.sampler()),
+                },
             ],
             label: Some("camera_bind_group"),
-        });
+        })
+    }
 
-        ProjectionCameraBuffer {
-            uniform,
-            buffer,
-            bind_group: camera_bind_group,
+    /// Bind the shadow map sampled by the lit pipelines, or the placeholder.
+    ///
+    /// The shadows must also be enabled in the uniform, see [`CameraUniform::set_shadow`].
+    pub fn set_shadow_map(
+        &mut self,
+        cx: &mut RenderContext,
+        shadow_map: Option<&ShadowMap>,
+    ) {
+        let id = shadow_map.map(ShadowMap::id);
+        if id == self.shadow_map {
+            return;
         }
+
+        let common = cx.singleton::<ProjectionCameraCommon>();
+        self.bind_group = Self::create_bind_group(cx, &common, &self.buffer, shadow_map);
+        self.shadow_map = id;
     }
 
     pub fn prepare(
@@ -132,6 +190,35 @@ impl ProjectionCameraBuffer {
         self.uniform.update_view_proj(camera, aspect);
         queue.write_buffer(&self.buffer, 0, bytemuck::cast_slice(&[self.uniform]));
     }
+
+    /// Same as [`ProjectionCameraBuffer::prepare`], with the size of the viewport in
+    /// pixels, which the screen space widths (e.g. of the lines) need
+    pub fn prepare_viewport(
+        &mut self,
+        queue: &wgpu::Queue,
+        camera: &impl ProjectionCamera,
+        width: u32,
+        height: u32,
+    ) {
+        self.uniform.update_view_proj(camera, width as f32 / height as f32);
+        self.uniform.viewport_size = [width as f32, height as f32];
+        queue.write_buffer(&self.buffer, 0, bytemuck::cast_slice(&[self.uniform]));
+    }
+
+    /// Prepare the buffer to render the shadow map of a light
+    pub fn prepare_light(
+        &mut self,
+        queue: &wgpu::Queue,
+        light: &LightCamera,
+        resolution: u32,
+    ) {
+        self.uniform.set_matrices(light.view, light.proj);
+        self.uniform.view_point = light.position.into();
+        self.uniform.light_dir = light.direction.into();
+        self.uniform.viewport_size = [resolution as f32; 2];
+        self.uniform.disable_shadow();
+        queue.write_buffer(&self.buffer, 0, bytemuck::cast_slice(&[self.uniform]));
+    }
 }
 
 #[repr(C)]
@@ -143,6 +230,27 @@ pub struct CameraUniform {
     _padding0: [f32; 1],
     light_dir: [f32; 3],
     _padding1: [f32; 1],
+    viewport_size: [f32; 2],
+    _padding2: [f32; 2],
+    light_view_proj: [[f32; 4]; 4],
+    shadow_bias: f32,
+    shadow_normal_bias: f32,
+    shadow_strength: f32,
+    shadow_pcf_radius: u32,
+    shadow_enabled: u32,
+    _padding3: [u32; 3],
+    inv_view: [[f32; 4]; 4],
+    inv_proj: [[f32; 4]; 4],
+    clip_planes: [[f32; 4]; MAX_CLIP_PLANES],
+    clip_plane_count: u32,
+    fog_mode: u32,
+    fog_density: f32,
+    fog_start: f32,
+    fog_end: f32,
+    fog_height: f32,
+    fog_height_falloff: f32,
+    fog_max_opacity: f32,
+    fog_colors: [[f32; 4]; 4],
 }
 
 impl CameraUniform {
@@ -155,12 +263,92 @@ impl CameraUniform {
             _padding0: [0.0; 1],
             light_dir: [0.0; 3],
             _padding1: [0.0; 1],
+            viewport_size: [1.0; 2],
+            _padding2: [0.0; 2],
+            light_view_proj: cgmath::Matrix4::identity().into(),
+            shadow_bias: 0.0,
+            shadow_normal_bias: 0.0,
+            shadow_strength: 0.0,
+            shadow_pcf_radius: 0,
+            shadow_enabled: 0,
+            _padding3: [0; 3],
+            inv_view: cgmath::Matrix4::identity().into(),
+            inv_proj: cgmath::Matrix4::identity().into(),
+            clip_planes: [[0.0; 4]; MAX_CLIP_PLANES],
+            clip_plane_count: 0,
+            fog_mode: 0,
+            fog_density: 0.0,
+            fog_start: 0.0,
+            fog_end: 0.0,
+            fog_height: 0.0,
+            fog_height_falloff: 0.0,
+            fog_max_opacity: 0.0,
+            fog_colors: [[0.0; 4]; 4],
+        }
+    }
+
+    fn set_matrices(&mut self, view: cgmath::Matrix4<f32>, proj: cgmath::Matrix4<f32>) {
+        use cgmath::SquareMatrix;
+        self.view = view.into();
+        self.proj = proj.into();
+        self.inv_view = view.invert().unwrap_or(cgmath::Matrix4::identity()).into();
+        self.inv_proj = proj.invert().unwrap_or(cgmath::Matrix4::identity()).into();
+    }
+
+    /// Receive the shadows of a light, whose [`ShadowMap`] is bound with [`ProjectionCameraBuffer::set_shadow_map`]
+    pub fn set_shadow(&mut self, light: &LightCamera, settings: &ShadowSettings) {
+        self.light_view_proj = light.view_proj().into();
+        self.shadow_bias = settings.bias;
+        self.shadow_normal_bias = settings.normal_bias * light.extent / settings.resolution as f32;
+        self.shadow_strength = settings.strength.clamp(0.0, 1.0);
+        self.shadow_pcf_radius = settings.pcf_radius.min(MAX_PCF_RADIUS);
+        self.shadow_enabled = 1;
+    }
+
+    pub fn disable_shadow(&mut self) {
+        self.shadow_enabled = 0;
+    }
+
+    /// Fade the fragments into the fog, `background` are the colors of the top left,
+    /// top right, bottom left and bottom right corners of the view, which are blended
+    /// toward unless [`FogSettings::color`] is set
+    pub fn set_fog(&mut self, settings: &FogSettings, background: [[f32; 4]; 4]) {
+        // values of `FOG_*` in `camera.wgsl`
+        let (mode, start, end, density, height, falloff) = match settings.falloff {
+            FogFalloff::Linear { start, end } => (1, start, end, 0.0, 0.0, 0.0),
+            FogFalloff::Exponential { start, density } => (2, start, 0.0, density, 0.0, 0.0),
+            FogFalloff::ExponentialSquared { start, density } => (3, start, 0.0, density, 0.0, 0.0),
+            FogFalloff::Height { start, density, height, falloff } => (4, start, 0.0, density, height, falloff),
+        };
+
+        self.fog_mode = mode;
+        self.fog_start = start;
+        self.fog_end = end;
+        self.fog_density = density;
+        self.fog_height = height;
+        self.fog_height_falloff = falloff;
+        self.fog_max_opacity = settings.max_opacity.clamp(0.0, 1.0);
+        self.fog_colors = match settings.color {
+            Some([r, g, b]) => [[r, g, b, 1.0]; 4],
+            None => background,
+        };
+    }
+
+    pub fn disable_fog(&mut self) {
+        self.fog_mode = 0;
+    }
+
+    /// Cut away the fragments outside of the planes, only the first [`MAX_CLIP_PLANES`] are kept
+    pub fn set_clip_planes(&mut self, planes: &[ClipPlane]) {
+        let planes = &planes[..planes.len().min(MAX_CLIP_PLANES)];
+        for (uniform, plane) in self.clip_planes.iter_mut().zip(planes) {
+            *uniform = plane.equation();
         }
+        self.clip_plane_count = planes.len() as u32;
     }
 
     fn update_view_proj(&mut self, camera: &impl ProjectionCamera, aspect: f32) {
-        self.view = camera.view_matrix().into();
-        self.proj = camera.projection_matrix(aspect).into();
+        self.set_matrices(camera.view_matrix(), camera.projection_matrix(aspect));
         self.view_point = camera.view_point().into();
         self.light_dir = camera.light_dir().into();
         //self.light_dir = nalgebra::Vector3::new(0.0, 1.0, 0.0).normalize().into();
@@ -1,13 +1,13 @@
 use cgmath::num_traits::Pow;
 use rotation3::*;
 
-use crate::{instance::Instance3d, pipelines::flat::{self, FlatPipeline}, Pass, RenderContext, Res, VertexBuffer};
+use crate::{instance::Instance3d, pipelines::{flat, line::{LineStyle, Lines}}, Aabb, Pass, RenderContext, Res, VertexBuffer};
 
 use self::movement::{MouseMovement, NewMouseMovement};
 
 use super::{ProjectionCamera, OPENGL_TO_WGPU_MATRIX};
 
-use wgpu::{CompareFunction, PrimitiveTopology};
+use wgpu::CompareFunction;
 
 mod movement;
 
@@ -48,6 +48,12 @@ impl TrackballCamera {
         pass: &mut Pass,
         trackball: &Trackball,
     ) {
+        // the circles have a unit radius before the scaling of `update_instance`
+        let radius = nalgebra::Vector3::repeat(self.distance * self.trackball_relative_radius);
+        if pass.is_culled(&Aabb::new(self.target - radius, self.target + radius)) {
+            return;
+        }
+
         let res = cx.resource(&trackball.res);
 
         res.update_instance(
@@ -57,19 +63,8 @@ impl TrackballCamera {
             self.trackball_relative_radius,
         );
 
-        res.flat_pipeline.render(
-            cx,
-            pass,
-            res.vertex_buffer.slice(..),
-            res.instance_buffer.slice(..),
-        );
-
-        res.flat_pipeline_2.render(
-            cx,
-            pass,
-            res.vertex_buffer_2.slice(..),
-            res.instance_buffer.slice(..),
-        );
+        res.lines.render(cx, pass, &res.instance_buffer);
+        res.lines_2.render(cx, pass, &res.instance_buffer);
     }
 }
 
@@ -186,22 +181,30 @@ impl Trackball {
     pub fn new(
     ) -> Self {
         Self {
-            res: Res::new(|cx: &mut RenderContext| TrackballRes::new(cx)),
+            res: Res::new(|cx: &mut RenderContext| TrackballRes::new(cx, None)),
+        }
+    }
+
+    /// A trackball gizmo drawn with thick lines, see [`LinePipeline`](crate::pipelines::line::LinePipeline)
+    pub fn with_line_style(
+        style: LineStyle,
+    ) -> Self {
+        Self {
+            res: Res::new(move |cx: &mut RenderContext| TrackballRes::new(cx, Some(style))),
         }
     }
 }
 
 pub struct TrackballRes {
-    vertex_buffer: VertexBuffer<flat::Vertex>,
-    vertex_buffer_2: VertexBuffer<flat::Vertex>,
+    lines: Lines,
+    lines_2: Lines,
     instance_buffer: VertexBuffer<Instance3d>,
-    flat_pipeline: FlatPipeline,
-    flat_pipeline_2: FlatPipeline,
 }
 
 impl TrackballRes {
     pub fn new(
         cx: &mut RenderContext,
+        style: Option<LineStyle>,
     ) -> Self {
         const N: usize = 100;
         const L: f32 = 0.25;
@@ -237,20 +240,24 @@ impl TrackballRes {
         v([0.0, 0.0, -L * 0.5], B);
         v([0.0, 0.0, L], B);
 
-        let vertex_buffer = VertexBuffer::<flat::Vertex>::from_slice(
-            cx.device,
+        let lines = Lines::new(
+            cx,
             &vertices,
-            None,
+            style.as_ref(),
+            CompareFunction::Always,
+            false,
         );
 
         for v in vertices.iter_mut() {
             v.color[3] = 0.5;
         }
 
-        let vertex_buffer_2 = VertexBuffer::<flat::Vertex>::from_slice(
-            cx.device,
+        let lines_2 = Lines::new(
+            cx,
             &vertices,
-            None,
+            style.as_ref(),
+            CompareFunction::LessEqual,
+            true,
         );
 
         let instance_buffer = VertexBuffer::<Instance3d>::single(
@@ -259,23 +266,10 @@ impl TrackballRes {
             None,
         );
 
-        let flat_pipeline = FlatPipeline::new(
-            PrimitiveTopology::LineList,
-            CompareFunction::Always,
-            false,
-        );
-        let flat_pipeline_2 = FlatPipeline::new(
-            PrimitiveTopology::LineList,
-            CompareFunction::LessEqual,
-            true,
-        );
-
         Self {
-            vertex_buffer,
-            vertex_buffer_2,
+            lines,
+            lines_2,
             instance_buffer,
-            flat_pipeline,
-            flat_pipeline_2,
         }
     }
 
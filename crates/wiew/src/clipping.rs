@@ -0,0 +1,70 @@
+use nalgebra::{Point3, Vector3};
+
+/// Largest number of [`ClipPlane`]s honoured by the pipelines, the others are ignored
+pub const MAX_CLIP_PLANES: usize = 6;
+
+/// A plane that cuts away the half-space behind it, opposite to its normal.
+///
+/// The built-in pipelines discard the fragments that are cut away by the
+/// planes of the globals (see [`CameraUniform::set_clip_planes`](crate::CameraUniform::set_clip_planes)).
+/// With a cap color, the cut through the closed surfaces of the scene is filled
+/// (see [`pipelines::section`](crate::pipelines::section)).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ClipPlane {
+    /// A point of the plane
+    pub origin: Point3<f32>,
+    /// Points towards the half-space that is kept
+    pub normal: Vector3<f32>,
+    /// The color of the section faces, `None` leaves the cut open
+    pub cap: Option<[f32; 4]>,
+}
+
+impl ClipPlane {
+    pub fn new(origin: Point3<f32>, normal: Vector3<f32>) -> Self {
+        Self {
+            origin,
+            normal,
+            cap: None,
+        }
+    }
+
+    /// Fill the section faces with a color
+    pub fn with_cap(mut self, color: [f32; 4]) -> Self {
+        self.cap = Some(color);
+        self
+    }
+
+    /// The plane facing the other way, which keeps what this one cuts away
+    pub fn flipped(&self) -> Self {
+        Self {
+            normal: -self.normal,
+            ..*self
+        }
+    }
+
+    /// The unit normal of the plane, the normal itself if it is zero
+    pub fn unit_normal(&self) -> Vector3<f32> {
+        self.normal.try_normalize(f32::EPSILON).unwrap_or(self.normal)
+    }
+
+    /// The coefficients `[a, b, c, d]` of the plane equation `ax + by + cz + d = 0`,
+    /// with a unit normal `(a, b, c)`
+    pub fn equation(&self) -> [f32; 4] {
+        let normal = self.unit_normal();
+        [normal.x, normal.y, normal.z, -normal.dot(&self.origin.coords)]
+    }
+
+    /// Signed distance from the plane, negative for the points that are cut away
+    pub fn distance(&self, p: &Point3<f32>) -> f32 {
+        self.unit_normal().dot(&(p - self.origin))
+    }
+
+    pub fn contains_point(&self, p: &Point3<f32>) -> bool {
+        self.distance(p) >= 0.0
+    }
+
+    /// Move the plane along its normal
+    pub fn translate(&mut self, distance: f32) {
+        self.origin += self.unit_normal() * distance;
+    }
+}
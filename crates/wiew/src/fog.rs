@@ -0,0 +1,88 @@
+/// How the opacity of the fog grows with the distance to the camera
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FogFalloff {
+    /// From transparent at `start` to opaque at `end`, in world units
+    Linear {
+        start: f32,
+        end: f32,
+    },
+    /// `1 - exp(-density * d)`, with `d` the distance past `start`
+    Exponential {
+        start: f32,
+        density: f32,
+    },
+    /// `1 - exp(-(density * d)²)`, with `d` the distance past `start`, clearer
+    /// close to the camera
+    ExponentialSquared {
+        start: f32,
+        density: f32,
+    },
+    /// Exponential fog whose density is `density` at the height `height` (along `+Y`)
+    /// and decreases exponentially above it with the rate `falloff`, e.g. for valleys
+    Height {
+        start: f32,
+        density: f32,
+        height: f32,
+        falloff: f32,
+    },
+}
+
+/// Fog that fades the distant fragments into the background of the view.
+///
+/// The built-in pipelines blend toward the colors of the background at the
+/// corners of the view (see [`CameraUniform::set_fog`](crate::CameraUniform::set_fog)),
+/// so the fog matches a gradient background.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FogSettings {
+    pub falloff: FogFalloff,
+    /// Opacity of the fog at an infinite distance, between `0` and `1`
+    pub max_opacity: f32,
+    /// Blend toward this color instead of the background
+    pub color: Option<[f32; 3]>,
+}
+
+impl FogSettings {
+    /// The color of the fog over backgrounds whose colors are not known, such as
+    /// environment maps, when [`FogSettings::color`] is not set
+    pub const DEFAULT_COLOR: [f32; 3] = [0.7, 0.72, 0.75];
+
+    pub fn linear(start: f32, end: f32) -> Self {
+        Self::new(FogFalloff::Linear { start, end })
+    }
+
+    pub fn exponential(density: f32) -> Self {
+        Self::new(FogFalloff::Exponential { start: 0.0, density })
+    }
+
+    pub fn exponential_squared(density: f32) -> Self {
+        Self::new(FogFalloff::ExponentialSquared { start: 0.0, density })
+    }
+
+    pub fn height(density: f32, height: f32, falloff: f32) -> Self {
+        Self::new(FogFalloff::Height { start: 0.0, density, height, falloff })
+    }
+
+    fn new(falloff: FogFalloff) -> Self {
+        Self {
+            falloff,
+            max_opacity: 1.0,
+            color: None,
+        }
+    }
+
+    pub fn with_max_opacity(mut self, max_opacity: f32) -> Self {
+        self.max_opacity = max_opacity;
+        self
+    }
+
+    pub fn with_color(mut self, color: [f32; 3]) -> Self {
+        self.color = Some(color);
+        self
+    }
+}
+
+impl Default for FogSettings {
+    fn default() -> Self {
+        Self::exponential_squared(0.05)
+    }
+}
@@ -0,0 +1,101 @@
+use nalgebra::Vector3;
+
+use crate::pipelines::line::LineStyle;
+
+/// The plane of a grid, spanned by two axes
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum GridPlane {
+    XY,
+    #[default]
+    XZ,
+    YZ,
+    /// The plane spanned by `u` and `v`, which are normalized and made perpendicular
+    Custom {
+        u: Vector3<f32>,
+        v: Vector3<f32>,
+    },
+}
+
+impl GridPlane {
+    /// The orthonormal axes of the plane, highlighted with [`GridSettings::axis_colors`]
+    pub fn axes(&self) -> (Vector3<f32>, Vector3<f32>) {
+        match *self {
+            GridPlane::XY => (Vector3::x(), Vector3::y()),
+            GridPlane::XZ => (Vector3::x(), Vector3::z()),
+            GridPlane::YZ => (Vector3::y(), Vector3::z()),
+            GridPlane::Custom { u, v } => {
+                let u = u.try_normalize(f32::EPSILON).unwrap_or_else(Vector3::x);
+                let v = (v - u * u.dot(&v)).try_normalize(f32::EPSILON).unwrap_or_else(|| {
+                    // any direction perpendicular to `u`
+                    let other = if u.x.abs() < 0.9 { Vector3::x() } else { Vector3::y() };
+                    u.cross(&other).normalize()
+                });
+                (u, v)
+            },
+        }
+    }
+
+    /// The normal of the plane, `v × u` so that it is `+Y` for [`GridPlane::XZ`]
+    pub fn normal(&self) -> Vector3<f32> {
+        let (u, v) = self.axes();
+        v.cross(&u)
+    }
+}
+
+/// Placement, spacing and colors of a grid
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GridSettings {
+    pub plane: GridPlane,
+    /// Offset of the origin of the grid, in world units
+    pub origin: Vector3<f32>,
+    /// Distance between the major lines, in world units
+    pub spacing: f32,
+    /// Number of cells between two major lines.
+    ///
+    /// The infinite grid multiplies the spacing by powers of this number as the camera moves away.
+    pub minor_divisions: u32,
+    /// Half of the size of the fixed size grid, in major cells
+    pub extent: u16,
+    pub major_color: [f32; 3],
+    pub minor_color: [f32; 3],
+    /// Colors of the positive half of the two axes of the plane, see [`GridPlane::axes`]
+    pub axis_colors: [[f32; 4]; 2],
+    /// Opacity of the major and minor lines
+    pub line_alpha: f32,
+    /// Draw the fixed size grid with thick lines instead of hairlines, see [`LinePipeline`](crate::pipelines::line::LinePipeline)
+    pub line_style: Option<LineStyle>,
+}
+
+impl Default for GridSettings {
+    fn default() -> Self {
+        Self {
+            plane: GridPlane::XZ,
+            origin: Vector3::zeros(),
+            spacing: 1.0,
+            minor_divisions: 5,
+            extent: 10,
+            major_color: [0.5, 0.5, 0.5],
+            minor_color: [0.25, 0.25, 0.25],
+            axis_colors: [[1.0, 0.25, 0.25, 1.0], [0.25, 0.25, 1.0, 1.0]],
+            line_alpha: 0.25,
+            line_style: None,
+        }
+    }
+}
+
+impl GridSettings {
+    /// Transform from the grid space (`x` along the first axis, `z` along the second
+    /// one, in major cells) to the world space
+    pub fn model_matrix(&self) -> cgmath::Matrix4<f32> {
+        let (u, v) = self.plane.axes();
+        let n = v.cross(&u);
+        let s = self.spacing.max(1e-6);
+
+        cgmath::Matrix4::new(
+            u.x * s, u.y * s, u.z * s, 0.0,
+            n.x * s, n.y * s, n.z * s, 0.0,
+            v.x * s, v.y * s, v.z * s, 0.0,
+            self.origin.x, self.origin.y, self.origin.z, 1.0,
+        )
+    }
+}
@@ -6,6 +6,8 @@ pub mod external {
     pub use cgmath;
     pub use nalgebra;
     pub use rotation3;
+    pub use image;
+    pub use ab_glyph;
 }
 
 mod pass;
@@ -16,6 +18,15 @@ mod vertex_buffer;
 mod id;
 mod render;
 mod camera;
+mod readback;
+mod texture;
+mod text;
+mod bounds;
+mod shadow;
+mod grid;
+mod lod;
+mod clipping;
+mod fog;
 pub mod provided;
 
 pub use pass::*;
@@ -25,4 +36,13 @@ pub use resource::*;
 pub use vertex_buffer::*;
 pub use id::*;
 pub use render::*;
-pub use camera::*;
\ No newline at end of file
+pub use camera::*;
+pub use readback::*;
+pub use texture::*;
+pub use text::*;
+pub use bounds::*;
+pub use shadow::*;
+pub use grid::*;
+pub use lod::*;
+pub use clipping::*;
+pub use fog::*;
\ No newline at end of file
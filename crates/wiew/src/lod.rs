@@ -0,0 +1,130 @@
+use std::sync::Mutex;
+
+use crate::{BoundingSphere, Pass, Render, RenderContext};
+
+/// A level of a [`Lod`]
+pub struct LodLevel<T> {
+    /// The smallest screen size (see [`PassCamera::screen_size`](crate::PassCamera::screen_size))
+    /// at which the level is drawn
+    pub min_screen_size: f32,
+    pub renderable: T,
+}
+
+/// Several levels of detail of an object, the level drawn is selected from the
+/// screen size of its bounding sphere with the camera of the pass (see [`Pass::with_camera`]).
+///
+/// The finest level whose [`LodLevel::min_screen_size`] is reached is drawn, nothing
+/// is drawn below the smallest one. To avoid switching back and forth around a
+/// threshold, the selected level is kept until the screen size leaves its range
+/// by more than the hysteresis (a fraction of the thresholds).
+///
+/// # Example
+/// ```ignore
+/// let lod = Lod::new(sphere)
+///     .with_level(0.5, fine_mesh)
+///     .with_level(0.1, coarse_mesh)
+///     .with_level(0.01, coarsest_mesh);
+/// ```
+pub struct Lod<T> {
+    levels: Vec<LodLevel<T>>,
+    bounds: BoundingSphere,
+    hysteresis: f32,
+    /// The selected level, `None` before the first selection and `levels.len()`
+    /// when nothing is drawn
+    selected: Mutex<Option<usize>>,
+}
+
+impl<T> Lod<T> {
+    /// An object bounded by `bounds` (in world space), without levels
+    pub fn new(bounds: BoundingSphere) -> Self {
+        Self {
+            levels: Vec::new(),
+            bounds,
+            hysteresis: 0.1,
+            selected: Mutex::new(None),
+        }
+    }
+
+    /// Add a level drawn from `min_screen_size`
+    pub fn with_level(mut self, min_screen_size: f32, renderable: T) -> Self {
+        self.levels.push(LodLevel {
+            min_screen_size,
+            renderable,
+        });
+        self.levels.sort_by(|a, b| b.min_screen_size.total_cmp(&a.min_screen_size));
+        *self.selected.get_mut().unwrap() = None;
+        self
+    }
+
+    /// Set the hysteresis, `0` switches exactly at the thresholds
+    pub fn with_hysteresis(mut self, hysteresis: f32) -> Self {
+        self.hysteresis = hysteresis.max(0.0);
+        self
+    }
+
+    /// The levels, from the finest to the coarsest
+    pub fn levels(&self) -> &[LodLevel<T>] {
+        &self.levels
+    }
+
+    pub fn bounds(&self) -> &BoundingSphere {
+        &self.bounds
+    }
+
+    /// Move the object
+    pub fn set_bounds(&mut self, bounds: BoundingSphere) {
+        self.bounds = bounds;
+    }
+
+    /// The index of the level selected by the last frame, `None` if nothing was drawn
+    pub fn selected_level(&self) -> Option<usize> {
+        self.selected.lock().unwrap().filter(|i| *i < self.levels.len())
+    }
+
+    /// Select the level drawn at the given screen size
+    pub fn select(&self, screen_size: f32) -> Option<&LodLevel<T>> {
+        let n = self.levels.len();
+        let threshold = |i: usize| self.levels.get(i).map_or(0.0, |level| level.min_screen_size);
+        let target = self.levels.iter()
+            .position(|level| screen_size >= level.min_screen_size)
+            .unwrap_or(n);
+
+        let mut selected = self.selected.lock().unwrap();
+        let index = match *selected {
+            Some(i) if i <= n => {
+                let lower = threshold(i) * (1.0 - self.hysteresis);
+                let upper = if i == 0 { f32::INFINITY } else { threshold(i - 1) * (1.0 + self.hysteresis) };
+                if lower <= screen_size && screen_size < upper { i } else { target }
+            },
+            _ => target,
+        };
+        *selected = Some(index);
+
+        self.levels.get(index)
+    }
+
+    /// The level to draw in `pass`, `None` if the object is culled (see [`Pass::is_culled`])
+    /// or too small. Without the camera of the view, the finest level is drawn.
+    pub fn level(&self, pass: &mut Pass) -> Option<&T> {
+        if pass.is_culled(&self.bounds) {
+            return None;
+        }
+
+        let screen_size = pass.camera()
+            .map_or(f32::INFINITY, |camera| camera.screen_size(&self.bounds));
+
+        self.select(screen_size).map(|level| &level.renderable)
+    }
+}
+
+impl<T: Render> Render for Lod<T> {
+    fn render(
+        &self,
+        cx: &mut RenderContext,
+        pass: &mut Pass,
+    ) {
+        if let Some(renderable) = self.level(pass) {
+            renderable.render(cx, pass);
+        }
+    }
+}
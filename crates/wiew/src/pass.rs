@@ -1,5 +1,13 @@
 use wgpu::{BindGroup, CommandEncoder, RenderPass};
 
+use crate::{BoundingSphere, BoundingVolume, Frustum, ProjectionCamera};
+
+/// A deferred render command, see [`Pass::defer`]
+type Step<'a> = Box<dyn for<'rp> Fn(&mut wgpu::RenderPass<'rp>, &'rp wgpu::BindGroup) + 'a>;
+
+/// A deferred compute command, see [`Pass::defer_compute`]
+type ComputeStep<'a> = Box<dyn for<'cp> Fn(&mut wgpu::ComputePass<'cp>, &'cp wgpu::BindGroup) + 'a>;
+
 /// A pass that can be executed on a render surface.
 ///
 /// Usually, in wgpu, you will prepare the necessary resources for rendering
@@ -16,7 +24,12 @@ pub struct Pass<'a> {
     surface_info: SurfaceInfo,
     pub globals: &'a wgpu::BindGroup,
     descriptor: Option<Box<dyn FnOnce(&'a mut CommandEncoder) -> RenderPass<'a> + 'a>>,
-    steps: Vec<Box<dyn for<'rp> Fn(&mut wgpu::RenderPass<'rp>, &'rp wgpu::BindGroup) + 'a>>,
+    steps: Vec<Step<'a>>,
+    transparent_steps: Vec<Step<'static>>,
+    compute_steps: Vec<ComputeStep<'a>>,
+    camera: Option<PassCamera>,
+    frustum: Option<Frustum>,
+    culled_draws: u32,
 }
 
 impl<'a> Pass<'a> {
@@ -30,13 +43,70 @@ impl<'a> Pass<'a> {
             globals: camera_bind_group,
             descriptor: Some(Box::new(descriptor)),
             steps: Vec::new(),
+            transparent_steps: Vec::new(),
+            compute_steps: Vec::new(),
+            camera: None,
+            frustum: None,
+            culled_draws: 0,
+        }
+    }
+
+    /// The camera of the view, for the level of detail (see [`Lod`](crate::Lod)), and
+    /// its frustum for the culling unless [`Pass::with_frustum`] is called afterwards
+    pub fn with_camera(mut self, camera: PassCamera) -> Self {
+        self.camera = Some(camera);
+        self.frustum = Some(camera.frustum());
+        self
+    }
+
+    pub fn camera(&self) -> Option<&PassCamera> {
+        self.camera.as_ref()
+    }
+
+    /// Cull the objects outside of the view frustum of the camera, see [`Pass::is_culled`]
+    pub fn with_frustum(mut self, frustum: Frustum) -> Self {
+        self.frustum = Some(frustum);
+        self
+    }
+
+    /// The view frustum of the camera, if the pass culls
+    pub fn frustum(&self) -> Option<&Frustum> {
+        self.frustum.as_ref()
+    }
+
+    /// Whether an object with the given world space bounds is outside of the view
+    /// frustum and should not be drawn, it is then counted in [`Pass::culled_draws`].
+    ///
+    /// Nothing is culled without a frustum (see [`Pass::with_frustum`]).
+    ///
+    /// # Remarks
+    /// Culling is opt-in: a renderable calls this with its bounds before recording its
+    /// draws. The built-in [`Grid`](crate::provided::Grid), [`Lod`](crate::Lod) and the trackball of
+    /// [`TrackballCamera::render`](crate::TrackballCamera::render) do, the backgrounds and
+    /// the infinite grid cover the whole view. The objects of [`Scene3d::raster`](crate::provided::Scene3d::raster)
+    /// are only culled if the scene calls it for them.
+    pub fn is_culled(&mut self, bounds: &impl BoundingVolume) -> bool {
+        let culled = self.frustum.as_ref().is_some_and(|frustum| !bounds.intersects_frustum(frustum));
+        if culled {
+            self.culled_draws += 1;
         }
+        culled
+    }
+
+    /// The number of objects that were culled by [`Pass::is_culled`]
+    pub fn culled_draws(&self) -> u32 {
+        self.culled_draws
     }
 
     pub fn surface_info(&self) -> &SurfaceInfo {
         &self.surface_info
     }
 
+    /// The kind of pass, shorthand for `surface_info().kind`
+    pub fn kind(&self) -> PassKind {
+        self.surface_info.kind
+    }
+
     /// Execute the pass.
     ///
     /// This will consume the pass and execute the deferred render commands.
@@ -45,10 +115,33 @@ impl<'a> Pass<'a> {
     /// This method has to be explicitly called, otherwise the recorded commands
     /// will not be executed.
     pub fn exec(mut self, encoder: &'a mut CommandEncoder) {
+        if !self.compute_steps.is_empty() {
+            let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                label: Some("deferred compute pass"),
+                timestamp_writes: None,
+            });
+            for step in &self.compute_steps {
+                step(&mut compute_pass, self.globals);
+            }
+        }
+
         let mut render_pass = (self.descriptor.take().unwrap())(encoder);
         for step in &self.steps {
             step(&mut render_pass, &self.globals);
         }
+
+        if !self.transparent_steps.is_empty() {
+            log::error!("Pass executed with transparent commands that were not `Pass::split_transparent`-ed");
+        }
+    }
+
+    /// The kind of pass that pipelines which opted into order-independent
+    /// transparency draw into, see [`Pass::defer_for`].
+    pub fn transparent_kind(&self) -> PassKind {
+        match self.surface_info.kind {
+            PassKind::Main if self.surface_info.order_independent_transparency => PassKind::Transparent,
+            kind => kind,
+        }
     }
 
     /// Defer a render command.
@@ -86,6 +179,67 @@ impl<'a> Pass<'a> {
             }),
         );
     }
+
+    /// Defer a render command into the pass of the given kind.
+    ///
+    /// Commands of the [`PassKind::Transparent`] kind are recorded for the
+    /// transparent pass (see [`Pass::split_transparent`]), the pipeline must have
+    /// been built for it (see [`Pipeline::get_for`](crate::Pipeline::get_for)).
+    pub fn defer_for<F>(&mut self, kind: PassKind, command: F)
+    where
+        F: Fn(&mut wgpu::RenderPass, &BindGroup) + 'static
+    {
+        if kind != PassKind::Transparent {
+            self.defer(command);
+            return;
+        }
+
+        self.transparent_steps.push(
+            Box::new(move |render_pass: &mut wgpu::RenderPass, globals: &wgpu::BindGroup| {
+                command(render_pass, globals);
+            }),
+        );
+    }
+
+    /// Defer a compute command, executed in a compute pass before the render pass.
+    ///
+    /// The command is given the same global [`BindGroup`] as the render commands,
+    /// use this to prepare data for them (e.g. the culling of
+    /// [`IndirectInstances`](crate::pipelines::indirect::IndirectInstances)), including
+    /// the commands moved by [`Pass::split_transparent`], which are executed afterwards.
+    pub fn defer_compute<F>(&mut self, command: F)
+    where
+        F: Fn(&mut wgpu::ComputePass, &BindGroup) + 'static
+    {
+        self.compute_steps.push(
+            Box::new(move |compute_pass: &mut wgpu::ComputePass, globals: &wgpu::BindGroup| {
+                command(compute_pass, globals);
+            }),
+        );
+    }
+
+    /// Move the transparent commands into their own pass.
+    ///
+    /// The returned pass renders into the accumulation and revealage targets
+    /// of [`oit`](crate::pipelines::oit), it must be executed after this one and
+    /// composited onto the surface. Returns `None` if there is nothing transparent.
+    pub fn split_transparent<'b>(
+        &mut self,
+        globals: &'b wgpu::BindGroup,
+        descriptor: impl FnOnce(&'b mut CommandEncoder) -> RenderPass<'b> + 'b,
+    ) -> Option<Pass<'b>> {
+        if self.transparent_steps.is_empty() {
+            return None;
+        }
+
+        let mut pass = Pass::new(self.surface_info.clone(), globals, descriptor);
+        pass.steps.extend(
+            self.transparent_steps.drain(..)
+                .map(|step| step as Step<'b>)
+        );
+
+        Some(pass)
+    }
 }
 
 impl<'a> Drop for Pass<'a> {
@@ -96,7 +250,48 @@ impl<'a> Drop for Pass<'a> {
     }
 }
 
+/// The matrices of the camera a pass is rendered with, see [`Pass::with_camera`]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PassCamera {
+    pub view: cgmath::Matrix4<f32>,
+    pub proj: cgmath::Matrix4<f32>,
+}
+
+impl PassCamera {
+    pub fn new(view: cgmath::Matrix4<f32>, proj: cgmath::Matrix4<f32>) -> Self {
+        Self {
+            view,
+            proj,
+        }
+    }
+
+    /// The matrices of `camera` for a viewport of the given aspect ratio (width / height)
+    pub fn from_camera(camera: &(impl ProjectionCamera + ?Sized), aspect: f32) -> Self {
+        Self::new(camera.view_matrix(), camera.projection_matrix(aspect))
+    }
+
+    pub fn frustum(&self) -> Frustum {
+        Frustum::new(self.view, self.proj)
+    }
+
+    /// The height of the projection of the sphere, as a fraction of the height of
+    /// the viewport (`1` fills it), infinite if the camera is inside the sphere
+    pub fn screen_size(&self, sphere: &BoundingSphere) -> f32 {
+        let center = sphere.center;
+        let clip = self.proj * self.view * cgmath::Vector4::new(center.x, center.y, center.z, 1.0);
+
+        // `w` is the distance along the view direction with a perspective projection, `1` otherwise
+        let perspective = self.proj.w.w == 0.0;
+        if perspective && clip.w <= sphere.radius {
+            return f32::INFINITY;
+        }
+
+        sphere.radius * self.proj.y.y.abs() / clip.w
+    }
+}
+
 /// Information about the surface that the pass will render to.
+#[derive(Debug, Clone)]
 pub struct SurfaceInfo {
     /// The width of the surface.
     pub width: u32,
@@ -106,4 +301,61 @@ pub struct SurfaceInfo {
     pub format: wgpu::TextureFormat,
     /// The depth format of the surface, if it has one.
     pub depth_format: Option<wgpu::TextureFormat>,
+    /// How the meshes are drawn.
+    pub render_mode: RenderMode,
+    /// Whether the pass has a transparent pass with weighted blended
+    /// order-independent transparency, see [`Pass::transparent_kind`].
+    pub order_independent_transparency: bool,
+    /// The kind of pass, pipelines are built for it (see [`Pipeline::get`](crate::Pipeline::get)).
+    pub kind: PassKind,
+}
+
+/// The kind of render pass a pipeline is built for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum PassKind {
+    /// The color and depth targets of the surface
+    #[default]
+    Main,
+    /// The accumulation and revealage targets of the order-independent
+    /// transparency, see [`oit`](crate::pipelines::oit)
+    Transparent,
+    /// The depth-only shadow map of the camera light, see [`ShadowMap`](crate::ShadowMap)
+    Shadow,
+    /// The section faces of a [`ClipPlane`](crate::ClipPlane): the surfaces only
+    /// invert the stencil buffer, see [`section`](crate::pipelines::section)
+    Section,
+}
+
+/// How the triangle meshes of a pass are drawn.
+///
+/// This is respected by the built-in mesh pipelines (see [`RenderModeState`](crate::pipelines::RenderModeState)),
+/// lines, points and overlays are not affected.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum RenderMode {
+    #[default]
+    Shaded,
+    /// Only the edges of the triangles
+    Wireframe,
+    /// Shaded, with the edges of the triangles drawn on top
+    ShadedWithEdges,
+    /// Semi-transparent surfaces that do not hide each other
+    XRay,
+}
+
+impl RenderMode {
+    pub const ALL: [RenderMode; 4] = [
+        RenderMode::Shaded,
+        RenderMode::Wireframe,
+        RenderMode::ShadedWithEdges,
+        RenderMode::XRay,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            RenderMode::Shaded => "Shaded",
+            RenderMode::Wireframe => "Wireframe",
+            RenderMode::ShadedWithEdges => "Shaded with edges",
+            RenderMode::XRay => "X-ray",
+        }
+    }
 }
\ No newline at end of file
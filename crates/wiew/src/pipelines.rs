@@ -2,15 +2,214 @@
 use std::{collections::HashMap, sync::{Arc, Mutex}};
 
 
-use crate::{Pass, RenderContext, Res};
+use crate::{Pass, PassKind, RenderContext, RenderMode, Res};
 
 
 pub mod stupid_triangle;
 pub mod flat;
+pub mod object_id;
+pub mod lit;
+pub mod textured;
+pub mod pbr;
+pub mod line;
+pub mod point;
+pub mod billboard;
+pub mod oit;
+pub mod ssao;
+pub mod post;
+pub mod outline;
+pub mod infinite_grid;
+pub mod builder;
+pub mod indirect;
+pub mod skybox;
+pub mod section;
+pub mod material;
+
+/// WGSL declarations of the [`ProjectionCameraCommon`](crate::ProjectionCameraCommon)
+/// bind group, to be used as `@group(0)`
+pub const CAMERA_WGSL: &str = include_str!("pipelines/camera.wgsl");
+
+/// Create a shader module whose source is prepended with [`CAMERA_WGSL`]
+pub fn shader_with_globals(
+    device: &wgpu::Device,
+    label: &str,
+    source: &str,
+) -> wgpu::ShaderModule {
+    device.create_shader_module(wgpu::ShaderModuleDescriptor {
+        label: Some(label),
+        source: wgpu::ShaderSource::Wgsl(format!("{CAMERA_WGSL}\n{source}").into()),
+    })
+}
 
 pub struct SurfaceFormats {
     pub target_formats: Vec<wgpu::TextureFormat>,
     pub depth_format: Option<wgpu::TextureFormat>,
+    pub render_mode: RenderMode,
+    pub kind: PassKind,
+}
+
+impl SurfaceFormats {
+    /// The color targets of the pass: the surface formats with the given blend state
+    /// for the main pass, the [`oit`] targets for the transparent pass, none for
+    /// the shadow pass and the surface formats without writes for the section pass.
+    pub fn color_targets(&self, blend: wgpu::BlendState) -> Vec<Option<wgpu::ColorTargetState>> {
+        match self.kind {
+            PassKind::Main => self.target_formats.iter().map(|format| {
+                Some(wgpu::ColorTargetState {
+                    format: *format,
+                    blend: Some(blend),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })
+            }).collect(),
+            PassKind::Transparent => oit::color_targets().to_vec(),
+            PassKind::Shadow => Vec::new(),
+            PassKind::Section => self.target_formats.iter().map(|format| {
+                Some(wgpu::ColorTargetState {
+                    format: *format,
+                    blend: None,
+                    write_mask: wgpu::ColorWrites::empty(),
+                })
+            }).collect(),
+        }
+    }
+
+    /// The fragment entry point for the pass: `fs_oit` (which returns an `OitOutput`)
+    /// for the transparent pass, `fs_main` otherwise.
+    pub fn fragment_entry_point(&self) -> &'static str {
+        match self.kind {
+            PassKind::Main | PassKind::Shadow | PassKind::Section => "fs_main",
+            PassKind::Transparent => "fs_oit",
+        }
+    }
+
+    /// Transparent fragments are tested against the depth buffer but never written to it,
+    /// the section pass only writes the stencil buffer
+    pub fn depth_write(&self) -> bool {
+        !matches!(self.kind, PassKind::Transparent | PassKind::Section)
+    }
+
+    /// The depth test of the pipeline, the section pass counts all the surfaces
+    /// along the view rays
+    pub fn depth_compare(&self, depth_compare: wgpu::CompareFunction) -> wgpu::CompareFunction {
+        match self.kind {
+            PassKind::Section => wgpu::CompareFunction::Always,
+            _ => depth_compare,
+        }
+    }
+
+    /// The face culling of the pipeline, the section pass needs both faces
+    pub fn cull_mode(&self, cull_mode: Option<wgpu::Face>) -> Option<wgpu::Face> {
+        match self.kind {
+            PassKind::Section => None,
+            _ => cull_mode,
+        }
+    }
+
+    /// Each fragment of the section pass inverts the stencil buffer, which is left
+    /// non-zero where an odd number of surfaces was drawn (see [`section`])
+    pub fn stencil(&self) -> wgpu::StencilState {
+        match self.kind {
+            PassKind::Section => {
+                let face = wgpu::StencilFaceState {
+                    compare: wgpu::CompareFunction::Always,
+                    fail_op: wgpu::StencilOperation::Keep,
+                    depth_fail_op: wgpu::StencilOperation::Keep,
+                    pass_op: wgpu::StencilOperation::Invert,
+                };
+
+                wgpu::StencilState {
+                    front: face,
+                    back: face,
+                    read_mask: 0xff,
+                    write_mask: 0xff,
+                }
+            },
+            _ => wgpu::StencilState::default(),
+        }
+    }
+
+    /// A slope-scaled depth bias for the shadow pass, against shadow acne
+    pub fn depth_bias(&self) -> wgpu::DepthBiasState {
+        match self.kind {
+            PassKind::Shadow => wgpu::DepthBiasState {
+                constant: 2,
+                slope_scale: 2.0,
+                clamp: 0.0,
+            },
+            _ => wgpu::DepthBiasState::default(),
+        }
+    }
+}
+
+/// How a triangle mesh pipeline implements the [`RenderMode`] of the pass.
+///
+/// The shader must use `apply_render_mode` from [`CAMERA_WGSL`] on its output color,
+/// with the barycentric coordinates given by `corner_barycentric`: edges are only
+/// available for non-indexed triangle lists. Other triangle topologies, and the
+/// indexed draws (see [`RenderModeState::indexed`]), fall back to [`wgpu::PolygonMode::Line`]
+/// for the wireframe mode when the device supports it.
+///
+/// The corners are told apart by `vertex_index % 3`, where the index includes the
+/// first vertex of the draw. Sub-range and indirect draws are supported as long as
+/// the first vertex starts a triangle, which the triangle list requires anyway: the
+/// three corners of each triangle still get distinct coordinates, only permuted.
+pub struct RenderModeState {
+    /// Pipeline-overridable constants for the fragment stage (`RENDER_MODE`)
+    pub constants: HashMap<String, f64>,
+    pub polygon_mode: wgpu::PolygonMode,
+    /// Whether the pipeline may write the depth buffer
+    pub depth_write: bool,
+}
+
+impl RenderModeState {
+    pub fn new(
+        device: &wgpu::Device,
+        render_mode: RenderMode,
+        topology: wgpu::PrimitiveTopology,
+    ) -> Self {
+        Self::with_indices(device, render_mode, topology, false)
+    }
+
+    /// The state of the indexed draws, whose `vertex_index` is the value of the index
+    /// and can't tell the corners of the triangles apart
+    pub fn indexed(
+        device: &wgpu::Device,
+        render_mode: RenderMode,
+        topology: wgpu::PrimitiveTopology,
+    ) -> Self {
+        Self::with_indices(device, render_mode, topology, true)
+    }
+
+    fn with_indices(
+        device: &wgpu::Device,
+        render_mode: RenderMode,
+        topology: wgpu::PrimitiveTopology,
+        indexed: bool,
+    ) -> Self {
+        use wgpu::{PolygonMode, PrimitiveTopology};
+
+        let triangles = matches!(topology, PrimitiveTopology::TriangleList | PrimitiveTopology::TriangleStrip);
+        let barycentric = topology == PrimitiveTopology::TriangleList && !indexed;
+        let polygon_mode_line = device.features().contains(wgpu::Features::POLYGON_MODE_LINE);
+
+        // values of `RENDER_MODE` in `camera.wgsl`
+        let (mode, polygon_mode) = match render_mode {
+            _ if !triangles => (0, PolygonMode::Fill),
+            RenderMode::Shaded => (0, PolygonMode::Fill),
+            RenderMode::Wireframe if barycentric => (1, PolygonMode::Fill),
+            RenderMode::Wireframe if polygon_mode_line => (0, PolygonMode::Line),
+            RenderMode::Wireframe => (0, PolygonMode::Fill),
+            RenderMode::ShadedWithEdges if barycentric => (2, PolygonMode::Fill),
+            RenderMode::ShadedWithEdges => (0, PolygonMode::Fill),
+            RenderMode::XRay => (3, PolygonMode::Fill),
+        };
+
+        Self {
+            constants: HashMap::from([("RENDER_MODE".to_string(), mode as f64)]),
+            polygon_mode,
+            depth_write: !(triangles && render_mode == RenderMode::XRay),
+        }
+    }
 }
 
 pub struct Pipeline {
@@ -20,7 +219,7 @@ pub struct Pipeline {
 
 struct Resources {
     target_formats: Vec<wgpu::TextureFormat>,
-    pipelines: HashMap<Option<wgpu::TextureFormat>, Res<wgpu::RenderPipeline>>,
+    pipelines: HashMap<(Option<wgpu::TextureFormat>, RenderMode, PassKind), Res<wgpu::RenderPipeline>>,
 }
 
 impl Pipeline {
@@ -45,12 +244,25 @@ impl Pipeline {
         &self,
         cx: &mut RenderContext,
         pass: &mut Pass,
+    ) -> Arc<wgpu::RenderPipeline> {
+        let kind = pass.kind();
+        self.get_for(cx, pass, kind)
+    }
+
+    /// Get the pipeline built for a given kind of pass, e.g. [`Pass::transparent_kind`]
+    pub fn get_for(
+        &self,
+        cx: &mut RenderContext,
+        pass: &mut Pass,
+        kind: PassKind,
     ) -> Arc<wgpu::RenderPipeline> {
         let mut res = self.res.lock().unwrap();
         let format = &pass.surface_info().format;
         let depth_format = &pass.surface_info().depth_format;
+        let render_mode = pass.surface_info().render_mode;
 
-        let ok = res.target_formats.iter().any(|f| f == format);
+        // the shadow pass has no color target
+        let ok = kind == PassKind::Shadow || res.target_formats.iter().any(|f| f == format);
 
         if !ok {
             let mut target_formats = res.target_formats.clone();
@@ -62,19 +274,21 @@ impl Pipeline {
             };
         }
 
-        let pipeline = match res.pipelines.get(depth_format) {
+        let pipeline = match res.pipelines.get(&(*depth_format, render_mode, kind)) {
             Some(pipeline) => pipeline.clone(),
             None => {
                 let formats = SurfaceFormats {
-                    target_formats: res.target_formats.clone(),
+                    target_formats: if kind == PassKind::Shadow { Vec::new() } else { res.target_formats.clone() },
                     depth_format: depth_format.clone(),
+                    render_mode,
+                    kind,
                 };
     
                 let builder = self.builder.clone();
     
                 let pipeline = Res::new(move |cx: &mut RenderContext| builder(cx, &formats));
 
-                res.pipelines.insert(depth_format.clone(), pipeline.clone());
+                res.pipelines.insert((*depth_format, render_mode, kind), pipeline.clone());
 
                 pipeline
             }
@@ -0,0 +1,345 @@
+use std::sync::Arc;
+
+use wgpu::{util::DeviceExt, Device, PrimitiveState, PrimitiveTopology, ShaderModule};
+
+use crate::{decl_vertex_raw_repr, instance::Instance3d, Pass, PassKind, ProjectionCameraCommon, RenderContext, Res, SingletonResource, Texture2d, Texture2dCommon, VertexBufferSlice, VertexRawRepr};
+
+use super::{shader_with_globals, Pipeline};
+
+decl_vertex_raw_repr! {
+    /// A camera-facing quad.
+    ///
+    /// Billboards are stepped per instance: each of them is expanded into a quad
+    /// by the vertex shader.
+    #[derive(Debug)]
+    struct Vertex (Instance step mode) {
+        /// Center of the quad
+        pub position: [f32; 3] as [7 => Float32x3],
+        /// Width and height, in the unit of [`BillboardStyle::size`]
+        pub size: [f32; 2] as [8 => Float32x2],
+        pub color: [f32; 4] as [9 => Float32x4],
+        /// Region of the texture: min uv, max uv
+        pub uv_rect: [f32; 4] as [10 => Float32x4],
+        /// Rotation in the plane of the quad, in radians
+        pub angle: f32 as [11 => Float32],
+    }
+}
+
+impl Vertex {
+    pub const fn new(position: [f32; 3], size: [f32; 2]) -> Self {
+        Self {
+            position,
+            size,
+            color: [1.0, 1.0, 1.0, 1.0],
+            uv_rect: [0.0, 0.0, 1.0, 1.0],
+            angle: 0.0,
+        }
+    }
+
+    pub const fn with_color(self, color: [f32; 4]) -> Self {
+        Self { color, ..self }
+    }
+
+    /// Use the cell `(column, row)` of a texture atlas made of `columns x rows` cells
+    pub fn with_atlas_cell(self, columns: u32, rows: u32, column: u32, row: u32) -> Self {
+        let (w, h) = (1.0 / columns as f32, 1.0 / rows as f32);
+        Self {
+            uv_rect: [column as f32 * w, row as f32 * h, (column + 1) as f32 * w, (row + 1) as f32 * h],
+            ..self
+        }
+    }
+
+    pub const fn with_angle(self, angle: f32) -> Self {
+        Self { angle, ..self }
+    }
+}
+
+/// A shader for billboards
+pub struct BillboardShader {
+    shader: ShaderModule,
+}
+
+impl BillboardShader {
+    /// Create a new billboard shader
+    pub fn new(
+        device: &Device,
+    ) -> Self {
+        Self {
+            shader: shader_with_globals(device, "billboard.wgsl", include_str!("billboard.wgsl")),
+        }
+    }
+}
+
+impl SingletonResource for BillboardShader {
+    fn init(ctx: &mut RenderContext) -> Self {
+        Self::new(ctx.device)
+    }
+}
+
+/// Unit of [`Vertex::size`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BillboardSize {
+    /// Constant size on screen
+    Pixels,
+    World,
+}
+
+/// How the billboards face the camera
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BillboardOrientation {
+    /// Parallel to the screen
+    Screen,
+    /// Rotating only around a world axis (e.g. trees or markers standing on the ground)
+    Axis([f32; 3]),
+}
+
+/// Appearance of the billboards drawn by [`BillboardPipeline`]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BillboardStyle {
+    pub size: BillboardSize,
+    pub orientation: BillboardOrientation,
+}
+
+impl Default for BillboardStyle {
+    fn default() -> Self {
+        Self {
+            size: BillboardSize::World,
+            orientation: BillboardOrientation::Screen,
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct BillboardStyleUniform {
+    axis: [f32; 3],
+    constrained: u32,
+    size_unit: u32,
+    _padding: [u32; 3],
+}
+
+impl From<&BillboardStyle> for BillboardStyleUniform {
+    fn from(style: &BillboardStyle) -> Self {
+        let (axis, constrained) = match style.orientation {
+            BillboardOrientation::Screen => ([0.0, 1.0, 0.0], 0),
+            BillboardOrientation::Axis(axis) => (axis, 1),
+        };
+
+        Self {
+            axis,
+            constrained,
+            size_unit: match style.size {
+                BillboardSize::Pixels => 0,
+                BillboardSize::World => 1,
+            },
+            _padding: [0; 3],
+        }
+    }
+}
+
+/// The bind group layout of [`BillboardMaterial`]s
+pub struct BillboardMaterialCommon {
+    bind_group_layout: wgpu::BindGroupLayout,
+}
+
+impl SingletonResource for BillboardMaterialCommon {
+    fn init(ctx: &mut RenderContext) -> Self {
+        let bind_group_layout = ctx.device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::VERTEX,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                }
+            ],
+            label: Some("billboard_material_bind_group_layout"),
+        });
+
+        Self {
+            bind_group_layout,
+        }
+    }
+}
+
+impl BillboardMaterialCommon {
+    pub fn layout(&self) -> &wgpu::BindGroupLayout {
+        &self.bind_group_layout
+    }
+}
+
+/// The GPU side of [`BillboardStyle`], bound as `@group(1)` by [`BillboardPipeline`]
+pub struct BillboardMaterial {
+    buffer: wgpu::Buffer,
+    bind_group: Arc<wgpu::BindGroup>,
+}
+
+impl BillboardMaterial {
+    pub fn new(
+        cx: &mut RenderContext,
+        style: &BillboardStyle,
+    ) -> Self {
+        let common = cx.singleton::<BillboardMaterialCommon>();
+
+        let buffer = cx.device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("billboard material buffer"),
+            contents: bytemuck::cast_slice(&[BillboardStyleUniform::from(style)]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let bind_group = cx.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: common.layout(),
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: buffer.as_entire_binding(),
+                }
+            ],
+            label: Some("billboard_material_bind_group"),
+        });
+
+        Self {
+            buffer,
+            bind_group: Arc::new(bind_group),
+        }
+    }
+
+    pub fn update(&self, queue: &wgpu::Queue, style: &BillboardStyle) {
+        queue.write_buffer(&self.buffer, 0, bytemuck::cast_slice(&[BillboardStyleUniform::from(style)]));
+    }
+}
+
+/// Textured quads facing the camera, for icons, markers and particles.
+///
+/// The texture is multiplied by the color of each billboard, use
+/// [`Texture2d::solid`] for plain quads. As for the [`PointPipeline`](super::point::PointPipeline),
+/// the first instance of the given [`Instance3d`] slice places all the billboards.
+pub struct BillboardPipeline {
+    pipeline: Pipeline,
+    default_material: Res<BillboardMaterial>,
+}
+
+impl BillboardPipeline {
+    pub fn new(
+        depth_compare: wgpu::CompareFunction,
+        use_depth_stencil: bool,
+    ) -> Self {
+        let primitive = PrimitiveState {
+            topology: PrimitiveTopology::TriangleList,
+            ..Default::default()
+        };
+
+        let pipeline = Pipeline::from_builder(move |cx, formats| {
+            let shader = cx.singleton::<BillboardShader>();
+
+            let camera_common = cx.singleton::<ProjectionCameraCommon>();
+            let material_common = cx.singleton::<BillboardMaterialCommon>();
+            let texture_common = cx.singleton::<Texture2dCommon>();
+
+            let render_pipeline_layout =
+            cx.device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: None,
+                bind_group_layouts: &[
+                    camera_common.layout(),
+                    material_common.layout(),
+                    texture_common.layout(),
+                ],
+                push_constant_ranges: &[],
+            });
+
+            let targets = formats.color_targets(wgpu::BlendState::ALPHA_BLENDING);
+
+            let shared_instance = wgpu::VertexBufferLayout {
+                array_stride: 0,
+                ..Instance3d::desc()
+            };
+
+            cx.device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some("billboard pipeline"),
+                layout: Some(&render_pipeline_layout),
+                vertex: wgpu::VertexState {
+                    module: &shader.shader,
+                    entry_point: "vs_main",
+                    buffers: &[
+                        Vertex::desc(),
+                        shared_instance,
+                    ],
+                    compilation_options: Default::default(),
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &shader.shader,
+                    entry_point: formats.fragment_entry_point(),
+                    targets: &targets,
+                    compilation_options: Default::default(),
+                }),
+                primitive,
+                depth_stencil: formats.depth_format.map(|format| wgpu::DepthStencilState {
+                    format,
+                    depth_write_enabled: use_depth_stencil,
+                    depth_compare,
+                    stencil: formats.stencil(),
+                    bias: formats.depth_bias(),
+                }),
+                multisample: Default::default(),
+                multiview: None,
+                cache: None,
+            })
+        });
+
+        Self {
+            pipeline,
+            default_material: Res::new(|cx: &mut RenderContext| BillboardMaterial::new(cx, &BillboardStyle::default())),
+        }
+    }
+
+    /// Render with the default [`BillboardStyle`]
+    pub fn render<'a>(
+        &self,
+        cx: &mut RenderContext,
+        pass: &mut Pass<'a>,
+        billboards: impl Into<VertexBufferSlice<Vertex>>,
+        instance: impl Into<VertexBufferSlice<Instance3d>>,
+        texture: &Texture2d,
+    ) {
+        let material = cx.resource(&self.default_material);
+        self.render_with_material(cx, pass, billboards, instance, texture, &material);
+    }
+
+    pub fn render_with_material<'a>(
+        &self,
+        cx: &mut RenderContext,
+        pass: &mut Pass<'a>,
+        billboards: impl Into<VertexBufferSlice<Vertex>>,
+        instance: impl Into<VertexBufferSlice<Instance3d>>,
+        texture: &Texture2d,
+        material: &BillboardMaterial,
+    ) {
+        // billboards do not bound a volume, they have no section faces
+        if pass.kind() == PassKind::Section {
+            return;
+        }
+
+        let billboards: VertexBufferSlice<Vertex> = billboards.into();
+        let instance: VertexBufferSlice<Instance3d> = instance.into();
+        let material = material.bind_group.clone();
+        let texture = texture.bind_group().clone();
+
+        let pipeline = self.pipeline.get(cx, pass);
+
+        pass.defer(move |rp, globals| {
+            let instance_offset = instance.range.start as wgpu::BufferAddress * std::mem::size_of::<Instance3d>() as wgpu::BufferAddress;
+
+            rp.set_pipeline(&pipeline);
+            rp.set_bind_group(0, globals, &[]);
+            rp.set_bind_group(1, &material, &[]);
+            rp.set_bind_group(2, &texture, &[]);
+            rp.set_vertex_buffer(0, billboards.buffer.slice(..));
+            rp.set_vertex_buffer(1, instance.buffer.slice(instance_offset..));
+            rp.draw(0..6, billboards.range.clone());
+        });
+    }
+}
@@ -0,0 +1,141 @@
+// ================================
+//             Style
+// ================================
+
+const SIZE_PIXELS: u32 = 0u;
+const SIZE_WORLD: u32 = 1u;
+
+struct BillboardStyle {
+    // world axis the quads rotate around, only used if `constrained` is set
+    axis: vec3<f32>,
+    constrained: u32,
+    size_unit: u32,
+};
+
+@group(1) @binding(0)
+var<uniform> style: BillboardStyle;
+
+@group(2) @binding(0)
+var billboard_texture: texture_2d<f32>;
+@group(2) @binding(1)
+var billboard_sampler: sampler;
+
+// ================================
+//            Vertex
+// ================================
+
+// billboards are stepped per instance, the vertex index selects the corner of the quad
+struct BillboardInput {
+    @location(7) position: vec3<f32>,
+    @location(8) size: vec2<f32>,
+    @location(9) color: vec4<f32>,
+    // region of the texture: min uv, max uv
+    @location(10) uv_rect: vec4<f32>,
+    // rotation in the plane of the quad, in radians
+    @location(11) angle: f32,
+};
+
+// the same transform is used for all the billboards of a draw call
+struct InstanceInput {
+    @location(0) model_0: vec4<f32>,
+    @location(1) model_1: vec4<f32>,
+    @location(2) model_2: vec4<f32>,
+    @location(3) model_3: vec4<f32>,
+    @location(4) model_inv_tr_0: vec3<f32>,
+    @location(5) model_inv_tr_1: vec3<f32>,
+    @location(6) model_inv_tr_2: vec3<f32>,
+    @location(13) color: vec4<f32>,
+    @location(14) object_id: u32,
+    @location(15) visible: u32,
+};
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) color: vec4<f32>,
+    @location(1) uv: vec2<f32>,
+    @location(2) world_position: vec3<f32>,
+};
+
+@vertex
+fn vs_main(
+    @builtin(vertex_index) vertex_index: u32,
+    billboard: BillboardInput,
+    instance: InstanceInput,
+) -> VertexOutput {
+    let model_matrix = mat4x4<f32>(
+        instance.model_0,
+        instance.model_1,
+        instance.model_2,
+        instance.model_3,
+    );
+
+    var corners = array<vec2<f32>, 6>(
+        vec2<f32>(-0.5, -0.5),
+        vec2<f32>(0.5, -0.5),
+        vec2<f32>(0.5, 0.5),
+        vec2<f32>(-0.5, -0.5),
+        vec2<f32>(0.5, 0.5),
+        vec2<f32>(-0.5, 0.5),
+    );
+    let corner = corners[vertex_index % 6u];
+
+    let center = model_matrix * vec4<f32>(billboard.position, 1.0);
+
+    // the rows of the view matrix are the camera axes in world space
+    var right = vec3<f32>(camera.view[0][0], camera.view[1][0], camera.view[2][0]);
+    var up = vec3<f32>(camera.view[0][1], camera.view[1][1], camera.view[2][1]);
+    if (style.constrained != 0u) {
+        up = normalize(style.axis);
+        let to_camera = camera.view_point - center.xyz;
+        let side = cross(up, to_camera);
+        if (dot(side, side) > 1e-12) {
+            right = normalize(side);
+        }
+    }
+
+    var scale = billboard.size;
+    if (style.size_unit == SIZE_PIXELS) {
+        // number of pixels per world unit at the depth of the center
+        let w = (camera.proj * camera.view * center).w;
+        scale /= 0.5 * camera.proj[1][1] * camera.viewport_size.y / w;
+    }
+
+    let c = cos(billboard.angle);
+    let s = sin(billboard.angle);
+    let local = vec2<f32>(c * corner.x - s * corner.y, s * corner.x + c * corner.y) * scale;
+    let world_position = center.xyz + right * local.x + up * local.y;
+
+    var out: VertexOutput;
+    out.clip_position = camera.proj * camera.view * vec4<f32>(world_position, 1.0);
+    out.world_position = world_position;
+    out.color = billboard.color * instance.color;
+    out.uv = vec2<f32>(
+        mix(billboard.uv_rect.x, billboard.uv_rect.z, corner.x + 0.5),
+        mix(billboard.uv_rect.w, billboard.uv_rect.y, corner.y + 0.5),
+    );
+
+    // hidden instances are moved outside of the clip volume
+    if (instance.visible == 0u) {
+        out.clip_position = vec4<f32>(2.0, 2.0, 2.0, 1.0);
+    }
+
+    return out;
+}
+
+// ================================
+//            Fragment
+// ================================
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    clip_fragment(in.world_position);
+
+    let color = in.color * textureSample(billboard_texture, billboard_sampler, in.uv);
+
+    // do not write the depth of transparent texels
+    if (color.a < 1.0 / 255.0) {
+        discard;
+    }
+
+    return apply_fog(color, in.world_position, in.clip_position.xy);
+}
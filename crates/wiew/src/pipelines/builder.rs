@@ -0,0 +1,474 @@
+use std::{borrow::Cow, marker::PhantomData, sync::Arc};
+
+use wgpu::{Buffer, PrimitiveState, PrimitiveTopology};
+
+use crate::{instance::Instance3d, Pass, PassKind, ProjectionCameraCommon, RenderContext, Res, SingletonResource, Texture2dCommon, VertexBufferSlice, VertexRawRepr};
+
+use super::{indirect::IndirectInstances, infinite_grid::InfiniteGridMaterialCommon, lit::LitMaterialCommon, Pipeline, RenderModeState, SurfaceFormats, CAMERA_WGSL};
+
+/// A singleton owning a bind group layout, that can be given to [`PipelineBuilder::bind_group`]
+pub trait BindGroupLayoutProvider: SingletonResource {
+    fn layout(&self) -> &wgpu::BindGroupLayout;
+}
+
+macro_rules! impl_bind_group_layout_provider {
+    ($($common:ty),*$(,)?) => {
+        $(
+            impl BindGroupLayoutProvider for $common {
+                fn layout(&self) -> &wgpu::BindGroupLayout {
+                    <$common>::layout(self)
+                }
+            }
+        )*
+    };
+}
+
+impl_bind_group_layout_provider!(
+    ProjectionCameraCommon,
+    LitMaterialCommon,
+    Texture2dCommon,
+    InfiniteGridMaterialCommon,
+);
+
+/// Owns a bind group layout, either a singleton or one built from entries
+trait LayoutSource: Send + Sync {
+    fn layout(&self) -> &wgpu::BindGroupLayout;
+}
+
+impl<T: BindGroupLayoutProvider> LayoutSource for T {
+    fn layout(&self) -> &wgpu::BindGroupLayout {
+        BindGroupLayoutProvider::layout(self)
+    }
+}
+
+struct EntriesLayout(wgpu::BindGroupLayout);
+
+impl LayoutSource for EntriesLayout {
+    fn layout(&self) -> &wgpu::BindGroupLayout {
+        &self.0
+    }
+}
+
+type LayoutGetter = Box<dyn Fn(&mut RenderContext) -> Arc<dyn LayoutSource> + Send + Sync>;
+
+/// Declares a mesh pipeline instead of writing its [`Pipeline::from_builder`] closure.
+///
+/// The pipeline draws vertices of type `V` with instances of type `I`, in the vertex
+/// buffers `0` and `1`. Unless [`PipelineBuilder::without_globals`] is called, the
+/// source is prepended with [`CAMERA_WGSL`], the globals are bound as `@group(0)` and
+/// the other bind groups follow. The variants for the kinds of passes and for the
+/// render modes are handled as by the built-in pipelines:
+/// - the fragment entry point defaults to [`SurfaceFormats::fragment_entry_point`](super::SurfaceFormats::fragment_entry_point),
+///   so the shader must have an `fs_oit` entry point for [`MeshPipeline::order_independent`];
+/// - with the globals, the `RENDER_MODE` constant and the polygon mode follow [`RenderModeState`],
+///   with [`RenderModeState::indexed`] for the indexed draws;
+/// - the depth test, the culling and the stencil follow [`SurfaceFormats`]
+///   for the section pass, the shader should call `clip_fragment` like the built-in ones.
+///   The lines and the points are not drawn into the section pass, only closed surfaces count.
+///
+/// # Example
+/// ```ignore
+/// let pipeline = PipelineBuilder::<flat::Vertex>::new("my pipeline", include_str!("my.wgsl"))
+///     .bind_group::<LitMaterialCommon>()
+///     .cull_mode(Some(wgpu::Face::Back))
+///     .build();
+///
+/// pipeline.render(cx, pass, &vertices, &instances, &[material_bind_group]);
+/// ```
+pub struct PipelineBuilder<V: VertexRawRepr, I: VertexRawRepr = Instance3d> {
+    label: String,
+    source: Cow<'static, str>,
+    globals: bool,
+    vertex_entry_point: &'static str,
+    fragment_entry_point: Option<&'static str>,
+    bind_groups: Vec<LayoutGetter>,
+    primitive: PrimitiveState,
+    blend: Option<wgpu::BlendState>,
+    depth_compare: wgpu::CompareFunction,
+    depth_write: bool,
+    depth_bias: Option<wgpu::DepthBiasState>,
+    stencil: Option<wgpu::StencilState>,
+    _phantom: PhantomData<fn() -> (V, I)>,
+}
+
+impl<V: VertexRawRepr, I: VertexRawRepr> PipelineBuilder<V, I> {
+    /// A pipeline with the WGSL `source`, alpha blended and depth tested with
+    /// [`wgpu::CompareFunction::Less`]
+    pub fn new(
+        label: impl Into<String>,
+        source: impl Into<Cow<'static, str>>,
+    ) -> Self {
+        Self {
+            label: label.into(),
+            source: source.into(),
+            globals: true,
+            vertex_entry_point: "vs_main",
+            fragment_entry_point: None,
+            bind_groups: Vec::new(),
+            primitive: PrimitiveState::default(),
+            blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+            depth_compare: wgpu::CompareFunction::Less,
+            depth_write: true,
+            depth_bias: None,
+            stencil: None,
+            _phantom: PhantomData,
+        }
+    }
+
+    /// Do not bind the [`ProjectionCameraCommon`] globals nor prepend [`CAMERA_WGSL`]
+    pub fn without_globals(mut self) -> Self {
+        self.globals = false;
+        self
+    }
+
+    pub fn vertex_entry_point(mut self, entry_point: &'static str) -> Self {
+        self.vertex_entry_point = entry_point;
+        self
+    }
+
+    /// Use the same fragment entry point for all the kinds of passes
+    pub fn fragment_entry_point(mut self, entry_point: &'static str) -> Self {
+        self.fragment_entry_point = Some(entry_point);
+        self
+    }
+
+    /// Append the layout of a singleton, e.g. [`LitMaterialCommon`]
+    pub fn bind_group<C: BindGroupLayoutProvider>(mut self) -> Self {
+        self.bind_groups.push(Box::new(|cx| cx.singleton::<C>() as Arc<dyn LayoutSource>));
+        self
+    }
+
+    /// Append a layout with the given entries
+    pub fn bind_group_entries(mut self, entries: Vec<wgpu::BindGroupLayoutEntry>) -> Self {
+        let label = format!("{}_bind_group_layout_{}", self.label, self.bind_groups.len());
+        let layout = Res::new(move |cx: &mut RenderContext| EntriesLayout(cx.device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some(&label),
+            entries: &entries,
+        })));
+
+        self.bind_groups.push(Box::new(move |cx| cx.resource(&layout) as Arc<dyn LayoutSource>));
+        self
+    }
+
+    pub fn topology(mut self, topology: PrimitiveTopology) -> Self {
+        self.primitive.topology = topology;
+        self
+    }
+
+    pub fn cull_mode(mut self, cull_mode: Option<wgpu::Face>) -> Self {
+        self.primitive.cull_mode = cull_mode;
+        self
+    }
+
+    pub fn front_face(mut self, front_face: wgpu::FrontFace) -> Self {
+        self.primitive.front_face = front_face;
+        self
+    }
+
+    /// The blend state of the color targets, `None` to replace
+    pub fn blend(mut self, blend: Option<wgpu::BlendState>) -> Self {
+        self.blend = blend;
+        self
+    }
+
+    /// The depth test, [`wgpu::CompareFunction::Always`] to disable it
+    pub fn depth_compare(mut self, depth_compare: wgpu::CompareFunction) -> Self {
+        self.depth_compare = depth_compare;
+        self
+    }
+
+    /// Whether the depth buffer is written, it never is in the transparent pass
+    pub fn depth_write(mut self, depth_write: bool) -> Self {
+        self.depth_write = depth_write;
+        self
+    }
+
+    /// Replace the default bias, which is [`SurfaceFormats::depth_bias`](super::SurfaceFormats::depth_bias)
+    pub fn depth_bias(mut self, bias: wgpu::DepthBiasState) -> Self {
+        self.depth_bias = Some(bias);
+        self
+    }
+
+    /// Replace the default stencil state, which is [`SurfaceFormats::stencil`](super::SurfaceFormats::stencil)
+    pub fn stencil(mut self, stencil: wgpu::StencilState) -> Self {
+        self.stencil = Some(stencil);
+        self
+    }
+
+    pub fn build(self) -> MeshPipeline<V, I> {
+        let Self {
+            label,
+            source,
+            globals,
+            vertex_entry_point,
+            fragment_entry_point,
+            bind_groups,
+            primitive,
+            blend,
+            depth_compare,
+            depth_write,
+            depth_bias,
+            stencil,
+            _phantom,
+        } = self;
+
+        let source = if globals { format!("{CAMERA_WGSL}\n{source}").into() } else { source };
+        let shader = {
+            let label = label.clone();
+            Res::new(move |cx: &mut RenderContext| cx.device.create_shader_module(wgpu::ShaderModuleDescriptor {
+                label: Some(&label),
+                source: wgpu::ShaderSource::Wgsl(source.clone()),
+            }))
+        };
+
+        let topology = primitive.topology;
+        let create_pipeline = Arc::new(move |cx: &mut RenderContext, formats: &SurfaceFormats, indexed: bool| {
+            let shader = cx.resource(&shader);
+
+            let mut layouts = Vec::new();
+            if globals {
+                layouts.push(cx.singleton::<ProjectionCameraCommon>() as Arc<dyn LayoutSource>);
+            }
+            layouts.extend(bind_groups.iter().map(|layout| layout(cx)));
+            let layouts = layouts.iter().map(|layout| layout.layout()).collect::<Vec<_>>();
+
+            let render_pipeline_layout =
+            cx.device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: None,
+                bind_group_layouts: &layouts,
+                push_constant_ranges: &[],
+            });
+
+            // the render mode needs the `RENDER_MODE` constant of the globals
+            let render_mode = globals.then(|| if indexed {
+                RenderModeState::indexed(cx.device, formats.render_mode, primitive.topology)
+            } else {
+                RenderModeState::new(cx.device, formats.render_mode, primitive.topology)
+            });
+
+            let targets = match blend {
+                Some(blend) => formats.color_targets(blend),
+                None => formats.color_targets(wgpu::BlendState::REPLACE).into_iter().map(|target| target.map(|target| wgpu::ColorTargetState {
+                    blend: None,
+                    ..target
+                })).collect(),
+            };
+
+            cx.device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some(&label),
+                layout: Some(&render_pipeline_layout),
+                vertex: wgpu::VertexState {
+                    module: &shader,
+                    entry_point: vertex_entry_point,
+                    buffers: &[
+                        V::desc(),
+                        I::desc(),
+                    ],
+                    compilation_options: Default::default(),
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &shader,
+                    entry_point: fragment_entry_point.unwrap_or_else(|| formats.fragment_entry_point()),
+                    targets: &targets,
+                    compilation_options: wgpu::PipelineCompilationOptions {
+                        constants: render_mode.as_ref().map(|render_mode| &render_mode.constants).unwrap_or(&Default::default()),
+                        ..Default::default()
+                    },
+                }),
+                primitive: PrimitiveState {
+                    polygon_mode: render_mode.as_ref().map_or(wgpu::PolygonMode::Fill, |render_mode| render_mode.polygon_mode),
+                    cull_mode: formats.cull_mode(primitive.cull_mode),
+                    ..primitive
+                },
+                depth_stencil: formats.depth_format.map(|format| wgpu::DepthStencilState {
+                    format,
+                    depth_write_enabled: depth_write
+                        && render_mode.as_ref().is_none_or(|render_mode| render_mode.depth_write)
+                        && formats.depth_write(),
+                    depth_compare: formats.depth_compare(depth_compare),
+                    stencil: stencil.clone().unwrap_or_else(|| formats.stencil()),
+                    bias: depth_bias.unwrap_or_else(|| formats.depth_bias()),
+                }),
+                multisample: Default::default(),
+                multiview: None,
+                cache: None,
+            })
+        });
+
+        // the indexed draws have their own variants, without the barycentric edges
+        let pipeline = {
+            let create_pipeline = create_pipeline.clone();
+            Pipeline::from_builder(move |cx, formats| create_pipeline(cx, formats, false))
+        };
+        let indexed_pipeline = Pipeline::from_builder(move |cx, formats| create_pipeline(cx, formats, true));
+
+        MeshPipeline {
+            pipeline,
+            indexed_pipeline,
+            globals,
+            triangles: matches!(topology, PrimitiveTopology::TriangleList | PrimitiveTopology::TriangleStrip),
+            order_independent: false,
+            _phantom: PhantomData,
+        }
+    }
+}
+
+/// A pipeline declared with a [`PipelineBuilder`]
+pub struct MeshPipeline<V: VertexRawRepr, I: VertexRawRepr = Instance3d> {
+    pipeline: Pipeline,
+    indexed_pipeline: Pipeline,
+    globals: bool,
+    /// Whether the topology has triangles, the others are not drawn in the section pass
+    triangles: bool,
+    order_independent: bool,
+    _phantom: PhantomData<fn() -> (V, I)>,
+}
+
+impl<V: VertexRawRepr, I: VertexRawRepr> MeshPipeline<V, I> {
+    /// Draw into the order-independent transparency pass of the view, when it has one
+    /// (see [`Pass::transparent_kind`]), and into the main pass otherwise.
+    ///
+    /// Use this for semi-transparent meshes: they blend regardless of the draw order,
+    /// but do not write the depth buffer. The shader must have an `fs_oit` entry point,
+    /// see [`PipelineBuilder`].
+    pub fn order_independent(self) -> Self {
+        Self {
+            order_independent: true,
+            ..self
+        }
+    }
+
+    /// Draw the `vertices` for each of the `instances`, with the given bind groups
+    /// after the globals
+    pub fn render<'a>(
+        &self,
+        cx: &mut RenderContext,
+        pass: &mut Pass<'a>,
+        vertices: impl Into<VertexBufferSlice<V>>,
+        instances: impl Into<VertexBufferSlice<I>>,
+        bind_groups: &[Arc<wgpu::BindGroup>],
+    ) {
+        self.render_inner(cx, pass, vertices.into(), instances.into(), bind_groups, None);
+    }
+
+    /// Same as [`MeshPipeline::render`], with an index buffer of `u16`
+    pub fn render_indexed<'a>(
+        &self,
+        cx: &mut RenderContext,
+        pass: &mut Pass<'a>,
+        vertices: impl Into<VertexBufferSlice<V>>,
+        instances: impl Into<VertexBufferSlice<I>>,
+        bind_groups: &[Arc<wgpu::BindGroup>],
+        index_buffer: Arc<Buffer>,
+    ) {
+        self.render_inner(cx, pass, vertices.into(), instances.into(), bind_groups, Some(index_buffer));
+    }
+
+    /// The kind of pass drawn into and its variant, `None` for the lines and the points
+    /// in the section pass, which would take part in the stencil count
+    fn pipeline_for(
+        &self,
+        cx: &mut RenderContext,
+        pass: &mut Pass,
+        indexed: bool,
+    ) -> Option<(PassKind, Arc<wgpu::RenderPipeline>)> {
+        if pass.kind() == PassKind::Section && !self.triangles {
+            return None;
+        }
+
+        let kind = if self.order_independent {
+            pass.transparent_kind()
+        } else {
+            pass.kind()
+        };
+        let pipeline = if indexed { &self.indexed_pipeline } else { &self.pipeline };
+
+        Some((kind, pipeline.get_for(cx, pass, kind)))
+    }
+
+    fn render_inner(
+        &self,
+        cx: &mut RenderContext,
+        pass: &mut Pass,
+        vertices: VertexBufferSlice<V>,
+        instances: VertexBufferSlice<I>,
+        bind_groups: &[Arc<wgpu::BindGroup>],
+        index_buffer: Option<Arc<Buffer>>,
+    ) {
+        let Some((kind, pipeline)) = self.pipeline_for(cx, pass, index_buffer.is_some()) else {
+            return;
+        };
+        let bind_groups = bind_groups.to_vec();
+        let globals = self.globals;
+
+        pass.defer_for(kind, move |rp, globals_bind_group| {
+            rp.set_pipeline(&pipeline);
+            let first = if globals {
+                rp.set_bind_group(0, globals_bind_group, &[]);
+                1
+            } else {
+                0
+            };
+            for (i, bind_group) in bind_groups.iter().enumerate() {
+                rp.set_bind_group(first + i as u32, bind_group, &[]);
+            }
+            rp.set_vertex_buffer(0, vertices.buffer.slice(..));
+            rp.set_vertex_buffer(1, instances.buffer.slice(..));
+            if let Some(index_buffer) = &index_buffer {
+                rp.set_index_buffer(index_buffer.slice(..), wgpu::IndexFormat::Uint16);
+                rp.draw_indexed(0..index_buffer.size() as u32 / 2, 0, instances.range.clone());
+            } else {
+                rp.draw(vertices.range.clone(), instances.range.clone());
+            }
+        });
+    }
+}
+
+impl<V: VertexRawRepr> MeshPipeline<V, Instance3d> {
+    /// Draw the `vertices` for the instances that pass the GPU culling, see [`IndirectInstances`].
+    ///
+    /// The draw is indexed when an index buffer of `u16` is given.
+    pub fn render_indirect<'a>(
+        &self,
+        cx: &mut RenderContext,
+        pass: &mut Pass<'a>,
+        vertices: impl Into<VertexBufferSlice<V>>,
+        instances: &IndirectInstances,
+        bind_groups: &[Arc<wgpu::BindGroup>],
+        index_buffer: Option<Arc<Buffer>>,
+    ) {
+        let vertices: VertexBufferSlice<V> = vertices.into();
+        let Some((kind, pipeline)) = self.pipeline_for(cx, pass, index_buffer.is_some()) else {
+            return;
+        };
+        let bind_groups = bind_groups.to_vec();
+        let globals = self.globals;
+
+        let args = match &index_buffer {
+            Some(index_buffer) => instances.cull(cx, pass, index_buffer.size() as u32 / 2, 0),
+            None => instances.cull(cx, pass, vertices.range.len() as u32, vertices.range.start),
+        };
+        let output = instances.output().clone();
+
+        pass.defer_for(kind, move |rp, globals_bind_group| {
+            rp.set_pipeline(&pipeline);
+            let first = if globals {
+                rp.set_bind_group(0, globals_bind_group, &[]);
+                1
+            } else {
+                0
+            };
+            for (i, bind_group) in bind_groups.iter().enumerate() {
+                rp.set_bind_group(first + i as u32, bind_group, &[]);
+            }
+            rp.set_vertex_buffer(0, vertices.buffer.slice(..));
+            rp.set_vertex_buffer(1, output.slice(..));
+            if let Some(index_buffer) = &index_buffer {
+                rp.set_index_buffer(index_buffer.slice(..), wgpu::IndexFormat::Uint16);
+                rp.draw_indexed_indirect(&args, 0);
+            } else {
+                rp.draw_indirect(&args, 0);
+            }
+        });
+    }
+}
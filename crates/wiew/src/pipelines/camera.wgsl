@@ -0,0 +1,257 @@
+// ================================
+//         Camera Uniform
+// ================================
+//
+// Shared by all the pipelines that use the `ProjectionCameraCommon` bind group,
+// it is prepended to their source (see `pipelines::shader_with_globals`).
+
+struct CameraUniform {
+    view: mat4x4<f32>,
+    proj: mat4x4<f32>,
+    view_point: vec3<f32>,
+    light_dir: vec3<f32>,
+    // size of the render target in pixels
+    viewport_size: vec2<f32>,
+    // shadows of the camera light, see `shadow_factor`
+    light_view_proj: mat4x4<f32>,
+    shadow_bias: f32,
+    shadow_normal_bias: f32,
+    shadow_strength: f32,
+    shadow_pcf_radius: u32,
+    shadow_enabled: u32,
+    inv_view: mat4x4<f32>,
+    inv_proj: mat4x4<f32>,
+    // user clipping planes, see `clip_fragment`
+    clip_planes: array<vec4<f32>, MAX_CLIP_PLANES>,
+    clip_plane_count: u32,
+    // fog, see `apply_fog`
+    fog_mode: u32,
+    fog_density: f32,
+    fog_start: f32,
+    fog_end: f32,
+    fog_height: f32,
+    fog_height_falloff: f32,
+    fog_max_opacity: f32,
+    // the colors blended toward at the top left, top right, bottom left and bottom right corners
+    fog_colors: array<vec4<f32>, 4>,
+};
+
+const MAX_CLIP_PLANES: u32 = 6u;
+
+@group(0) @binding(0)
+var<uniform> camera: CameraUniform;
+@group(0) @binding(1)
+var shadow_map: texture_depth_2d;
+@group(0) @binding(2)
+var shadow_sampler: sampler_comparison;
+
+// ================================
+//            Shadows
+// ================================
+//
+// The shadow map is rendered from the camera light before the main pass
+// (see `ShadowMap`), it is a placeholder when `shadow_enabled` is 0.
+
+// Fraction of the camera light that reaches a point, with percentage-closer filtering
+fn shadow_factor(world_position: vec3<f32>, world_normal: vec3<f32>) -> f32 {
+    if (camera.shadow_enabled == 0u) {
+        return 1.0;
+    }
+
+    // moving the receiver along its normal avoids self-shadowing on slopes
+    let offset = normalize(world_normal) * camera.shadow_normal_bias;
+    let clip = camera.light_view_proj * vec4<f32>(world_position + offset, 1.0);
+    let ndc = clip.xyz / clip.w;
+    let uv = vec2<f32>(ndc.x * 0.5 + 0.5, 0.5 - ndc.y * 0.5);
+    let depth = ndc.z - camera.shadow_bias;
+
+    // outside of the shadow map
+    if (any(uv < vec2<f32>(0.0)) || any(uv > vec2<f32>(1.0)) || depth > 1.0) {
+        return 1.0;
+    }
+
+    let texel = 1.0 / vec2<f32>(textureDimensions(shadow_map));
+    let r = i32(camera.shadow_pcf_radius);
+    var lit = 0.0;
+    for (var y = -r; y <= r; y++) {
+        for (var x = -r; x <= r; x++) {
+            lit += textureSampleCompareLevel(shadow_map, shadow_sampler, uv + vec2<f32>(f32(x), f32(y)) * texel, depth);
+        }
+    }
+    lit /= f32((2 * r + 1) * (2 * r + 1));
+
+    return 1.0 - camera.shadow_strength * (1.0 - lit);
+}
+
+// ================================
+//         Clipping Planes
+// ================================
+//
+// Points `p` with `dot(plane.xyz, p) + plane.w < 0` are cut away (see `ClipPlane`),
+// the pipelines call `clip_fragment` with the world position of their fragments.
+
+fn is_clipped(world_position: vec3<f32>) -> bool {
+    for (var i = 0u; i < min(camera.clip_plane_count, MAX_CLIP_PLANES); i++) {
+        let plane = camera.clip_planes[i];
+        if (dot(plane.xyz, world_position) + plane.w < 0.0) {
+            return true;
+        }
+    }
+    return false;
+}
+
+// Discard the fragments that are cut away by the clipping planes
+fn clip_fragment(world_position: vec3<f32>) {
+    if (is_clipped(world_position)) {
+        discard;
+    }
+}
+
+// ================================
+//              Fog
+// ================================
+//
+// The fragments fade into the background with their distance to the camera
+// (see `FogSettings`), the pipelines call `apply_fog` on their output color.
+
+const FOG_NONE: u32 = 0u;
+const FOG_LINEAR: u32 = 1u;
+const FOG_EXPONENTIAL: u32 = 2u;
+const FOG_EXPONENTIAL_SQUARED: u32 = 3u;
+const FOG_HEIGHT: u32 = 4u;
+
+// Opacity of the fog in front of a point
+fn fog_factor(world_position: vec3<f32>) -> f32 {
+    let ray = world_position - camera.view_point;
+    let distance = length(ray);
+
+    var fog = 0.0;
+    switch camera.fog_mode {
+        case FOG_LINEAR: {
+            fog = clamp((distance - camera.fog_start) / max(camera.fog_end - camera.fog_start, 1e-6), 0.0, 1.0);
+        }
+        case FOG_EXPONENTIAL: {
+            fog = 1.0 - exp(-camera.fog_density * max(distance - camera.fog_start, 0.0));
+        }
+        case FOG_EXPONENTIAL_SQUARED: {
+            let d = camera.fog_density * max(distance - camera.fog_start, 0.0);
+            fog = 1.0 - exp(-d * d);
+        }
+        case FOG_HEIGHT: {
+            // the density decreases exponentially above `fog_height`, integrated along the ray
+            let falloff = max(camera.fog_height_falloff, 1e-6);
+            let density = camera.fog_density * exp(-falloff * (camera.view_point.y - camera.fog_height));
+            let dy = falloff * ray.y;
+            var integral = 1.0;
+            if (abs(dy) > 1e-4) {
+                integral = (1.0 - exp(-dy)) / dy;
+            }
+            fog = 1.0 - exp(-density * integral * max(distance - camera.fog_start, 0.0));
+        }
+        default: {}
+    }
+
+    return clamp(fog, 0.0, 1.0) * camera.fog_max_opacity;
+}
+
+// Color of the background behind a fragment, from its framebuffer coordinates
+fn fog_color(frag_coord: vec2<f32>) -> vec3<f32> {
+    let uv = clamp(frag_coord / camera.viewport_size, vec2<f32>(0.0), vec2<f32>(1.0));
+    let top = mix(camera.fog_colors[0].rgb, camera.fog_colors[1].rgb, uv.x);
+    let bottom = mix(camera.fog_colors[2].rgb, camera.fog_colors[3].rgb, uv.x);
+    return mix(top, bottom, uv.y);
+}
+
+// Blend the color of a fragment toward the background
+fn apply_fog(color: vec4<f32>, world_position: vec3<f32>, frag_coord: vec2<f32>) -> vec4<f32> {
+    if (camera.fog_mode == FOG_NONE) {
+        return color;
+    }
+    return vec4<f32>(mix(color.rgb, fog_color(frag_coord), fog_factor(world_position)), color.a);
+}
+
+// ================================
+//          Render Mode
+// ================================
+//
+// Set by the mesh pipelines according to the `RenderMode` of the pass
+// (see `pipelines::RenderModeState`).
+
+override RENDER_MODE: u32 = 0u;
+
+const RENDER_MODE_SHADED: u32 = 0u;
+const RENDER_MODE_WIREFRAME: u32 = 1u;
+const RENDER_MODE_SHADED_WITH_EDGES: u32 = 2u;
+const RENDER_MODE_XRAY: u32 = 3u;
+
+const EDGE_COLOR: vec3<f32> = vec3<f32>(0.05, 0.05, 0.05);
+const XRAY_OPACITY: f32 = 0.25;
+
+// Barycentric coordinates of a corner of a non-indexed triangle list. `vertex_index`
+// includes the first vertex of the draw, which needs not be a multiple of 3: the
+// corners of a triangle are consecutive, so they still get distinct coordinates.
+fn corner_barycentric(vertex_index: u32) -> vec3<f32> {
+    let i = vertex_index % 3u;
+    return vec3<f32>(f32(i == 0u), f32(i == 1u), f32(i == 2u));
+}
+
+// Coverage of the edges of the triangle, about one pixel wide
+fn edge_coverage(barycentric: vec3<f32>) -> f32 {
+    let d = fwidth(barycentric);
+    let a = smoothstep(vec3<f32>(0.0), d * 1.5, barycentric);
+    return 1.0 - min(min(a.x, a.y), a.z);
+}
+
+// Apply the render mode to the shaded color of a fragment
+fn apply_render_mode(color: vec4<f32>, barycentric: vec3<f32>) -> vec4<f32> {
+    // derivatives must be computed in uniform control flow
+    let edge = edge_coverage(barycentric);
+
+    switch RENDER_MODE {
+        case RENDER_MODE_WIREFRAME: {
+            if (edge <= 0.0) {
+                discard;
+            }
+            return vec4<f32>(color.rgb, color.a * edge);
+        }
+        case RENDER_MODE_SHADED_WITH_EDGES: {
+            return vec4<f32>(mix(color.rgb, EDGE_COLOR, edge), color.a);
+        }
+        case RENDER_MODE_XRAY: {
+            return vec4<f32>(color.rgb, color.a * XRAY_OPACITY);
+        }
+        default: {
+            return color;
+        }
+    }
+}
+
+// ================================
+//  Order-Independent Transparency
+// ================================
+//
+// Weighted blended OIT (McGuire and Bavoil, 2013): the `fs_oit` entry points
+// accumulate their color in the transparent pass of the view, which is then
+// composited onto the surface (see `pipelines::oit`).
+
+struct OitOutput {
+    // weighted premultiplied color, summed
+    @location(0) accum: vec4<f32>,
+    // alpha, the target is multiplied by `1 - alpha`
+    @location(1) revealage: f32,
+};
+
+// Output of a transparent fragment of the given color, at the given depth in [0, 1]
+fn oit_output(color: vec4<f32>, depth: f32) -> OitOutput {
+    // closer and more opaque fragments weigh more (equation 10 of the paper)
+    let weight = clamp(
+        pow(min(1.0, color.a * 10.0) + 0.01, 3.0) * 1e8 * pow(1.0 - depth * 0.9, 3.0),
+        1e-2,
+        3e3,
+    );
+
+    var out: OitOutput;
+    out.accum = vec4<f32>(color.rgb * color.a, color.a) * weight;
+    out.revealage = color.a;
+    return out;
+}
@@ -1,17 +1,3 @@
-
-// ================================
-//         Camera Uniform
-// ================================
-
-struct CameraUniform {
-    view: mat4x4<f32>,
-    proj: mat4x4<f32>,
-    view_position: vec3<f32>,
-};
-
-@group(0) @binding(0)
-var<uniform> camera: CameraUniform;
-
 // ================================
 //            Vertex
 // ================================
@@ -29,10 +15,14 @@ struct InstanceInput {
     //@location(4) model_4: vec3<f32>,
     //@location(5) model_5: vec3<f32>,
     //@location(6) model_6: vec3<f32>,
+    @location(13) color: vec4<f32>,
+    @location(14) object_id: u32,
+    @location(15) visible: u32,
 };
 
 @vertex
 fn vs_main(
+    @builtin(vertex_index) vertex_index: u32,
     model: VertexInput,
     instance: InstanceInput,
 ) -> VertexOutput {
@@ -43,22 +33,48 @@ fn vs_main(
         instance.model_3,
     );
 
+    let world_position = model_matrix * vec4<f32>(model.position, 1.0);
+
     var out: VertexOutput;
-    out.clip_position = camera.proj * camera.view * model_matrix * vec4<f32>(model.position, 1.0);
-    out.color = model.color;
+    out.clip_position = camera.proj * camera.view * world_position;
+    out.world_position = world_position.xyz;
+    out.color = model.color * instance.color;
+    out.object_id = instance.object_id;
+    out.barycentric = corner_barycentric(vertex_index);
+
+    // hidden instances are moved outside of the clip volume
+    if (instance.visible == 0u) {
+        out.clip_position = vec4<f32>(2.0, 2.0, 2.0, 1.0);
+    }
+
     return out;
 }
 
 struct VertexOutput {
     @builtin(position) clip_position: vec4<f32>,
     @location(0) color: vec4<f32>,
+    @location(1) @interpolate(flat) object_id: u32,
+    @location(2) barycentric: vec3<f32>,
+    @location(3) world_position: vec3<f32>,
 };
 
 // ================================
 //            Fragment
 // ================================
 
+fn shade(in: VertexOutput) -> vec4<f32> {
+    clip_fragment(in.world_position);
+
+    return apply_fog(apply_render_mode(in.color, in.barycentric), in.world_position, in.clip_position.xy);
+}
+
 @fragment
 fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
-    return in.color;
-}
\ No newline at end of file
+    return shade(in);
+}
+
+// transparent pass of the view, see `oit_output`
+@fragment
+fn fs_oit(in: VertexOutput) -> OitOutput {
+    return oit_output(shade(in), in.clip_position.z);
+}
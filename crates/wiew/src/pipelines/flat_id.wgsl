@@ -12,9 +12,12 @@ struct InstanceInput {
     @location(1) model_1: vec4<f32>,
     @location(2) model_2: vec4<f32>,
     @location(3) model_3: vec4<f32>,
-    //@location(4) model_3: vec3<f32>,
-    //@location(5) model_3: vec3<f32>,
-    //@location(6) model_3: vec3<f32>,
+    //@location(4) model_4: vec3<f32>,
+    //@location(5) model_5: vec3<f32>,
+    //@location(6) model_6: vec3<f32>,
+    @location(13) color: vec4<f32>,
+    @location(14) object_id: u32,
+    @location(15) visible: u32,
 };
 
 @vertex
@@ -31,13 +34,21 @@ fn vs_main(
 
     var out: VertexOutput;
     out.clip_position = model_matrix * vec4<f32>(model.position, 1.0);
-    out.color = model.color;
+    out.color = model.color * instance.color;
+    out.object_id = instance.object_id;
+
+    // hidden instances are moved outside of the clip volume
+    if (instance.visible == 0u) {
+        out.clip_position = vec4<f32>(2.0, 2.0, 2.0, 1.0);
+    }
+
     return out;
 }
 
 struct VertexOutput {
     @builtin(position) clip_position: vec4<f32>,
     @location(0) color: vec4<f32>,
+    @location(1) @interpolate(flat) object_id: u32,
 };
 
 // ================================
@@ -0,0 +1,309 @@
+use std::{collections::HashMap, mem, sync::Arc};
+
+use wgpu::util::DeviceExt;
+
+use crate::{instance::Instance3d, BoundingSphere, Pass, ProjectionCameraCommon, RenderContext, SingletonResource};
+
+use super::shader_with_globals;
+
+const WORKGROUP_SIZE: u32 = 64;
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct CullParams {
+    instance_count: u32,
+    draw_count: u32,
+    first: u32,
+    _padding: u32,
+}
+
+/// The bind group layout and the compute pipelines of the [`IndirectInstances`]
+pub struct IndirectCommon {
+    bind_group_layout: wgpu::BindGroupLayout,
+    reset_pipeline: wgpu::ComputePipeline,
+    cull_pipeline: wgpu::ComputePipeline,
+}
+
+impl SingletonResource for IndirectCommon {
+    fn init(ctx: &mut RenderContext) -> Self {
+        let storage = |binding, read_only| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::COMPUTE,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Storage { read_only },
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        };
+
+        let bind_group_layout = ctx.device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                // instances
+                storage(1, true),
+                // bounding spheres
+                storage(2, true),
+                // visible instances
+                storage(3, false),
+                // indirect draw arguments
+                storage(4, false),
+            ],
+            label: Some("indirect_bind_group_layout"),
+        });
+
+        let shader = shader_with_globals(ctx.device, "indirect.wgsl", include_str!("indirect.wgsl"));
+        let camera_common = ctx.singleton::<ProjectionCameraCommon>();
+
+        let pipeline_layout = ctx.device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: None,
+            bind_group_layouts: &[
+                camera_common.layout(),
+                &bind_group_layout,
+            ],
+            push_constant_ranges: &[],
+        });
+
+        // the instances are copied word by word
+        let constants = HashMap::from([
+            ("INSTANCE_WORDS".to_string(), (mem::size_of::<Instance3d>() / 4) as f64),
+            ("VISIBLE_WORD".to_string(), (mem::offset_of!(Instance3d, visible) / 4) as f64),
+        ]);
+
+        let create_pipeline = |label, entry_point| ctx.device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some(label),
+            layout: Some(&pipeline_layout),
+            module: &shader,
+            entry_point,
+            compilation_options: wgpu::PipelineCompilationOptions {
+                constants: &constants,
+                ..Default::default()
+            },
+            cache: None,
+        });
+
+        let reset_pipeline = create_pipeline("indirect reset pipeline", "cs_reset");
+        let cull_pipeline = create_pipeline("indirect cull pipeline", "cs_cull");
+
+        Self {
+            bind_group_layout,
+            reset_pipeline,
+            cull_pipeline,
+        }
+    }
+}
+
+impl IndirectCommon {
+    pub fn layout(&self) -> &wgpu::BindGroupLayout {
+        &self.bind_group_layout
+    }
+}
+
+/// Instances culled on the GPU, for indirect draws.
+///
+/// Each instance has a bounding sphere in world space. Before a pass draws them
+/// (see [`MeshPipeline::render_indirect`](super::builder::MeshPipeline::render_indirect)),
+/// a compute command of the pass (see [`Pass::defer_compute`]) copies the visible
+/// instances that intersect the view frustum of its camera to the front of
+/// [`IndirectInstances::output`], and writes their count into the arguments of
+/// [`wgpu::RenderPass::draw_indirect`] or [`wgpu::RenderPass::draw_indexed_indirect`].
+///
+/// # Remarks
+/// Each cull has its own parameters and arguments, so the instances can be culled by
+/// several passes of a frame, e.g. the shadow and the main passes or several views, and
+/// drawn with several meshes in a pass. The output is shared: the culls of a pass have
+/// the same camera, and the compute commands of a pass run right before its render
+/// commands. Compute shaders are not available with WebGL.
+pub struct IndirectInstances {
+    len: u32,
+    instances: wgpu::Buffer,
+    spheres: wgpu::Buffer,
+    output: Arc<wgpu::Buffer>,
+}
+
+impl IndirectInstances {
+    /// The `instances` with the world space bounding sphere of each of them
+    pub fn new(
+        cx: &mut RenderContext,
+        instances: &[Instance3d],
+        spheres: &[BoundingSphere],
+    ) -> Self {
+        assert_eq!(instances.len(), spheres.len(), "one bounding sphere per instance is expected");
+
+        // empty bindings are not allowed
+        let padded = |mut contents: Vec<u8>, size: usize| {
+            contents.resize(contents.len().max(size), 0);
+            contents
+        };
+
+        let instances_buffer = cx.device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("indirect instances buffer"),
+            contents: &padded(bytemuck::cast_slice(instances).to_vec(), mem::size_of::<Instance3d>()),
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let spheres_buffer = cx.device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("indirect spheres buffer"),
+            contents: &padded(bytemuck::cast_slice(&sphere_data(spheres)).to_vec(), mem::size_of::<[f32; 4]>()),
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let output = cx.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("indirect output buffer"),
+            size: (instances.len().max(1) * mem::size_of::<Instance3d>()) as u64,
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::STORAGE,
+            mapped_at_creation: false,
+        });
+
+        Self {
+            len: instances.len() as u32,
+            instances: instances_buffer,
+            spheres: spheres_buffer,
+            output: Arc::new(output),
+        }
+    }
+
+    /// The `instances` of a mesh bounded by `sphere` (in the space of the mesh)
+    pub fn from_bounds(
+        cx: &mut RenderContext,
+        instances: &[Instance3d],
+        sphere: &BoundingSphere,
+    ) -> Self {
+        Self::new(cx, instances, &instance_spheres(instances, sphere))
+    }
+
+    pub fn len(&self) -> u32 {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Replace the instances and their bounding spheres, there must be [`IndirectInstances::len`] of them
+    pub fn update(
+        &self,
+        queue: &wgpu::Queue,
+        instances: &[Instance3d],
+        spheres: &[BoundingSphere],
+    ) {
+        assert!(instances.len() == self.len as usize && spheres.len() == self.len as usize, "the number of instances cannot change");
+
+        queue.write_buffer(&self.instances, 0, bytemuck::cast_slice(instances));
+        queue.write_buffer(&self.spheres, 0, bytemuck::cast_slice(&sphere_data(spheres)));
+    }
+
+    /// Replace the instances of a mesh bounded by `sphere`, see [`IndirectInstances::from_bounds`]
+    pub fn update_from_bounds(
+        &self,
+        queue: &wgpu::Queue,
+        instances: &[Instance3d],
+        sphere: &BoundingSphere,
+    ) {
+        self.update(queue, instances, &instance_spheres(instances, sphere));
+    }
+
+    /// The visible instances, to bind as the instance vertex buffer
+    pub fn output(&self) -> &Arc<wgpu::Buffer> {
+        &self.output
+    }
+
+    /// Cull the instances with the camera of `pass`, before its render commands.
+    ///
+    /// Returns the arguments of the indirect draw, they draw `draw_count` vertices
+    /// from `first`, or indices when they are given to [`wgpu::RenderPass::draw_indexed_indirect`].
+    pub fn cull<'a>(
+        &self,
+        cx: &mut RenderContext,
+        pass: &mut Pass<'a>,
+        draw_count: u32,
+        first: u32,
+    ) -> Arc<wgpu::Buffer> {
+        let common = cx.singleton::<IndirectCommon>();
+
+        // queue writes land before the frame is submitted, a buffer per cull keeps
+        // the parameters of the other passes
+        let params = cx.device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("indirect params buffer"),
+            contents: bytemuck::cast_slice(&[CullParams {
+                instance_count: self.len,
+                draw_count,
+                first,
+                _padding: 0,
+            }]),
+            usage: wgpu::BufferUsages::UNIFORM,
+        });
+
+        // the other draws of the pass may cull for another mesh
+        let args = Arc::new(cx.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("indirect args buffer"),
+            size: mem::size_of::<wgpu::util::DrawIndexedIndirectArgs>() as u64,
+            usage: wgpu::BufferUsages::INDIRECT | wgpu::BufferUsages::STORAGE,
+            mapped_at_creation: false,
+        }));
+
+        let bind_group = cx.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: common.layout(),
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: params.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: self.instances.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: self.spheres.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: self.output.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 4,
+                    resource: args.as_entire_binding(),
+                },
+            ],
+            label: Some("indirect_bind_group"),
+        });
+        let workgroups = self.len.div_ceil(WORKGROUP_SIZE);
+
+        pass.defer_compute(move |cp, globals| {
+            cp.set_bind_group(0, globals, &[]);
+            cp.set_bind_group(1, &bind_group, &[]);
+
+            cp.set_pipeline(&common.reset_pipeline);
+            cp.dispatch_workgroups(1, 1, 1);
+
+            if workgroups > 0 {
+                cp.set_pipeline(&common.cull_pipeline);
+                cp.dispatch_workgroups(workgroups, 1, 1);
+            }
+        });
+
+        args
+    }
+}
+
+fn sphere_data(spheres: &[BoundingSphere]) -> Vec<[f32; 4]> {
+    spheres.iter()
+        .map(|sphere| [sphere.center.x, sphere.center.y, sphere.center.z, sphere.radius])
+        .collect()
+}
+
+fn instance_spheres(instances: &[Instance3d], sphere: &BoundingSphere) -> Vec<BoundingSphere> {
+    instances.iter()
+        .map(|instance| sphere.transformed(&nalgebra::Matrix4::from(instance.model)))
+        .collect()
+}
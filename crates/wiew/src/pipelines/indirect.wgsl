@@ -0,0 +1,100 @@
+// ================================
+//        Frustum culling
+// ================================
+//
+// The instances whose bounding sphere intersects the view frustum of the camera
+// are copied, word by word, to the front of `output`, and counted in the
+// `instance_count` of the indirect draw arguments.
+
+// number of 32 bits words of an instance, and index of its visibility flag
+override INSTANCE_WORDS: u32 = 31u;
+override VISIBLE_WORD: u32 = 30u;
+
+struct CullParams {
+    instance_count: u32,
+    // vertex count or index count of the draw
+    draw_count: u32,
+    // first vertex or first index of the draw
+    first: u32,
+    _padding: u32,
+};
+
+@group(1) @binding(0)
+var<uniform> params: CullParams;
+@group(1) @binding(1)
+var<storage, read> input: array<u32>;
+// world space center and radius of the instances
+@group(1) @binding(2)
+var<storage, read> spheres: array<vec4<f32>>;
+@group(1) @binding(3)
+var<storage, read_write> output: array<u32>;
+// `DrawIndirectArgs` or `DrawIndexedIndirectArgs`, `instance_count` is the second
+// word of both, the next ones are `0` but for the first vertex or index
+@group(1) @binding(4)
+var<storage, read_write> args: array<atomic<u32>, 5>;
+
+@compute @workgroup_size(1)
+fn cs_reset() {
+    atomicStore(&args[0], params.draw_count);
+    atomicStore(&args[1], 0u);
+    atomicStore(&args[2], params.first);
+    atomicStore(&args[3], 0u);
+    atomicStore(&args[4], 0u);
+}
+
+fn matrix_row(m: mat4x4<f32>, i: u32) -> vec4<f32> {
+    return vec4<f32>(m[0][i], m[1][i], m[2][i], m[3][i]);
+}
+
+// signed distance from a plane of the frustum, positive inside
+fn plane_distance(plane: vec4<f32>, p: vec3<f32>, radius: f32) -> f32 {
+    let len = length(plane.xyz);
+    // the far plane of an infinite projection
+    if (len < 1e-6) {
+        return radius;
+    }
+    return (dot(plane.xyz, p) + plane.w) / len;
+}
+
+fn in_frustum(sphere: vec4<f32>) -> bool {
+    let m = camera.proj * camera.view;
+    let r0 = matrix_row(m, 0u);
+    let r1 = matrix_row(m, 1u);
+    let r2 = matrix_row(m, 2u);
+    let r3 = matrix_row(m, 3u);
+
+    // the depth range is [0, 1]
+    var planes = array<vec4<f32>, 6>(
+        r3 + r0,
+        r3 - r0,
+        r3 + r1,
+        r3 - r1,
+        r2,
+        r3 - r2,
+    );
+
+    for (var i = 0u; i < 6u; i++) {
+        if (plane_distance(planes[i], sphere.xyz, sphere.w) < -sphere.w) {
+            return false;
+        }
+    }
+    return true;
+}
+
+@compute @workgroup_size(64)
+fn cs_cull(@builtin(global_invocation_id) id: vec3<u32>) {
+    let i = id.x;
+    if (i >= params.instance_count) {
+        return;
+    }
+
+    let first_word = i * INSTANCE_WORDS;
+    if (input[first_word + VISIBLE_WORD] == 0u || !in_frustum(spheres[i])) {
+        return;
+    }
+
+    let j = atomicAdd(&args[1], 1u);
+    for (var w = 0u; w < INSTANCE_WORDS; w++) {
+        output[j * INSTANCE_WORDS + w] = input[first_word + w];
+    }
+}
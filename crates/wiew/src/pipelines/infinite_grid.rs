@@ -0,0 +1,243 @@
+use std::sync::Arc;
+
+use wgpu::{util::DeviceExt, Device, PrimitiveState, PrimitiveTopology, ShaderModule};
+
+use crate::{GridSettings, Pass, PassKind, ProjectionCameraCommon, RenderContext, SingletonResource};
+
+use super::{shader_with_globals, Pipeline};
+
+/// A shader for the infinite ground grid
+pub struct InfiniteGridShader {
+    shader: ShaderModule,
+}
+
+impl InfiniteGridShader {
+    /// Create a new infinite grid shader
+    pub fn new(
+        device: &Device,
+    ) -> Self {
+        Self {
+            shader: shader_with_globals(device, "infinite_grid.wgsl", include_str!("infinite_grid.wgsl")),
+        }
+    }
+}
+
+impl SingletonResource for InfiniteGridShader {
+    fn init(ctx: &mut RenderContext) -> Self {
+        Self::new(ctx.device)
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct InfiniteGridUniform {
+    origin: [f32; 3],
+    spacing: f32,
+    u: [f32; 3],
+    minor_divisions: f32,
+    v: [f32; 3],
+    line_alpha: f32,
+    major_color: [f32; 4],
+    minor_color: [f32; 4],
+    u_axis_color: [f32; 4],
+    v_axis_color: [f32; 4],
+}
+
+impl From<&GridSettings> for InfiniteGridUniform {
+    fn from(settings: &GridSettings) -> Self {
+        let (u, v) = settings.plane.axes();
+        let [r, g, b] = settings.major_color;
+        let major_color = [r, g, b, 1.0];
+        let [r, g, b] = settings.minor_color;
+        let minor_color = [r, g, b, 1.0];
+
+        Self {
+            origin: settings.origin.into(),
+            spacing: settings.spacing.max(1e-6),
+            u: u.into(),
+            minor_divisions: settings.minor_divisions as f32,
+            v: v.into(),
+            line_alpha: settings.line_alpha,
+            major_color,
+            minor_color,
+            u_axis_color: settings.axis_colors[0],
+            v_axis_color: settings.axis_colors[1],
+        }
+    }
+}
+
+/// The bind group layout of [`InfiniteGridMaterial`]s
+pub struct InfiniteGridMaterialCommon {
+    bind_group_layout: wgpu::BindGroupLayout,
+}
+
+impl SingletonResource for InfiniteGridMaterialCommon {
+    fn init(ctx: &mut RenderContext) -> Self {
+        let bind_group_layout = ctx.device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                }
+            ],
+            label: Some("infinite_grid_material_bind_group_layout"),
+        });
+
+        Self {
+            bind_group_layout,
+        }
+    }
+}
+
+impl InfiniteGridMaterialCommon {
+    pub fn layout(&self) -> &wgpu::BindGroupLayout {
+        &self.bind_group_layout
+    }
+}
+
+/// The GPU side of [`GridSettings`], bound as `@group(1)` by [`InfiniteGridPipeline`]
+pub struct InfiniteGridMaterial {
+    buffer: wgpu::Buffer,
+    bind_group: Arc<wgpu::BindGroup>,
+}
+
+impl InfiniteGridMaterial {
+    pub fn new(
+        cx: &mut RenderContext,
+        settings: &GridSettings,
+    ) -> Self {
+        let common = cx.singleton::<InfiniteGridMaterialCommon>();
+
+        let buffer = cx.device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("infinite grid material buffer"),
+            contents: bytemuck::cast_slice(&[InfiniteGridUniform::from(settings)]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let bind_group = cx.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: common.layout(),
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: buffer.as_entire_binding(),
+                }
+            ],
+            label: Some("infinite_grid_material_bind_group"),
+        });
+
+        Self {
+            buffer,
+            bind_group: Arc::new(bind_group),
+        }
+    }
+
+    pub fn update(&self, queue: &wgpu::Queue, settings: &GridSettings) {
+        queue.write_buffer(&self.buffer, 0, bytemuck::cast_slice(&[InfiniteGridUniform::from(settings)]));
+    }
+}
+
+/// Draws a grid on a whole plane with a full screen triangle.
+///
+/// The view rays are intersected with the plane of the [`GridSettings`], the spacing
+/// of the lines is multiplied by a power of [`GridSettings::minor_divisions`] chosen
+/// from the distance of the camera and the lines fade out with the distance.
+pub struct InfiniteGridPipeline {
+    pipeline: Pipeline,
+}
+
+impl Default for InfiniteGridPipeline {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl InfiniteGridPipeline {
+    pub fn new() -> Self {
+        let primitive = PrimitiveState {
+            topology: PrimitiveTopology::TriangleList,
+            ..Default::default()
+        };
+
+        let pipeline = Pipeline::from_builder(move |cx, formats| {
+            let shader = cx.singleton::<InfiniteGridShader>();
+
+            let camera_common = cx.singleton::<ProjectionCameraCommon>();
+            let material_common = cx.singleton::<InfiniteGridMaterialCommon>();
+
+            let render_pipeline_layout =
+            cx.device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: None,
+                bind_group_layouts: &[
+                    camera_common.layout(),
+                    material_common.layout(),
+                ],
+                push_constant_ranges: &[],
+            });
+
+            let targets = formats.color_targets(wgpu::BlendState::ALPHA_BLENDING);
+
+            cx.device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some("infinite grid pipeline"),
+                layout: Some(&render_pipeline_layout),
+                vertex: wgpu::VertexState {
+                    module: &shader.shader,
+                    entry_point: "vs_main",
+                    buffers: &[],
+                    compilation_options: Default::default(),
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &shader.shader,
+                    entry_point: "fs_main",
+                    targets: &targets,
+                    compilation_options: Default::default(),
+                }),
+                primitive,
+                // the depth of the plane is written by the fragment shader, the lines
+                // are hidden by the objects but do not hide them
+                depth_stencil: formats.depth_format.map(|format| wgpu::DepthStencilState {
+                    format,
+                    depth_write_enabled: false,
+                    depth_compare: wgpu::CompareFunction::LessEqual,
+                    stencil: wgpu::StencilState::default(),
+                    bias: wgpu::DepthBiasState::default(),
+                }),
+                multisample: Default::default(),
+                multiview: None,
+                cache: None,
+            })
+        });
+
+        Self {
+            pipeline,
+        }
+    }
+
+    /// Draw the grid, only in the main pass
+    pub fn render<'a>(
+        &self,
+        cx: &mut RenderContext,
+        pass: &mut Pass<'a>,
+        material: &InfiniteGridMaterial,
+    ) {
+        if pass.kind() != PassKind::Main {
+            return;
+        }
+
+        let bind_group = material.bind_group.clone();
+
+        let pipeline = self.pipeline.get(cx, pass);
+
+        pass.defer(move |rp, globals| {
+            rp.set_pipeline(&pipeline);
+            rp.set_bind_group(0, globals, &[]);
+            rp.set_bind_group(1, &bind_group, &[]);
+            rp.draw(0..3, 0..1);
+        });
+    }
+}
@@ -0,0 +1,125 @@
+// ================================
+//            Grid
+// ================================
+
+// see `GridSettings`
+struct Grid {
+    origin: vec3<f32>,
+    spacing: f32,
+    // orthonormal axes of the plane
+    u: vec3<f32>,
+    minor_divisions: f32,
+    v: vec3<f32>,
+    line_alpha: f32,
+    major_color: vec4<f32>,
+    minor_color: vec4<f32>,
+    u_axis_color: vec4<f32>,
+    v_axis_color: vec4<f32>,
+};
+
+@group(1) @binding(0)
+var<uniform> grid: Grid;
+
+// ================================
+//            Vertex
+// ================================
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) ndc: vec2<f32>,
+};
+
+// a triangle covering the whole screen
+@vertex
+fn vs_main(@builtin(vertex_index) vertex_index: u32) -> VertexOutput {
+    let uv = vec2<f32>(f32((vertex_index << 1u) & 2u), f32(vertex_index & 2u));
+    let ndc = uv * 2.0 - 1.0;
+
+    var out: VertexOutput;
+    out.clip_position = vec4<f32>(ndc, 0.0, 1.0);
+    out.ndc = ndc;
+    return out;
+}
+
+// ================================
+//            Fragment
+// ================================
+
+// lines are faded out between these distances, in major cells
+const FADE_START: f32 = 10.0;
+const FADE_END: f32 = 40.0;
+
+struct FragmentOutput {
+    @location(0) color: vec4<f32>,
+    @builtin(frag_depth) depth: f32,
+};
+
+fn unproject(ndc: vec3<f32>) -> vec3<f32> {
+    let p = camera.inv_view * camera.inv_proj * vec4<f32>(ndc, 1.0);
+    return p.xyz / p.w;
+}
+
+// coverage of the lines every `spacing` units, anti-aliased over a pixel
+fn lines(coords: vec2<f32>, spacing: f32) -> f32 {
+    let c = coords / spacing;
+    let width = fwidth(c);
+    let distance = abs(fract(c - 0.5) - 0.5) / width;
+    // lines closer than a few pixels would just be noise
+    let density = smoothstep(0.25, 0.5, max(width.x, width.y));
+    return (1.0 - min(min(distance.x, distance.y), 1.0)) * (1.0 - density);
+}
+
+// coverage of a line of 1.5 pixels where `d` is 0
+fn axis(d: f32) -> f32 {
+    return 1.0 - min(abs(d) / (1.5 * fwidth(d)), 1.0);
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> FragmentOutput {
+    // intersection of the view ray with the plane of the grid
+    let normal = cross(grid.v, grid.u);
+    let near = unproject(vec3<f32>(in.ndc, 0.0));
+    let far = unproject(vec3<f32>(in.ndc, 1.0));
+    let ray = far - near;
+    let denominator = dot(ray, normal);
+    let t = dot(grid.origin - near, normal) / denominator;
+    let p = near + t * ray;
+    let hit = t > 0.0 && abs(denominator) > 1e-6;
+
+    // powers of the divisions from the distance of the camera, the minor lines of the
+    // next level fade in as the camera moves closer
+    let divisions = max(grid.minor_divisions, 2.0);
+    let height = max(abs(dot(camera.view_point - grid.origin, normal)) / grid.spacing, 1e-4);
+    let level = log2(height) / log2(divisions);
+    let major = grid.spacing * pow(divisions, floor(level));
+    let minor = major / divisions;
+    let minor_fade = 1.0 - fract(level);
+
+    let coords = vec2<f32>(dot(p - grid.origin, grid.u), dot(p - grid.origin, grid.v));
+    let major_lines = lines(coords, major);
+    let minor_lines = lines(coords, minor) * minor_fade;
+
+    var color = vec4<f32>(grid.minor_color.rgb, grid.line_alpha * minor_lines);
+    color = mix(color, vec4<f32>(grid.major_color.rgb, grid.line_alpha), major_lines);
+
+    // the positive half of the axes
+    color = mix(color, grid.u_axis_color, axis(coords.y) * f32(coords.x > 0.0));
+    color = mix(color, grid.v_axis_color, axis(coords.x) * f32(coords.y > 0.0));
+
+    let distance = length(p - camera.view_point) / major;
+    color.a *= 1.0 - smoothstep(FADE_START, FADE_END, distance);
+    // the lines disappear in the fog
+    color.a *= 1.0 - fog_factor(p);
+
+    // `fwidth` must be evaluated in uniform control flow, so the misses are discarded last
+    if (!hit || color.a <= 0.0) {
+        discard;
+    }
+
+    let clip = camera.proj * camera.view * vec4<f32>(p, 1.0);
+
+    var out: FragmentOutput;
+    out.color = color;
+    out.depth = clip.z / clip.w;
+    return out;
+}
@@ -0,0 +1,555 @@
+use std::sync::Arc;
+
+use wgpu::{util::DeviceExt, Device, PrimitiveState, PrimitiveTopology, ShaderModule};
+
+use crate::{decl_vertex_raw_repr, instance::Instance3d, Pass, PassKind, ProjectionCameraCommon, RenderContext, Res, SingletonResource, VertexBuffer, VertexBufferSlice, VertexRawRepr};
+
+use super::{flat::{self, FlatPipeline}, shader_with_globals, Pipeline};
+
+decl_vertex_raw_repr! {
+    /// One corner of the quad of a line segment.
+    ///
+    /// Every segment is made of 6 vertices (two triangles), they are built
+    /// from polylines with [`Vertex::from_strip`] and [`Vertex::from_segments`].
+    #[derive(Debug)]
+    struct Vertex (Vertex step mode) {
+        /// The segment end this corner belongs to
+        pub position: [f32; 3] as [7 => Float32x3],
+        /// The other end of the segment
+        pub other: [f32; 3] as [8 => Float32x3],
+        /// The previous (for the start) or next (for the end) point of the polyline, used for joins
+        pub neighbor: [f32; 3] as [9 => Float32x3],
+        pub color: [f32; 4] as [10 => Float32x4],
+        /// Side (-1 or 1), end (0 or 1), distance along the polyline and width factor
+        pub params: [f32; 4] as [11 => Float32x4],
+        /// Whether this end (bit 0) and the other end (bit 1) have a neighbor
+        pub flags: u32 as [12 => Uint32],
+    }
+}
+
+/// A point of a polyline
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LinePoint {
+    pub position: [f32; 3],
+    pub color: [f32; 4],
+    /// Multiplies [`LineStyle::width`]
+    pub width: f32,
+}
+
+impl LinePoint {
+    pub const fn new(position: [f32; 3], color: [f32; 4]) -> Self {
+        Self {
+            position,
+            color,
+            width: 1.0,
+        }
+    }
+
+    pub const fn with_width(self, width: f32) -> Self {
+        Self {
+            width,
+            ..self
+        }
+    }
+}
+
+impl From<flat::Vertex> for LinePoint {
+    fn from(vertex: flat::Vertex) -> Self {
+        Self::new(vertex.position, vertex.color)
+    }
+}
+
+impl Vertex {
+    /// The vertices of a connected polyline, the segments are joined according to [`LineStyle::join`].
+    ///
+    /// If `closed` is true, the last point is connected to the first one.
+    pub fn from_strip(points: &[LinePoint], closed: bool) -> Vec<Vertex> {
+        let n = points.len();
+        if n < 2 {
+            return Vec::new();
+        }
+
+        let segments = if closed { n } else { n - 1 };
+        let mut vertices = Vec::with_capacity(segments * 6);
+        let mut distance = 0.0;
+
+        for i in 0..segments {
+            let a = &points[i];
+            let b = &points[(i + 1) % n];
+
+            let prev = if i > 0 || closed {
+                Some(points[(i + n - 1) % n].position)
+            } else {
+                None
+            };
+            let next = if i + 2 < n || closed {
+                Some(points[(i + 2) % n].position)
+            } else {
+                None
+            };
+
+            let length = dist(a.position, b.position);
+            push_segment(&mut vertices, a, b, prev, next, distance, distance + length);
+            distance += length;
+        }
+
+        vertices
+    }
+
+    /// The vertices of independent segments, each pair of points is a segment
+    /// (like [`PrimitiveTopology::LineList`]).
+    pub fn from_segments(points: &[LinePoint]) -> Vec<Vertex> {
+        let mut vertices = Vec::with_capacity(points.len() / 2 * 6);
+
+        for pair in points.chunks_exact(2) {
+            let length = dist(pair[0].position, pair[1].position);
+            push_segment(&mut vertices, &pair[0], &pair[1], None, None, 0.0, length);
+        }
+
+        vertices
+    }
+}
+
+fn dist(a: [f32; 3], b: [f32; 3]) -> f32 {
+    ((b[0] - a[0]).powi(2) + (b[1] - a[1]).powi(2) + (b[2] - a[2]).powi(2)).sqrt()
+}
+
+fn push_segment(
+    vertices: &mut Vec<Vertex>,
+    a: &LinePoint,
+    b: &LinePoint,
+    prev: Option<[f32; 3]>,
+    next: Option<[f32; 3]>,
+    distance_a: f32,
+    distance_b: f32,
+) {
+    // (end, side) of the two triangles
+    const CORNERS: [(bool, f32); 6] = [
+        (false, -1.0), (false, 1.0), (true, -1.0),
+        (true, -1.0), (false, 1.0), (true, 1.0),
+    ];
+
+    for (is_end, side) in CORNERS {
+        let (this, other, neighbor, distance) = if is_end {
+            (b, a, next, distance_b)
+        } else {
+            (a, b, prev, distance_a)
+        };
+        let other_neighbor = if is_end { prev } else { next };
+
+        vertices.push(Vertex {
+            position: this.position,
+            other: other.position,
+            neighbor: neighbor.unwrap_or(this.position),
+            color: this.color,
+            params: [side, is_end as u32 as f32, distance, this.width],
+            flags: neighbor.is_some() as u32 | (other_neighbor.is_some() as u32) << 1,
+        });
+    }
+}
+
+/// A shader for thick lines
+pub struct LineShader {
+    shader: ShaderModule,
+}
+
+impl LineShader {
+    /// Create a new line shader
+    pub fn new(
+        device: &Device,
+    ) -> Self {
+        Self {
+            shader: shader_with_globals(device, "line.wgsl", include_str!("line.wgsl")),
+        }
+    }
+}
+
+impl SingletonResource for LineShader {
+    fn init(ctx: &mut RenderContext) -> Self {
+        Self::new(ctx.device)
+    }
+}
+
+/// The width of a line
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LineWidth {
+    /// Constant width on screen
+    Pixels(f32),
+    /// Width in world units, lines get thinner as they go away from the camera
+    World(f32),
+}
+
+/// How consecutive segments of a strip are connected
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LineJoin {
+    /// Sharp corners, limited by [`LineStyle::miter_limit`]
+    Miter,
+    Round,
+}
+
+/// How the ends of a line are drawn
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LineCap {
+    /// The line stops at its end point
+    Butt,
+    /// The line is extended by half its width
+    Square,
+    Round,
+}
+
+/// A dash pattern, in the units of the vertex positions
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LineDash {
+    pub dash: f32,
+    pub gap: f32,
+}
+
+/// Appearance of the lines drawn by [`LinePipeline`]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LineStyle {
+    pub width: LineWidth,
+    pub join: LineJoin,
+    pub cap: LineCap,
+    /// Maximum length of a miter, relative to the half width of the line
+    pub miter_limit: f32,
+    pub dash: Option<LineDash>,
+}
+
+impl Default for LineStyle {
+    fn default() -> Self {
+        Self {
+            width: LineWidth::Pixels(2.0),
+            join: LineJoin::Miter,
+            cap: LineCap::Butt,
+            miter_limit: 4.0,
+            dash: None,
+        }
+    }
+}
+
+impl LineStyle {
+    pub fn pixels(width: f32) -> Self {
+        Self {
+            width: LineWidth::Pixels(width),
+            ..Default::default()
+        }
+    }
+
+    pub fn world(width: f32) -> Self {
+        Self {
+            width: LineWidth::World(width),
+            ..Default::default()
+        }
+    }
+
+    pub fn with_join(self, join: LineJoin) -> Self {
+        Self { join, ..self }
+    }
+
+    pub fn with_cap(self, cap: LineCap) -> Self {
+        Self { cap, ..self }
+    }
+
+    pub fn with_dash(self, dash: f32, gap: f32) -> Self {
+        Self { dash: Some(LineDash { dash, gap }), ..self }
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct LineStyleUniform {
+    width: f32,
+    width_unit: u32,
+    join: u32,
+    cap: u32,
+    miter_limit: f32,
+    dash_length: f32,
+    gap_length: f32,
+    _padding: f32,
+}
+
+impl From<&LineStyle> for LineStyleUniform {
+    fn from(style: &LineStyle) -> Self {
+        let (width, width_unit) = match style.width {
+            LineWidth::Pixels(width) => (width, 0),
+            LineWidth::World(width) => (width, 1),
+        };
+        let (dash_length, gap_length) = style.dash
+            .map(|d| (d.dash, d.gap))
+            .unwrap_or((0.0, 0.0));
+
+        Self {
+            width,
+            width_unit,
+            join: match style.join {
+                LineJoin::Miter => 0,
+                LineJoin::Round => 1,
+            },
+            cap: match style.cap {
+                LineCap::Butt => 0,
+                LineCap::Square => 1,
+                LineCap::Round => 2,
+            },
+            miter_limit: style.miter_limit,
+            dash_length,
+            gap_length,
+            _padding: 0.0,
+        }
+    }
+}
+
+/// The bind group layout of [`LineMaterial`]s
+pub struct LineMaterialCommon {
+    bind_group_layout: wgpu::BindGroupLayout,
+}
+
+impl SingletonResource for LineMaterialCommon {
+    fn init(ctx: &mut RenderContext) -> Self {
+        let bind_group_layout = ctx.device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                }
+            ],
+            label: Some("line_material_bind_group_layout"),
+        });
+
+        Self {
+            bind_group_layout,
+        }
+    }
+}
+
+impl LineMaterialCommon {
+    pub fn layout(&self) -> &wgpu::BindGroupLayout {
+        &self.bind_group_layout
+    }
+}
+
+/// The GPU side of [`LineStyle`], bound as `@group(1)` by [`LinePipeline`]
+pub struct LineMaterial {
+    buffer: wgpu::Buffer,
+    bind_group: Arc<wgpu::BindGroup>,
+}
+
+impl LineMaterial {
+    pub fn new(
+        cx: &mut RenderContext,
+        style: &LineStyle,
+    ) -> Self {
+        let common = cx.singleton::<LineMaterialCommon>();
+
+        let buffer = cx.device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("line material buffer"),
+            contents: bytemuck::cast_slice(&[LineStyleUniform::from(style)]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let bind_group = cx.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: common.layout(),
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: buffer.as_entire_binding(),
+                }
+            ],
+            label: Some("line_material_bind_group"),
+        });
+
+        Self {
+            buffer,
+            bind_group: Arc::new(bind_group),
+        }
+    }
+
+    pub fn update(&self, queue: &wgpu::Queue, style: &LineStyle) {
+        queue.write_buffer(&self.buffer, 0, bytemuck::cast_slice(&[LineStyleUniform::from(style)]));
+    }
+}
+
+/// Thick, antialiased lines.
+///
+/// Segments are expanded into quads in the vertex shader, so the width does not
+/// depend on the support of wide lines by the backend.
+pub struct LinePipeline {
+    pipeline: Pipeline,
+    default_material: Res<LineMaterial>,
+}
+
+impl LinePipeline {
+    pub fn new(
+        depth_compare: wgpu::CompareFunction,
+        use_depth_stencil: bool,
+    ) -> Self {
+        let primitive = PrimitiveState {
+            topology: PrimitiveTopology::TriangleList,
+            ..Default::default()
+        };
+
+        let pipeline = Pipeline::from_builder(move |cx, formats| {
+            let shader = cx.singleton::<LineShader>();
+
+            let camera_common = cx.singleton::<ProjectionCameraCommon>();
+            let material_common = cx.singleton::<LineMaterialCommon>();
+
+            let render_pipeline_layout =
+            cx.device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: None,
+                bind_group_layouts: &[
+                    camera_common.layout(),
+                    material_common.layout(),
+                ],
+                push_constant_ranges: &[],
+            });
+
+            let targets = formats.color_targets(wgpu::BlendState::ALPHA_BLENDING);
+
+            cx.device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some("line pipeline"),
+                layout: Some(&render_pipeline_layout),
+                vertex: wgpu::VertexState {
+                    module: &shader.shader,
+                    entry_point: "vs_main",
+                    buffers: &[
+                        Vertex::desc(),
+                        Instance3d::desc(),
+                    ],
+                    compilation_options: Default::default(),
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &shader.shader,
+                    entry_point: formats.fragment_entry_point(),
+                    targets: &targets,
+                    compilation_options: Default::default(),
+                }),
+                primitive,
+                depth_stencil: formats.depth_format.map(|format| wgpu::DepthStencilState {
+                    format,
+                    depth_write_enabled: use_depth_stencil,
+                    depth_compare,
+                    stencil: formats.stencil(),
+                    bias: formats.depth_bias(),
+                }),
+                multisample: Default::default(),
+                multiview: None,
+                cache: None,
+            })
+        });
+
+        Self {
+            pipeline,
+            default_material: Res::new(|cx: &mut RenderContext| LineMaterial::new(cx, &LineStyle::default())),
+        }
+    }
+
+    /// Render with the default [`LineStyle`]
+    pub fn render<'a>(
+        &self,
+        cx: &mut RenderContext,
+        pass: &mut Pass<'a>,
+        vertices: impl Into<VertexBufferSlice<Vertex>>,
+        instances: impl Into<VertexBufferSlice<Instance3d>>,
+    ) {
+        let material = cx.resource(&self.default_material);
+        self.render_with_material(cx, pass, vertices, instances, &material);
+    }
+
+    pub fn render_with_material<'a>(
+        &self,
+        cx: &mut RenderContext,
+        pass: &mut Pass<'a>,
+        vertices: impl Into<VertexBufferSlice<Vertex>>,
+        instances: impl Into<VertexBufferSlice<Instance3d>>,
+        material: &LineMaterial,
+    ) {
+        // lines do not bound a volume, they have no section faces
+        if pass.kind() == PassKind::Section {
+            return;
+        }
+
+        let vertices: VertexBufferSlice<Vertex> = vertices.into();
+        let instances: VertexBufferSlice<Instance3d> = instances.into();
+        let material = material.bind_group.clone();
+
+        let pipeline = self.pipeline.get(cx, pass);
+
+        pass.defer(move |rp, globals| {
+            rp.set_pipeline(&pipeline);
+            rp.set_bind_group(0, globals, &[]);
+            rp.set_bind_group(1, &material, &[]);
+            rp.set_vertex_buffer(0, vertices.buffer.slice(..));
+            rp.set_vertex_buffer(1, instances.buffer.slice(..));
+            rp.draw(vertices.range.clone(), instances.range.clone());
+        });
+    }
+}
+
+/// Line segments drawn either as hairlines or with a [`LinePipeline`]
+pub(crate) enum Lines {
+    Hairline {
+        vertex_buffer: VertexBuffer<flat::Vertex>,
+        pipeline: FlatPipeline,
+    },
+    Thick {
+        vertex_buffer: VertexBuffer<Vertex>,
+        material: LineMaterial,
+        pipeline: LinePipeline,
+    },
+}
+
+impl Lines {
+    /// `vertices` are pairs of segment ends, as for [`PrimitiveTopology::LineList`]
+    pub(crate) fn new(
+        cx: &mut RenderContext,
+        vertices: &[flat::Vertex],
+        style: Option<&LineStyle>,
+        depth_compare: wgpu::CompareFunction,
+        use_depth_stencil: bool,
+    ) -> Self {
+        match style {
+            None => Lines::Hairline {
+                vertex_buffer: VertexBuffer::from_slice(cx.device, vertices, None),
+                pipeline: FlatPipeline::new(PrimitiveTopology::LineList, depth_compare, use_depth_stencil),
+            },
+            Some(style) => {
+                let points = vertices.iter().map(|v| LinePoint::from(*v)).collect::<Vec<_>>();
+
+                Lines::Thick {
+                    vertex_buffer: VertexBuffer::from_slice(cx.device, &Vertex::from_segments(&points), None),
+                    material: LineMaterial::new(cx, style),
+                    pipeline: LinePipeline::new(depth_compare, use_depth_stencil),
+                }
+            },
+        }
+    }
+
+    pub(crate) fn render(
+        &self,
+        cx: &mut RenderContext,
+        pass: &mut Pass,
+        instances: &VertexBuffer<Instance3d>,
+    ) {
+        match self {
+            Lines::Hairline { vertex_buffer, pipeline } => pipeline.render(
+                cx,
+                pass,
+                vertex_buffer.slice(..),
+                instances.slice(..),
+            ),
+            Lines::Thick { vertex_buffer, material, pipeline } => pipeline.render_with_material(
+                cx,
+                pass,
+                vertex_buffer.slice(..),
+                instances.slice(..),
+                material,
+            ),
+        }
+    }
+}
@@ -0,0 +1,271 @@
+// ================================
+//             Style
+// ================================
+
+const WIDTH_PIXELS: u32 = 0u;
+const WIDTH_WORLD: u32 = 1u;
+
+const JOIN_MITER: u32 = 0u;
+const JOIN_ROUND: u32 = 1u;
+
+const CAP_BUTT: u32 = 0u;
+const CAP_SQUARE: u32 = 1u;
+const CAP_ROUND: u32 = 2u;
+
+struct LineStyle {
+    width: f32,
+    width_unit: u32,
+    join: u32,
+    cap: u32,
+    miter_limit: f32,
+    dash_length: f32,
+    gap_length: f32,
+    _padding: f32,
+};
+
+@group(1) @binding(0)
+var<uniform> style: LineStyle;
+
+// extra width given to the quads for antialiasing, in pixels
+const AA_MARGIN: f32 = 1.0;
+
+// ================================
+//            Vertex
+// ================================
+
+// bits of `VertexInput::flags`
+const FLAG_NEIGHBOR: u32 = 1u;
+const FLAG_OTHER_NEIGHBOR: u32 = 2u;
+
+struct VertexInput {
+    // the segment end this vertex belongs to
+    @location(7) position: vec3<f32>,
+    // the other end of the segment
+    @location(8) other: vec3<f32>,
+    // the point before `position` (start) or after it (end) on the polyline, if any
+    @location(9) neighbor: vec3<f32>,
+    @location(10) color: vec4<f32>,
+    // x: side (-1 or 1), y: end (0 for the start, 1 for the end),
+    // z: distance along the polyline, w: width factor
+    @location(11) params: vec4<f32>,
+    @location(12) flags: u32,
+};
+
+struct InstanceInput {
+    @location(0) model_0: vec4<f32>,
+    @location(1) model_1: vec4<f32>,
+    @location(2) model_2: vec4<f32>,
+    @location(3) model_3: vec4<f32>,
+    @location(4) model_inv_tr_0: vec3<f32>,
+    @location(5) model_inv_tr_1: vec3<f32>,
+    @location(6) model_inv_tr_2: vec3<f32>,
+    @location(13) color: vec4<f32>,
+    @location(14) object_id: u32,
+    @location(15) visible: u32,
+};
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) color: vec4<f32>,
+    @location(1) distance: f32,
+    // start and end of the segment, in pixels
+    @location(2) @interpolate(flat) segment: vec4<f32>,
+    // half widths at the start and at the end, in pixels
+    @location(3) @interpolate(flat) half_widths: vec2<f32>,
+    // bit 0: round start, bit 1: round end
+    @location(4) @interpolate(flat) round_ends: u32,
+    // interpolated between the ends of the segment
+    @location(5) world_position: vec3<f32>,
+};
+
+// Move `p` along the segment towards `q` until it is in front of the near plane
+fn clip_near(p: vec4<f32>, q: vec4<f32>) -> vec4<f32> {
+    if (p.z < 0.0 && q.z > 0.0) {
+        return mix(p, q, p.z / (p.z - q.z));
+    }
+    return p;
+}
+
+fn to_screen(p: vec4<f32>) -> vec2<f32> {
+    return (p.xy / p.w * 0.5 + 0.5) * camera.viewport_size;
+}
+
+fn half_width(p: vec4<f32>, factor: f32) -> f32 {
+    let width = style.width * factor;
+    if (style.width_unit == WIDTH_WORLD) {
+        // projected size of `width` at the depth of `p`
+        return 0.25 * width * camera.proj[1][1] * camera.viewport_size.y / p.w;
+    }
+    return 0.5 * width;
+}
+
+fn perp(v: vec2<f32>) -> vec2<f32> {
+    return vec2<f32>(-v.y, v.x);
+}
+
+fn safe_normalize(v: vec2<f32>, fallback: vec2<f32>) -> vec2<f32> {
+    let l = length(v);
+    if (l < 1e-6) {
+        return fallback;
+    }
+    return v / l;
+}
+
+// Whether the end of a segment is rounded
+fn is_round(has_neighbor: bool) -> bool {
+    if (has_neighbor) {
+        return style.join == JOIN_ROUND;
+    }
+    return style.cap == CAP_ROUND;
+}
+
+// Whether the quad must be extended past the end of a segment
+fn is_extended(has_neighbor: bool) -> bool {
+    if (has_neighbor) {
+        return style.join == JOIN_ROUND;
+    }
+    return style.cap != CAP_BUTT;
+}
+
+@vertex
+fn vs_main(
+    model: VertexInput,
+    instance: InstanceInput,
+) -> VertexOutput {
+    let model_matrix = mat4x4<f32>(
+        instance.model_0,
+        instance.model_1,
+        instance.model_2,
+        instance.model_3,
+    );
+    let view_proj = camera.proj * camera.view * model_matrix;
+
+    let side = model.params.x;
+    let is_end = model.params.y > 0.5;
+    let has_neighbor = (model.flags & FLAG_NEIGHBOR) != 0u;
+    let other_has_neighbor = (model.flags & FLAG_OTHER_NEIGHBOR) != 0u;
+
+    let this_clip = view_proj * vec4<f32>(model.position, 1.0);
+    let other_clip = view_proj * vec4<f32>(model.other, 1.0);
+    let p = clip_near(this_clip, other_clip);
+    let q = clip_near(other_clip, this_clip);
+
+    let p_screen = to_screen(p);
+    let q_screen = to_screen(q);
+
+    var a = p_screen;
+    var b = q_screen;
+    if (is_end) {
+        a = q_screen;
+        b = p_screen;
+    }
+
+    let dir = safe_normalize(b - a, vec2<f32>(1.0, 0.0));
+    let normal = perp(dir);
+    let hw = half_width(p, model.params.w);
+    let hw_aa = hw + AA_MARGIN;
+
+    var offset = side * normal * hw_aa;
+
+    if (has_neighbor && style.join == JOIN_MITER) {
+        let neighbor_screen = to_screen(clip_near(view_proj * vec4<f32>(model.neighbor, 1.0), p));
+
+        // direction of the adjacent segment, oriented like this one
+        var neighbor_dir = safe_normalize(p_screen - neighbor_screen, dir);
+        if (is_end) {
+            neighbor_dir = safe_normalize(neighbor_screen - p_screen, dir);
+        }
+
+        let tangent = safe_normalize(dir + neighbor_dir, dir);
+        let miter = perp(tangent);
+        let cos_half_angle = dot(miter, normal);
+        let miter_length = min(hw_aa / max(cos_half_angle, 1e-3), hw_aa * style.miter_limit);
+
+        offset = side * miter * miter_length;
+    } else if (is_extended(has_neighbor)) {
+        var along = -dir;
+        if (is_end) {
+            along = dir;
+        }
+        offset += along * hw_aa;
+    }
+
+    let screen = p_screen + offset;
+    let ndc = screen / camera.viewport_size * 2.0 - 1.0;
+
+    var out: VertexOutput;
+    out.clip_position = vec4<f32>(ndc * p.w, p.z, p.w);
+    out.color = model.color * instance.color;
+    out.distance = model.params.z;
+    out.segment = vec4<f32>(a, b);
+    out.world_position = (model_matrix * vec4<f32>(model.position, 1.0)).xyz;
+
+    let other_hw = half_width(q, model.params.w);
+    var round_this = 0u;
+    var round_other = 0u;
+    if (is_round(has_neighbor)) {
+        round_this = 1u;
+    }
+    if (is_round(other_has_neighbor)) {
+        round_other = 1u;
+    }
+    if (is_end) {
+        out.half_widths = vec2<f32>(other_hw, hw);
+        out.round_ends = round_other | (round_this << 1u);
+    } else {
+        out.half_widths = vec2<f32>(hw, other_hw);
+        out.round_ends = round_this | (round_other << 1u);
+    }
+
+    // hidden instances and segments entirely behind the camera are moved outside of the clip volume
+    if (instance.visible == 0u || (this_clip.z < 0.0 && other_clip.z < 0.0)) {
+        out.clip_position = vec4<f32>(2.0, 2.0, 2.0, 1.0);
+    }
+
+    return out;
+}
+
+// ================================
+//            Fragment
+// ================================
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    clip_fragment(in.world_position);
+
+    // framebuffer coordinates have their origin at the top left corner
+    let p = vec2<f32>(in.clip_position.x, camera.viewport_size.y - in.clip_position.y);
+
+    let a = in.segment.xy;
+    let b = in.segment.zw;
+    let ab = b - a;
+    let len2 = dot(ab, ab);
+
+    var t = 0.0;
+    if (len2 > 0.0) {
+        t = dot(p - a, ab) / len2;
+    }
+    let normal = perp(safe_normalize(ab, vec2<f32>(1.0, 0.0)));
+    let hw = mix(in.half_widths.x, in.half_widths.y, clamp(t, 0.0, 1.0));
+
+    var d = abs(dot(p - a, normal));
+    if (t < 0.0 && (in.round_ends & 1u) != 0u) {
+        d = distance(p, a);
+    } else if (t > 1.0 && (in.round_ends & 2u) != 0u) {
+        d = distance(p, b);
+    }
+
+    let coverage = clamp(hw + 0.5 - d, 0.0, 1.0);
+    if (coverage <= 0.0) {
+        discard;
+    }
+
+    if (style.dash_length > 0.0 && style.gap_length > 0.0) {
+        let period = style.dash_length + style.gap_length;
+        if (abs(in.distance) % period > style.dash_length) {
+            discard;
+        }
+    }
+
+    return apply_fog(vec4<f32>(in.color.rgb, in.color.a * coverage), in.world_position, in.clip_position.xy);
+}
@@ -0,0 +1,299 @@
+use std::sync::Arc;
+
+use wgpu::{util::DeviceExt, Device, PrimitiveState, PrimitiveTopology, ShaderModule};
+
+use crate::{decl_vertex_raw_repr, instance::Instance3d, Pass, ProjectionCameraCommon, RenderContext, Res, SingletonResource, VertexBufferSlice, VertexRawRepr};
+
+use super::{shader_with_globals, Pipeline, RenderModeState};
+
+decl_vertex_raw_repr! {
+    #[derive(Debug)]
+    struct Vertex (Vertex step mode) {
+        pub position: [f32; 3] as [7 => Float32x3],
+        pub normal: [f32; 3] as [8 => Float32x3],
+        pub color: [f32; 4] as [9 => Float32x4],
+    }
+}
+
+/// A shader for Blinn-Phong shading
+pub struct LitShader {
+    shader: ShaderModule,
+}
+
+impl LitShader {
+    /// Create a new lit shader
+    pub fn new(
+        device: &Device,
+    ) -> Self {
+        Self {
+            shader: shader_with_globals(device, "lit.wgsl", include_str!("lit.wgsl")),
+        }
+    }
+}
+
+impl SingletonResource for LitShader {
+    fn init(ctx: &mut RenderContext) -> Self {
+        Self::new(ctx.device)
+    }
+}
+
+/// Lighting parameters of a [`LitMaterial`].
+///
+/// The light is the one attached to the camera, see [`ProjectionCamera::light_dir`](crate::ProjectionCamera::light_dir).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LitSettings {
+    /// Fraction of the color that is always visible
+    pub ambient: f32,
+    /// Strength of the diffuse (lambertian) term
+    pub diffuse: f32,
+    /// Strength of the white specular highlight
+    pub specular: f32,
+    /// Blinn-Phong exponent, higher values give smaller highlights
+    pub shininess: f32,
+    /// Light the back faces as if they were front faces
+    pub two_sided: bool,
+}
+
+impl Default for LitSettings {
+    fn default() -> Self {
+        Self {
+            ambient: 0.2,
+            diffuse: 0.8,
+            specular: 0.25,
+            shininess: 32.0,
+            two_sided: true,
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct LitMaterialUniform {
+    ambient: f32,
+    diffuse: f32,
+    specular: f32,
+    shininess: f32,
+    two_sided: u32,
+    _padding: [u32; 3],
+}
+
+impl From<&LitSettings> for LitMaterialUniform {
+    fn from(settings: &LitSettings) -> Self {
+        Self {
+            ambient: settings.ambient,
+            diffuse: settings.diffuse,
+            specular: settings.specular,
+            shininess: settings.shininess,
+            two_sided: settings.two_sided as u32,
+            _padding: [0; 3],
+        }
+    }
+}
+
+/// The bind group layout of [`LitMaterial`]s
+pub struct LitMaterialCommon {
+    bind_group_layout: wgpu::BindGroupLayout,
+}
+
+impl SingletonResource for LitMaterialCommon {
+    fn init(ctx: &mut RenderContext) -> Self {
+        let bind_group_layout = ctx.device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                }
+            ],
+            label: Some("lit_material_bind_group_layout"),
+        });
+
+        Self {
+            bind_group_layout,
+        }
+    }
+}
+
+impl LitMaterialCommon {
+    pub fn layout(&self) -> &wgpu::BindGroupLayout {
+        &self.bind_group_layout
+    }
+}
+
+/// The GPU side of [`LitSettings`], bound as `@group(1)` by [`LitPipeline`]
+pub struct LitMaterial {
+    buffer: wgpu::Buffer,
+    bind_group: Arc<wgpu::BindGroup>,
+}
+
+impl LitMaterial {
+    pub fn new(
+        cx: &mut RenderContext,
+        settings: &LitSettings,
+    ) -> Self {
+        let common = cx.singleton::<LitMaterialCommon>();
+
+        let buffer = cx.device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("lit material buffer"),
+            contents: bytemuck::cast_slice(&[LitMaterialUniform::from(settings)]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let bind_group = cx.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: common.layout(),
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: buffer.as_entire_binding(),
+                }
+            ],
+            label: Some("lit_material_bind_group"),
+        });
+
+        Self {
+            buffer,
+            bind_group: Arc::new(bind_group),
+        }
+    }
+
+    pub fn update(&self, queue: &wgpu::Queue, settings: &LitSettings) {
+        queue.write_buffer(&self.buffer, 0, bytemuck::cast_slice(&[LitMaterialUniform::from(settings)]));
+    }
+}
+
+/// Blinn-Phong shaded meshes, lit by the camera light.
+pub struct LitPipeline {
+    pipeline: Pipeline,
+    order_independent: bool,
+    default_material: Res<LitMaterial>,
+}
+
+impl LitPipeline {
+    pub fn new(
+        topology: PrimitiveTopology,
+        depth_compare: wgpu::CompareFunction,
+        use_depth_stencil: bool,
+    ) -> Self {
+        let primitive = PrimitiveState {
+            topology,
+            ..Default::default()
+        };
+
+        let pipeline = Pipeline::from_builder(move |cx, formats| {
+            let shader = cx.singleton::<LitShader>();
+            let render_mode = RenderModeState::new(cx.device, formats.render_mode, primitive.topology);
+
+            let camera_common = cx.singleton::<ProjectionCameraCommon>();
+            let material_common = cx.singleton::<LitMaterialCommon>();
+
+            let render_pipeline_layout =
+            cx.device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: None,
+                bind_group_layouts: &[
+                    camera_common.layout(),
+                    material_common.layout(),
+                ],
+                push_constant_ranges: &[],
+            });
+
+            let targets = formats.color_targets(wgpu::BlendState::ALPHA_BLENDING);
+
+            cx.device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some("lit pipeline"),
+                layout: Some(&render_pipeline_layout),
+                vertex: wgpu::VertexState {
+                    module: &shader.shader,
+                    entry_point: "vs_main",
+                    buffers: &[
+                        Vertex::desc(),
+                        Instance3d::desc(),
+                    ],
+                    compilation_options: Default::default(),
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &shader.shader,
+                    entry_point: formats.fragment_entry_point(),
+                    targets: &targets,
+                    compilation_options: wgpu::PipelineCompilationOptions {
+                        constants: &render_mode.constants,
+                        ..Default::default()
+                    },
+                }),
+                primitive: PrimitiveState {
+                    polygon_mode: render_mode.polygon_mode,
+                    ..primitive
+                },
+                depth_stencil: formats.depth_format.map(|format| wgpu::DepthStencilState {
+                    format,
+                    depth_write_enabled: use_depth_stencil && render_mode.depth_write && formats.depth_write(),
+                    depth_compare: formats.depth_compare(depth_compare),
+                    stencil: formats.stencil(),
+                    bias: formats.depth_bias(),
+                }),
+                multisample: Default::default(),
+                multiview: None,
+                cache: None,
+            })
+        });
+
+        Self {
+            pipeline,
+            order_independent: false,
+            default_material: Res::new(|cx: &mut RenderContext| LitMaterial::new(cx, &LitSettings::default())),
+        }
+    }
+
+    /// Draw into the order-independent transparency pass, see [`MeshPipeline::order_independent`](super::builder::MeshPipeline::order_independent)
+    pub fn order_independent(self) -> Self {
+        Self {
+            order_independent: true,
+            ..self
+        }
+    }
+
+    /// Render with the default [`LitSettings`]
+    pub fn render<'a>(
+        &self,
+        cx: &mut RenderContext,
+        pass: &mut Pass<'a>,
+        vertices: impl Into<VertexBufferSlice<Vertex>>,
+        instances: impl Into<VertexBufferSlice<Instance3d>>,
+    ) {
+        let material = cx.resource(&self.default_material);
+        self.render_with_material(cx, pass, vertices, instances, &material);
+    }
+
+    pub fn render_with_material<'a>(
+        &self,
+        cx: &mut RenderContext,
+        pass: &mut Pass<'a>,
+        vertices: impl Into<VertexBufferSlice<Vertex>>,
+        instances: impl Into<VertexBufferSlice<Instance3d>>,
+        material: &LitMaterial,
+    ) {
+        let vertices: VertexBufferSlice<Vertex> = vertices.into();
+        let instances: VertexBufferSlice<Instance3d> = instances.into();
+        let material = material.bind_group.clone();
+
+        let kind = if self.order_independent {
+            pass.transparent_kind()
+        } else {
+            pass.kind()
+        };
+        let pipeline = self.pipeline.get_for(cx, pass, kind);
+
+        pass.defer_for(kind, move |rp, globals| {
+            rp.set_pipeline(&pipeline);
+            rp.set_bind_group(0, globals, &[]);
+            rp.set_bind_group(1, &material, &[]);
+            rp.set_vertex_buffer(0, vertices.buffer.slice(..));
+            rp.set_vertex_buffer(1, instances.buffer.slice(..));
+            rp.draw(vertices.range.clone(), instances.range.clone());
+        });
+    }
+}
@@ -0,0 +1,506 @@
+//! Pipelines for materials written in WGSL by the user, see [`Material`].
+//!
+//! The shader of a material only has the functions that make it different:
+//! ```wgsl
+//! struct MaterialUniform {
+//!     tint: vec4<f32>,
+//! };
+//!
+//! fn material_fragment(in: MaterialInput, front_facing: bool) -> vec4<f32> {
+//!     return textureSample(albedo, albedo_sampler, in.uv) * in.color * material.tint;
+//! }
+//! ```
+//! [`MaterialPipeline`] assembles the full shader around them:
+//! - the globals of [`CAMERA_WGSL`](super::CAMERA_WGSL) as `@group(0)`;
+//! - the `VertexInput` and `InstanceInput` structs, declared from the attributes of
+//!   [`Material::Vertex`] and [`Instance3d`] and named after their fields (the fields
+//!   spanning several locations are suffixed with `_0`, `_1`...);
+//! - the `material` uniform and the [`Material::TEXTURES`] with their samplers as `@group(1)`;
+//! - the `MaterialInput` struct given to the fragment function:
+//! ```wgsl
+//! struct MaterialInput {
+//!     @builtin(position) clip_position: vec4<f32>,
+//!     @location(0) world_position: vec3<f32>,
+//!     @location(1) world_normal: vec3<f32>,
+//!     @location(2) color: vec4<f32>,
+//!     @location(3) uv: vec2<f32>,
+//!     @location(4) barycentric: vec3<f32>,
+//!     @location(5) @interpolate(flat) object_id: u32,
+//! };
+//! ```
+//! - the entry points, which hide the invisible instances, clip the fragments and
+//!   apply the render mode and the fog to the color of `material_fragment`.
+//!
+//! Unless [`Material::CUSTOM_VERTEX`] is set, the vertices are transformed from their
+//! `position` field, and the `normal`, `color` and `uv` fields are used when the
+//! vertex type has them.
+
+use std::{fmt::Write, marker::PhantomData, sync::Arc};
+
+use wgpu::{util::DeviceExt, Buffer, VertexFormat};
+
+use crate::{instance::Instance3d, Pass, RenderContext, SingletonResource, Texture2d, VertexBufferSlice, VertexRawRepr};
+
+use super::{builder::{BindGroupLayoutProvider, MeshPipeline, PipelineBuilder}, indirect::IndirectInstances};
+
+/// A material whose shading is written in WGSL, drawn by a [`MaterialPipeline`].
+///
+/// # Example
+/// ```ignore
+/// struct Tinted;
+///
+/// impl Material for Tinted {
+///     type Vertex = textured::Vertex;
+///     type Uniform = [f32; 4];
+///     const LABEL: &'static str = "tinted";
+///     const TEXTURES: &'static [&'static str] = &["albedo"];
+///
+///     fn shader() -> &'static str {
+///         include_str!("tinted.wgsl")
+///     }
+/// }
+///
+/// let pipeline = MaterialPipeline::<Tinted>::new();
+/// let bindings = MaterialBindings::<Tinted>::new(cx, &[1.0, 0.5, 0.5, 1.0], &[&albedo]);
+///
+/// pipeline.render(cx, pass, &vertices, &instances, &bindings);
+/// ```
+pub trait Material: 'static {
+    /// The vertices drawn with the material
+    type Vertex: VertexRawRepr;
+    /// The parameters bound as `var<uniform> material: MaterialUniform`, `()` for none.
+    ///
+    /// The shader declares the `MaterialUniform` struct, whose layout must match this
+    /// type with the alignment rules of WGSL uniforms.
+    type Uniform: bytemuck::Pod;
+
+    /// The label of the pipeline and of its resources
+    const LABEL: &'static str;
+
+    /// The names of the textures of the material, each bound as `var name: texture_2d<f32>`
+    /// with `var name_sampler: sampler`
+    const TEXTURES: &'static [&'static str] = &[];
+
+    /// Whether the shader has its own vertex function:
+    /// ```wgsl
+    /// fn material_vertex(model: VertexInput, instance: InstanceInput, vertex_index: u32) -> MaterialInput
+    /// ```
+    const CUSTOM_VERTEX: bool = false;
+
+    /// The WGSL source with the `MaterialUniform` struct, the `material_fragment`
+    /// function, and `material_vertex` if [`Material::CUSTOM_VERTEX`] is set
+    fn shader() -> &'static str;
+
+    /// Change the state of the pipeline, e.g. its topology or its culling
+    fn pipeline(builder: PipelineBuilder<Self::Vertex>) -> PipelineBuilder<Self::Vertex> {
+        builder
+    }
+}
+
+/// Whether the material binds a uniform buffer
+fn has_uniform<M: Material>() -> bool {
+    std::mem::size_of::<M::Uniform>() > 0
+}
+
+/// The bind group layout of the [`MaterialBindings`] of `M`: the uniform at binding `0`,
+/// then each texture and its sampler
+pub struct MaterialCommon<M: Material> {
+    bind_group_layout: wgpu::BindGroupLayout,
+    _phantom: PhantomData<fn() -> M>,
+}
+
+impl<M: Material> SingletonResource for MaterialCommon<M> {
+    fn init(ctx: &mut RenderContext) -> Self {
+        let visibility = wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT;
+
+        let mut entries = Vec::new();
+        if has_uniform::<M>() {
+            entries.push(wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            });
+        }
+        for i in 0..M::TEXTURES.len() as u32 {
+            entries.push(wgpu::BindGroupLayoutEntry {
+                binding: 1 + 2 * i,
+                visibility,
+                ty: wgpu::BindingType::Texture {
+                    multisampled: false,
+                    view_dimension: wgpu::TextureViewDimension::D2,
+                    sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                },
+                count: None,
+            });
+            entries.push(wgpu::BindGroupLayoutEntry {
+                binding: 2 + 2 * i,
+                visibility,
+                ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                count: None,
+            });
+        }
+
+        let bind_group_layout = ctx.device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &entries,
+            label: Some(&format!("{}_bind_group_layout", M::LABEL)),
+        });
+
+        Self {
+            bind_group_layout,
+            _phantom: PhantomData,
+        }
+    }
+}
+
+impl<M: Material> MaterialCommon<M> {
+    pub fn layout(&self) -> &wgpu::BindGroupLayout {
+        &self.bind_group_layout
+    }
+}
+
+impl<M: Material> BindGroupLayoutProvider for MaterialCommon<M> {
+    fn layout(&self) -> &wgpu::BindGroupLayout {
+        &self.bind_group_layout
+    }
+}
+
+/// The uniform and the textures of a [`Material`], bound as `@group(1)` by its [`MaterialPipeline`]
+pub struct MaterialBindings<M: Material> {
+    buffer: Option<Buffer>,
+    bind_group: Arc<wgpu::BindGroup>,
+    _phantom: PhantomData<fn() -> M>,
+}
+
+impl<M: Material> MaterialBindings<M> {
+    /// # Panics
+    /// If there is not one texture for each of [`Material::TEXTURES`]
+    pub fn new(
+        cx: &mut RenderContext,
+        uniform: &M::Uniform,
+        textures: &[&Texture2d],
+    ) -> Self {
+        assert_eq!(
+            textures.len(),
+            M::TEXTURES.len(),
+            "material `{}` expects the textures {:?}",
+            M::LABEL,
+            M::TEXTURES,
+        );
+
+        let common = cx.singleton::<MaterialCommon<M>>();
+
+        let buffer = has_uniform::<M>().then(|| cx.device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some(&format!("{} buffer", M::LABEL)),
+            contents: bytemuck::cast_slice(std::slice::from_ref(uniform)),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        }));
+
+        let mut entries = Vec::new();
+        if let Some(buffer) = &buffer {
+            entries.push(wgpu::BindGroupEntry {
+                binding: 0,
+                resource: buffer.as_entire_binding(),
+            });
+        }
+        for (i, texture) in textures.iter().enumerate() {
+            let i = i as u32;
+            entries.push(wgpu::BindGroupEntry {
+                binding: 1 + 2 * i,
+                resource: wgpu::BindingResource::TextureView(texture.view()),
+            });
+            entries.push(wgpu::BindGroupEntry {
+                binding: 2 + 2 * i,
+                resource: wgpu::BindingResource::Sampler(texture.sampler()),
+            });
+        }
+
+        let bind_group = cx.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: common.layout(),
+            entries: &entries,
+            label: Some(&format!("{}_bind_group", M::LABEL)),
+        });
+
+        Self {
+            buffer,
+            bind_group: Arc::new(bind_group),
+            _phantom: PhantomData,
+        }
+    }
+
+    pub fn update(&self, queue: &wgpu::Queue, uniform: &M::Uniform) {
+        if let Some(buffer) = &self.buffer {
+            queue.write_buffer(buffer, 0, bytemuck::cast_slice(std::slice::from_ref(uniform)));
+        }
+    }
+
+    pub fn bind_group(&self) -> &Arc<wgpu::BindGroup> {
+        &self.bind_group
+    }
+}
+
+/// Draws the [`Material`] `M`, with the variants of the built-in pipelines for the
+/// kinds of passes and the render modes
+pub struct MaterialPipeline<M: Material> {
+    pipeline: MeshPipeline<M::Vertex>,
+}
+
+impl<M: Material> Default for MaterialPipeline<M> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<M: Material> MaterialPipeline<M> {
+    pub fn new() -> Self {
+        let builder = PipelineBuilder::new(M::LABEL, Self::source())
+            .bind_group::<MaterialCommon<M>>();
+
+        Self {
+            pipeline: M::pipeline(builder).build(),
+        }
+    }
+
+    /// The shader assembled around [`Material::shader`], without the globals
+    ///
+    /// # Panics
+    /// If the vertices have no `position` field of three floats and the material has no
+    /// custom vertex function
+    pub fn source() -> String {
+        let mut source = String::new();
+
+        source.push_str("// ================================\n//            Inputs\n// ================================\n\n");
+        source.push_str(&input_struct("VertexInput", &M::Vertex::desc(), M::Vertex::fields()));
+        source.push_str(&input_struct("InstanceInput", &Instance3d::desc(), Instance3d::fields()));
+        source.push_str(MATERIAL_INPUT_WGSL);
+
+        source.push_str("// ================================\n//            Material\n// ================================\n\n");
+        if has_uniform::<M>() {
+            source.push_str("@group(1) @binding(0)\nvar<uniform> material: MaterialUniform;\n\n");
+        }
+        for (i, name) in M::TEXTURES.iter().enumerate() {
+            let _ = write!(
+                source,
+                "@group(1) @binding({})\nvar {name}: texture_2d<f32>;\n@group(1) @binding({})\nvar {name}_sampler: sampler;\n\n",
+                1 + 2 * i,
+                2 + 2 * i,
+            );
+        }
+        source.push_str(M::shader());
+        source.push('\n');
+
+        source.push_str("\n// ================================\n//            Vertex\n// ================================\n\n");
+        let vertex_function = if M::CUSTOM_VERTEX {
+            "material_vertex"
+        } else {
+            source.push_str(&default_vertex(&M::Vertex::desc(), M::Vertex::fields(), M::LABEL));
+            "default_vertex"
+        };
+        source.push_str(&MATERIAL_ENTRY_POINTS_WGSL.replace("{vertex_function}", vertex_function));
+
+        source
+    }
+
+    /// Draw into the order-independent transparency pass, see [`MeshPipeline::order_independent`]
+    pub fn order_independent(self) -> Self {
+        Self {
+            pipeline: self.pipeline.order_independent(),
+        }
+    }
+
+    pub fn render<'a>(
+        &self,
+        cx: &mut RenderContext,
+        pass: &mut Pass<'a>,
+        vertices: impl Into<VertexBufferSlice<M::Vertex>>,
+        instances: impl Into<VertexBufferSlice<Instance3d>>,
+        bindings: &MaterialBindings<M>,
+    ) {
+        self.pipeline.render(cx, pass, vertices, instances, std::slice::from_ref(&bindings.bind_group));
+    }
+
+    /// Same as [`MaterialPipeline::render`], with an index buffer of `u16`
+    pub fn render_indexed<'a>(
+        &self,
+        cx: &mut RenderContext,
+        pass: &mut Pass<'a>,
+        vertices: impl Into<VertexBufferSlice<M::Vertex>>,
+        instances: impl Into<VertexBufferSlice<Instance3d>>,
+        bindings: &MaterialBindings<M>,
+        index_buffer: Arc<Buffer>,
+    ) {
+        self.pipeline.render_indexed(cx, pass, vertices, instances, std::slice::from_ref(&bindings.bind_group), index_buffer);
+    }
+
+    /// Draw the instances that pass the GPU culling, see [`IndirectInstances`]
+    pub fn render_indirect<'a>(
+        &self,
+        cx: &mut RenderContext,
+        pass: &mut Pass<'a>,
+        vertices: impl Into<VertexBufferSlice<M::Vertex>>,
+        instances: &IndirectInstances,
+        bindings: &MaterialBindings<M>,
+        index_buffer: Option<Arc<Buffer>>,
+    ) {
+        self.pipeline.render_indirect(cx, pass, vertices, instances, std::slice::from_ref(&bindings.bind_group), index_buffer);
+    }
+}
+
+const MATERIAL_INPUT_WGSL: &str = "struct MaterialInput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) world_position: vec3<f32>,
+    @location(1) world_normal: vec3<f32>,
+    @location(2) color: vec4<f32>,
+    @location(3) uv: vec2<f32>,
+    @location(4) barycentric: vec3<f32>,
+    @location(5) @interpolate(flat) object_id: u32,
+};
+
+";
+
+const MATERIAL_ENTRY_POINTS_WGSL: &str = "@vertex
+fn vs_main(
+    @builtin(vertex_index) vertex_index: u32,
+    model: VertexInput,
+    instance: InstanceInput,
+) -> MaterialInput {
+    var out = {vertex_function}(model, instance, vertex_index);
+
+    // hidden instances are moved outside of the clip volume
+    if (instance.visible == 0u) {
+        out.clip_position = vec4<f32>(2.0, 2.0, 2.0, 1.0);
+    }
+
+    return out;
+}
+
+// ================================
+//            Fragment
+// ================================
+
+fn shade(in: MaterialInput, front_facing: bool) -> vec4<f32> {
+    clip_fragment(in.world_position);
+
+    let color = material_fragment(in, front_facing);
+    return apply_fog(apply_render_mode(color, in.barycentric), in.world_position, in.clip_position.xy);
+}
+
+@fragment
+fn fs_main(in: MaterialInput, @builtin(front_facing) front_facing: bool) -> @location(0) vec4<f32> {
+    return shade(in, front_facing);
+}
+
+// transparent pass of the view, see `oit_output`
+@fragment
+fn fs_oit(in: MaterialInput, @builtin(front_facing) front_facing: bool) -> OitOutput {
+    return oit_output(shade(in, front_facing), in.clip_position.z);
+}
+";
+
+/// The WGSL type of a vertex attribute
+fn wgsl_type(format: VertexFormat) -> &'static str {
+    use VertexFormat::*;
+
+    match format {
+        Float32 => "f32",
+        Float32x2 | Float16x2 | Unorm8x2 | Snorm8x2 | Unorm16x2 | Snorm16x2 => "vec2<f32>",
+        Float32x3 => "vec3<f32>",
+        Float32x4 | Float16x4 | Unorm8x4 | Snorm8x4 | Unorm16x4 | Snorm16x4 | Unorm10_10_10_2 => "vec4<f32>",
+        Uint32 => "u32",
+        Uint32x2 | Uint8x2 | Uint16x2 => "vec2<u32>",
+        Uint32x3 => "vec3<u32>",
+        Uint32x4 | Uint8x4 | Uint16x4 => "vec4<u32>",
+        Sint32 => "i32",
+        Sint32x2 | Sint8x2 | Sint16x2 => "vec2<i32>",
+        Sint32x3 => "vec3<i32>",
+        Sint32x4 | Sint8x4 | Sint16x4 => "vec4<i32>",
+        format => panic!("vertex format {format:?} has no WGSL type"),
+    }
+}
+
+/// The names of the attributes of a vertex buffer, from the fields of its type or
+/// from their locations when the fields are not known
+fn attribute_names(layout: &wgpu::VertexBufferLayout, fields: &[(&str, usize)]) -> Vec<String> {
+    let names = fields.iter()
+        .flat_map(|&(name, count)| (0..count).map(move |i| match count {
+            1 => name.to_string(),
+            _ => format!("{name}_{i}"),
+        }))
+        .collect::<Vec<_>>();
+
+    if names.len() == layout.attributes.len() {
+        names
+    } else {
+        layout.attributes.iter().map(|attribute| format!("attribute_{}", attribute.shader_location)).collect()
+    }
+}
+
+fn input_struct(name: &str, layout: &wgpu::VertexBufferLayout, fields: &[(&str, usize)]) -> String {
+    let mut source = format!("struct {name} {{\n");
+    for (attribute, field) in layout.attributes.iter().zip(attribute_names(layout, fields)) {
+        let _ = writeln!(source, "    @location({}) {field}: {},", attribute.shader_location, wgsl_type(attribute.format));
+    }
+    source.push_str("};\n\n");
+    source
+}
+
+/// The vertex function of the materials without `material_vertex`, which uses the
+/// fields of the vertex type that it knows
+fn default_vertex(layout: &wgpu::VertexBufferLayout, fields: &[(&str, usize)], label: &str) -> String {
+    let field_type = |field: &str| layout.attributes.iter()
+        .zip(attribute_names(layout, fields))
+        .find(|(_, name)| name == field)
+        .map(|(attribute, _)| wgsl_type(attribute.format));
+
+    assert_eq!(
+        field_type("position"),
+        Some("vec3<f32>"),
+        "material `{label}` needs vertices with a `position` of three floats, or a custom vertex function",
+    );
+
+    let world_normal = match field_type("normal") {
+        Some("vec3<f32>") => "normal_matrix * model.normal",
+        _ => "vec3<f32>(0.0)",
+    };
+    let color = match field_type("color") {
+        Some("vec4<f32>") => "model.color * instance.color",
+        Some("vec3<f32>") => "vec4<f32>(model.color, 1.0) * instance.color",
+        _ => "instance.color",
+    };
+    let uv = match field_type("uv") {
+        Some("vec2<f32>") => "model.uv",
+        _ => "vec2<f32>(0.0)",
+    };
+
+    format!("fn default_vertex(model: VertexInput, instance: InstanceInput, vertex_index: u32) -> MaterialInput {{
+    let model_matrix = mat4x4<f32>(
+        instance.model_0,
+        instance.model_1,
+        instance.model_2,
+        instance.model_3,
+    );
+    let normal_matrix = mat3x3<f32>(
+        instance.model_inv_tr_0,
+        instance.model_inv_tr_1,
+        instance.model_inv_tr_2,
+    );
+
+    let world_position = model_matrix * vec4<f32>(model.position, 1.0);
+
+    var out: MaterialInput;
+    out.clip_position = camera.proj * camera.view * world_position;
+    out.world_position = world_position.xyz;
+    out.world_normal = {world_normal};
+    out.color = {color};
+    out.uv = {uv};
+    out.barycentric = corner_barycentric(vertex_index);
+    out.object_id = instance.object_id;
+    return out;
+}}
+
+")
+}
@@ -0,0 +1,157 @@
+use std::sync::Arc;
+
+use wgpu::{Buffer, Device, PrimitiveState, PrimitiveTopology, ShaderModule};
+
+use crate::{instance::Instance3d, Pass, ProjectionCameraCommon, RenderContext, SingletonResource, VertexBufferSlice, VertexRawRepr};
+
+use super::{flat, shader_with_globals, Pipeline};
+
+/// A shader that writes [`Instance3d::object_id`] instead of a color
+pub struct ObjectIdShader {
+    shader: ShaderModule,
+}
+
+impl ObjectIdShader {
+    /// Create a new object id shader
+    pub fn new(
+        device: &Device,
+    ) -> Self {
+        Self {
+            shader: shader_with_globals(device, "object_id.wgsl", include_str!("object_id.wgsl")),
+        }
+    }
+}
+
+impl SingletonResource for ObjectIdShader {
+    fn init(ctx: &mut RenderContext) -> Self {
+        Self::new(ctx.device)
+    }
+}
+
+/// Renders the object ids of [`flat::Vertex`] meshes.
+///
+/// The pass has to target an integer texture, usually [`ObjectIdPipeline::FORMAT`],
+/// the id of the nearest instance is written for every pixel. Reading the texture
+/// back allows picking the object under the cursor.
+pub struct ObjectIdPipeline {
+    pipeline: Pipeline,
+}
+
+impl ObjectIdPipeline {
+    /// The expected format of the id target
+    pub const FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::R32Uint;
+
+    pub fn new(
+        topology: PrimitiveTopology,
+    ) -> Self {
+        let primitive = PrimitiveState {
+            topology,
+            ..Default::default()
+        };
+
+        let pipeline = Pipeline::from_builder(move |cx, formats| {
+            let shader = cx.singleton::<ObjectIdShader>();
+
+            let camera_common = cx.singleton::<ProjectionCameraCommon>();
+
+            let render_pipeline_layout =
+            cx.device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: None,
+                bind_group_layouts: &[
+                    camera_common.layout(),
+                ],
+                push_constant_ranges: &[],
+            });
+
+            // integer targets can't be blended
+            let targets = formats.target_formats.iter().map(|format| {
+                Some(wgpu::ColorTargetState {
+                    format: *format,
+                    blend: None,
+                    write_mask: wgpu::ColorWrites::ALL,
+                })
+            }).collect::<Vec<_>>();
+
+            cx.device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some("object id pipeline"),
+                layout: Some(&render_pipeline_layout),
+                vertex: wgpu::VertexState {
+                    module: &shader.shader,
+                    entry_point: "vs_main",
+                    buffers: &[
+                        flat::Vertex::desc(),
+                        Instance3d::desc(),
+                    ],
+                    compilation_options: Default::default(),
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &shader.shader,
+                    entry_point: "fs_main",
+                    targets: &targets,
+                    compilation_options: Default::default(),
+                }),
+                primitive,
+                depth_stencil: formats.depth_format.map(|format| wgpu::DepthStencilState {
+                    format,
+                    depth_write_enabled: true,
+                    depth_compare: wgpu::CompareFunction::LessEqual,
+                    stencil: wgpu::StencilState::default(),
+                    bias: wgpu::DepthBiasState::default(),
+                }),
+                multisample: Default::default(),
+                multiview: None,
+                cache: None,
+            })
+        });
+
+        Self {
+            pipeline,
+        }
+    }
+
+    pub fn render<'a>(
+        &self,
+        cx: &mut RenderContext,
+        pass: &mut Pass<'a>,
+        vertices: impl Into<VertexBufferSlice<flat::Vertex>>,
+        instances: impl Into<VertexBufferSlice<Instance3d>>,
+    ) {
+        self.render_inner(cx, pass, vertices.into(), instances.into(), None);
+    }
+
+    /// Same as [`ObjectIdPipeline::render`], with an index buffer of `u16`
+    pub fn render_indexed<'a>(
+        &self,
+        cx: &mut RenderContext,
+        pass: &mut Pass<'a>,
+        vertices: impl Into<VertexBufferSlice<flat::Vertex>>,
+        instances: impl Into<VertexBufferSlice<Instance3d>>,
+        index_buffer: Arc<Buffer>,
+    ) {
+        self.render_inner(cx, pass, vertices.into(), instances.into(), Some(index_buffer));
+    }
+
+    fn render_inner(
+        &self,
+        cx: &mut RenderContext,
+        pass: &mut Pass,
+        vertices: VertexBufferSlice<flat::Vertex>,
+        instances: VertexBufferSlice<Instance3d>,
+        index_buffer: Option<Arc<Buffer>>,
+    ) {
+        let pipeline = self.pipeline.get(cx, pass);
+
+        pass.defer(move |rp, globals| {
+            rp.set_pipeline(&pipeline);
+            rp.set_bind_group(0, globals, &[]);
+            rp.set_vertex_buffer(0, vertices.buffer.slice(..));
+            rp.set_vertex_buffer(1, instances.buffer.slice(..));
+            if let Some(index_buffer) = &index_buffer {
+                rp.set_index_buffer(index_buffer.slice(..), wgpu::IndexFormat::Uint16);
+                rp.draw_indexed(0..index_buffer.size() as u32 / 2, 0, instances.range.clone());
+            } else {
+                rp.draw(vertices.range.clone(), instances.range.clone());
+            }
+        });
+    }
+}
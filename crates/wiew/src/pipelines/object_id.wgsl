@@ -0,0 +1,59 @@
+// ================================
+//            Vertex
+// ================================
+
+struct VertexInput {
+    @location(7) position: vec3<f32>,
+};
+
+struct InstanceInput {
+    @location(0) model_0: vec4<f32>,
+    @location(1) model_1: vec4<f32>,
+    @location(2) model_2: vec4<f32>,
+    @location(3) model_3: vec4<f32>,
+    @location(14) object_id: u32,
+    @location(15) visible: u32,
+};
+
+@vertex
+fn vs_main(
+    model: VertexInput,
+    instance: InstanceInput,
+) -> VertexOutput {
+    let model_matrix = mat4x4<f32>(
+        instance.model_0,
+        instance.model_1,
+        instance.model_2,
+        instance.model_3,
+    );
+
+    let world_position = model_matrix * vec4<f32>(model.position, 1.0);
+
+    var out: VertexOutput;
+    out.clip_position = camera.proj * camera.view * world_position;
+    out.world_position = world_position.xyz;
+    out.object_id = instance.object_id;
+
+    if (instance.visible == 0u) {
+        out.clip_position = vec4<f32>(2.0, 2.0, 2.0, 1.0);
+    }
+
+    return out;
+}
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) @interpolate(flat) object_id: u32,
+    @location(1) world_position: vec3<f32>,
+};
+
+// ================================
+//            Fragment
+// ================================
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) u32 {
+    clip_fragment(in.world_position);
+
+    return in.object_id;
+}
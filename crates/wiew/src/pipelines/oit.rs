@@ -0,0 +1,277 @@
+use std::sync::Arc;
+
+use wgpu::{Device, PrimitiveState, PrimitiveTopology, ShaderModule};
+
+use crate::{Pass, RenderContext, SingletonResource};
+
+use super::Pipeline;
+
+/// Format of the accumulation target: the weighted sum of the premultiplied colors
+/// and of the alphas of the transparent fragments
+pub const ACCUM_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+
+/// Format of the revealage target: the product of the `1 - alpha` of the transparent fragments
+pub const REVEALAGE_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::R8Unorm;
+
+/// The color targets of the transparent pass, written by the `OitOutput` of the
+/// `fs_oit` entry points (see [`CAMERA_WGSL`](super::CAMERA_WGSL))
+pub fn color_targets() -> [Option<wgpu::ColorTargetState>; 2] {
+    let sum = wgpu::BlendComponent {
+        src_factor: wgpu::BlendFactor::One,
+        dst_factor: wgpu::BlendFactor::One,
+        operation: wgpu::BlendOperation::Add,
+    };
+
+    let product = wgpu::BlendComponent {
+        src_factor: wgpu::BlendFactor::Zero,
+        dst_factor: wgpu::BlendFactor::OneMinusSrc,
+        operation: wgpu::BlendOperation::Add,
+    };
+
+    [
+        Some(wgpu::ColorTargetState {
+            format: ACCUM_FORMAT,
+            blend: Some(wgpu::BlendState {
+                color: sum,
+                alpha: sum,
+            }),
+            write_mask: wgpu::ColorWrites::ALL,
+        }),
+        Some(wgpu::ColorTargetState {
+            format: REVEALAGE_FORMAT,
+            blend: Some(wgpu::BlendState {
+                color: product,
+                alpha: product,
+            }),
+            write_mask: wgpu::ColorWrites::ALL,
+        }),
+    ]
+}
+
+/// The bind group layout of [`OitTargets`], as read by the [`OitCompositePipeline`]
+pub struct OitTargetsCommon {
+    bind_group_layout: wgpu::BindGroupLayout,
+}
+
+impl SingletonResource for OitTargetsCommon {
+    fn init(ctx: &mut RenderContext) -> Self {
+        let texture_entry = |binding| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Texture {
+                sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                view_dimension: wgpu::TextureViewDimension::D2,
+                multisampled: false,
+            },
+            count: None,
+        };
+
+        let bind_group_layout = ctx.device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[
+                texture_entry(0),
+                texture_entry(1),
+            ],
+            label: Some("oit_targets_bind_group_layout"),
+        });
+
+        Self {
+            bind_group_layout,
+        }
+    }
+}
+
+impl OitTargetsCommon {
+    pub fn layout(&self) -> &wgpu::BindGroupLayout {
+        &self.bind_group_layout
+    }
+}
+
+/// The accumulation and revealage targets of the transparent pass
+pub struct OitTargets {
+    accum: wgpu::TextureView,
+    revealage: wgpu::TextureView,
+    bind_group: Arc<wgpu::BindGroup>,
+}
+
+impl OitTargets {
+    pub fn new(
+        cx: &mut RenderContext,
+        width: u32,
+        height: u32,
+    ) -> Self {
+        let common = cx.singleton::<OitTargetsCommon>();
+
+        let create_view = |label, format| {
+            cx.device.create_texture(&wgpu::TextureDescriptor {
+                label: Some(label),
+                size: wgpu::Extent3d {
+                    width,
+                    height,
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format,
+                usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+                view_formats: &[],
+            }).create_view(&wgpu::TextureViewDescriptor::default())
+        };
+
+        let accum = create_view("oit accumulation texture", ACCUM_FORMAT);
+        let revealage = create_view("oit revealage texture", REVEALAGE_FORMAT);
+
+        let bind_group = cx.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: common.layout(),
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&accum),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(&revealage),
+                },
+            ],
+            label: Some("oit_targets_bind_group"),
+        });
+
+        Self {
+            accum,
+            revealage,
+            bind_group: Arc::new(bind_group),
+        }
+    }
+
+    /// The color attachments of the transparent pass: nothing accumulated, everything revealed
+    pub fn color_attachments(&self) -> [Option<wgpu::RenderPassColorAttachment<'_>>; 2] {
+        [
+            Some(wgpu::RenderPassColorAttachment {
+                view: &self.accum,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                    store: wgpu::StoreOp::Store,
+                },
+            }),
+            Some(wgpu::RenderPassColorAttachment {
+                view: &self.revealage,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color::WHITE),
+                    store: wgpu::StoreOp::Store,
+                },
+            }),
+        ]
+    }
+}
+
+/// A shader that composites the transparent pass
+pub struct OitCompositeShader {
+    shader: ShaderModule,
+}
+
+impl OitCompositeShader {
+    /// Create a new composite shader
+    pub fn new(
+        device: &Device,
+    ) -> Self {
+        Self {
+            shader: device.create_shader_module(wgpu::include_wgsl!("oit_composite.wgsl")),
+        }
+    }
+}
+
+impl SingletonResource for OitCompositeShader {
+    fn init(ctx: &mut RenderContext) -> Self {
+        Self::new(ctx.device)
+    }
+}
+
+/// Blends the average color of the transparent fragments over the surface,
+/// with a full screen triangle.
+pub struct OitCompositePipeline {
+    pipeline: Pipeline,
+}
+
+impl Default for OitCompositePipeline {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl OitCompositePipeline {
+    pub fn new() -> Self {
+        let primitive = PrimitiveState {
+            topology: PrimitiveTopology::TriangleList,
+            ..Default::default()
+        };
+
+        let pipeline = Pipeline::from_builder(move |cx, formats| {
+            let shader = cx.singleton::<OitCompositeShader>();
+
+            let targets_common = cx.singleton::<OitTargetsCommon>();
+
+            let render_pipeline_layout =
+            cx.device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: None,
+                bind_group_layouts: &[
+                    targets_common.layout(),
+                ],
+                push_constant_ranges: &[],
+            });
+
+            let targets = formats.color_targets(wgpu::BlendState::ALPHA_BLENDING);
+
+            cx.device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some("oit composite pipeline"),
+                layout: Some(&render_pipeline_layout),
+                vertex: wgpu::VertexState {
+                    module: &shader.shader,
+                    entry_point: "vs_main",
+                    buffers: &[],
+                    compilation_options: Default::default(),
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &shader.shader,
+                    entry_point: "fs_main",
+                    targets: &targets,
+                    compilation_options: Default::default(),
+                }),
+                primitive,
+                depth_stencil: formats.depth_format.map(|format| wgpu::DepthStencilState {
+                    format,
+                    depth_write_enabled: false,
+                    depth_compare: wgpu::CompareFunction::Always,
+                    stencil: wgpu::StencilState::default(),
+                    bias: wgpu::DepthBiasState::default(),
+                }),
+                multisample: Default::default(),
+                multiview: None,
+                cache: None,
+            })
+        });
+
+        Self {
+            pipeline,
+        }
+    }
+
+    /// Composite the transparent pass rendered into `targets` onto the surface of `pass`
+    pub fn render<'a>(
+        &self,
+        cx: &mut RenderContext,
+        pass: &mut Pass<'a>,
+        targets: &OitTargets,
+    ) {
+        let bind_group = targets.bind_group.clone();
+
+        let pipeline = self.pipeline.get(cx, pass);
+
+        pass.defer(move |rp, _| {
+            rp.set_pipeline(&pipeline);
+            rp.set_bind_group(0, &bind_group, &[]);
+            rp.draw(0..3, 0..1);
+        });
+    }
+}
@@ -0,0 +1,41 @@
+// ================================
+//            Targets
+// ================================
+
+// weighted sum of the premultiplied colors and alphas
+@group(0) @binding(0)
+var accum_texture: texture_2d<f32>;
+// product of the `1 - alpha`
+@group(0) @binding(1)
+var revealage_texture: texture_2d<f32>;
+
+// ================================
+//            Vertex
+// ================================
+
+// a triangle covering the whole screen
+@vertex
+fn vs_main(@builtin(vertex_index) vertex_index: u32) -> @builtin(position) vec4<f32> {
+    let uv = vec2<f32>(f32((vertex_index << 1u) & 2u), f32(vertex_index & 2u));
+    return vec4<f32>(uv * 2.0 - 1.0, 0.0, 1.0);
+}
+
+// ================================
+//            Fragment
+// ================================
+
+@fragment
+fn fs_main(@builtin(position) position: vec4<f32>) -> @location(0) vec4<f32> {
+    let coords = vec2<i32>(position.xy);
+
+    let revealage = textureLoad(revealage_texture, coords, 0).r;
+    // nothing transparent was drawn here
+    if (revealage >= 1.0) {
+        discard;
+    }
+
+    let accum = textureLoad(accum_texture, coords, 0);
+    let average = accum.rgb / clamp(accum.a, 1e-4, 5e4);
+
+    return vec4<f32>(average, 1.0 - revealage);
+}
@@ -0,0 +1,467 @@
+use std::sync::Arc;
+
+use wgpu::{util::DeviceExt, Device, PrimitiveState, PrimitiveTopology, ShaderModule};
+
+use crate::{Pass, RenderContext, SingletonResource};
+
+use super::{object_id::ObjectIdPipeline, Pipeline};
+
+/// Format of the mask of the selected and hovered objects
+pub const MASK_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::R8Unorm;
+
+/// Largest number of selected objects that are outlined, the next ones are ignored
+pub const MAX_SELECTED: usize = 64;
+
+/// Largest [`OutlineSettings::width`]
+pub const MAX_OUTLINE_WIDTH: u32 = 8;
+
+/// The objects to outline, by [`Instance3d::object_id`](crate::instance::Instance3d::object_id).
+///
+/// The id `0` is the one of the background and of the objects without an id, it is never outlined.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Selection {
+    pub selected: Vec<u32>,
+    pub hovered: Option<u32>,
+}
+
+impl Selection {
+    pub fn is_empty(&self) -> bool {
+        self.selected.is_empty() && self.hovered.is_none()
+    }
+}
+
+/// Look of the outline of the [`Selection`]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct OutlineSettings {
+    /// Color of the outline of the selected objects
+    pub color: [f32; 4],
+    /// Color of the outline of the hovered object
+    pub hover_color: [f32; 4],
+    /// Width of the outline, in pixels (at most [`MAX_OUTLINE_WIDTH`])
+    pub width: u32,
+    /// Opacity of the outline color over the objects themselves, `0` for no highlight
+    pub fill_alpha: f32,
+}
+
+impl Default for OutlineSettings {
+    fn default() -> Self {
+        Self {
+            color: [1.0, 0.6, 0.0, 1.0],
+            hover_color: [0.3, 0.7, 1.0, 1.0],
+            width: 3,
+            fill_alpha: 0.15,
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct OutlineUniform {
+    color: [f32; 4],
+    hover_color: [f32; 4],
+    width: u32,
+    fill_alpha: f32,
+    selected_count: u32,
+    hovered: u32,
+    selected: [u32; MAX_SELECTED],
+}
+
+impl OutlineUniform {
+    fn new(selection: &Selection, settings: &OutlineSettings) -> Self {
+        if selection.selected.len() > MAX_SELECTED {
+            log::warn!("Too many selected objects ({}), only the first {MAX_SELECTED} are outlined", selection.selected.len());
+        }
+
+        let mut selected = [0; MAX_SELECTED];
+        let count = selection.selected.len().min(MAX_SELECTED);
+        selected[..count].copy_from_slice(&selection.selected[..count]);
+
+        Self {
+            color: settings.color,
+            hover_color: settings.hover_color,
+            width: settings.width.min(MAX_OUTLINE_WIDTH),
+            fill_alpha: settings.fill_alpha,
+            selected_count: count as u32,
+            hovered: selection.hovered.unwrap_or(0),
+            selected,
+        }
+    }
+}
+
+/// A shader for the mask and the outline passes
+pub struct OutlineShader {
+    shader: ShaderModule,
+}
+
+impl OutlineShader {
+    /// Create a new outline shader
+    pub fn new(
+        device: &Device,
+    ) -> Self {
+        Self {
+            shader: device.create_shader_module(wgpu::include_wgsl!("outline.wgsl")),
+        }
+    }
+}
+
+impl SingletonResource for OutlineShader {
+    fn init(ctx: &mut RenderContext) -> Self {
+        Self::new(ctx.device)
+    }
+}
+
+/// The bind group layouts of [`OutlineTargets`]: the settings with the object ids for
+/// the mask pass, the settings with the mask for the outline pass
+pub struct OutlineCommon {
+    mask_layout: wgpu::BindGroupLayout,
+    outline_layout: wgpu::BindGroupLayout,
+}
+
+impl SingletonResource for OutlineCommon {
+    fn init(ctx: &mut RenderContext) -> Self {
+        let uniform_entry = wgpu::BindGroupLayoutEntry {
+            binding: 0,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Uniform,
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        };
+
+        let texture_entry = |binding, sample_type| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Texture {
+                sample_type,
+                view_dimension: wgpu::TextureViewDimension::D2,
+                multisampled: false,
+            },
+            count: None,
+        };
+
+        let mask_layout = ctx.device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[
+                uniform_entry,
+                texture_entry(1, wgpu::TextureSampleType::Uint),
+            ],
+            label: Some("outline_mask_bind_group_layout"),
+        });
+
+        let outline_layout = ctx.device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[
+                uniform_entry,
+                texture_entry(2, wgpu::TextureSampleType::Float { filterable: false }),
+            ],
+            label: Some("outline_bind_group_layout"),
+        });
+
+        Self {
+            mask_layout,
+            outline_layout,
+        }
+    }
+}
+
+impl OutlineCommon {
+    pub fn mask_layout(&self) -> &wgpu::BindGroupLayout {
+        &self.mask_layout
+    }
+
+    pub fn outline_layout(&self) -> &wgpu::BindGroupLayout {
+        &self.outline_layout
+    }
+}
+
+/// The object id, depth and mask targets of the outline of a view
+pub struct OutlineTargets {
+    buffer: wgpu::Buffer,
+    ids: wgpu::TextureView,
+    depth: wgpu::TextureView,
+    mask: wgpu::TextureView,
+    mask_bind_group: Arc<wgpu::BindGroup>,
+    outline_bind_group: Arc<wgpu::BindGroup>,
+}
+
+impl OutlineTargets {
+    pub fn new(
+        cx: &mut RenderContext,
+        depth_format: wgpu::TextureFormat,
+        width: u32,
+        height: u32,
+    ) -> Self {
+        let common = cx.singleton::<OutlineCommon>();
+
+        let buffer = cx.device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("outline buffer"),
+            contents: bytemuck::cast_slice(&[OutlineUniform::new(&Selection::default(), &OutlineSettings::default())]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let create_view = |label, format| {
+            cx.device.create_texture(&wgpu::TextureDescriptor {
+                label: Some(label),
+                size: wgpu::Extent3d {
+                    width,
+                    height,
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format,
+                usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+                view_formats: &[],
+            }).create_view(&wgpu::TextureViewDescriptor::default())
+        };
+
+        let ids = create_view("outline object id texture", ObjectIdPipeline::FORMAT);
+        let depth = create_view("outline depth texture", depth_format);
+        let mask = create_view("outline mask texture", MASK_FORMAT);
+
+        let create_bind_group = |label, layout, binding, view| cx.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding,
+                    resource: wgpu::BindingResource::TextureView(view),
+                },
+            ],
+            label: Some(label),
+        });
+
+        let mask_bind_group = create_bind_group("outline_mask_bind_group", common.mask_layout(), 1, &ids);
+        let outline_bind_group = create_bind_group("outline_bind_group", common.outline_layout(), 2, &mask);
+
+        Self {
+            buffer,
+            ids,
+            depth,
+            mask,
+            mask_bind_group: Arc::new(mask_bind_group),
+            outline_bind_group: Arc::new(outline_bind_group),
+        }
+    }
+
+    pub fn update(
+        &self,
+        queue: &wgpu::Queue,
+        selection: &Selection,
+        settings: &OutlineSettings,
+    ) {
+        queue.write_buffer(&self.buffer, 0, bytemuck::cast_slice(&[OutlineUniform::new(selection, settings)]));
+    }
+
+    /// The color attachment of the object id pass, to be drawn with [`ObjectIdPipeline`]: no object
+    pub fn ids_attachment(&self) -> wgpu::RenderPassColorAttachment<'_> {
+        wgpu::RenderPassColorAttachment {
+            view: &self.ids,
+            resolve_target: None,
+            ops: wgpu::Operations {
+                load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                store: wgpu::StoreOp::Store,
+            },
+        }
+    }
+
+    /// The depth attachment of the object id pass
+    pub fn depth_attachment(&self) -> wgpu::RenderPassDepthStencilAttachment<'_> {
+        wgpu::RenderPassDepthStencilAttachment {
+            view: &self.depth,
+            depth_ops: Some(wgpu::Operations {
+                load: wgpu::LoadOp::Clear(1.0),
+                store: wgpu::StoreOp::Discard,
+            }),
+            stencil_ops: None,
+        }
+    }
+
+    /// The color attachment of the mask pass
+    pub fn mask_attachment(&self) -> wgpu::RenderPassColorAttachment<'_> {
+        wgpu::RenderPassColorAttachment {
+            view: &self.mask,
+            resolve_target: None,
+            ops: wgpu::Operations {
+                load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                store: wgpu::StoreOp::Store,
+            },
+        }
+    }
+}
+
+/// Turns the object ids of [`OutlineTargets`] into the mask of the [`Selection`],
+/// with a full screen triangle.
+pub struct OutlineMaskPipeline {
+    pipeline: Pipeline,
+}
+
+impl Default for OutlineMaskPipeline {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl OutlineMaskPipeline {
+    pub fn new() -> Self {
+        let primitive = PrimitiveState {
+            topology: PrimitiveTopology::TriangleList,
+            ..Default::default()
+        };
+
+        let pipeline = Pipeline::from_builder(move |cx, _formats| {
+            let shader = cx.singleton::<OutlineShader>();
+
+            let common = cx.singleton::<OutlineCommon>();
+
+            let render_pipeline_layout =
+            cx.device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: None,
+                bind_group_layouts: &[
+                    common.mask_layout(),
+                ],
+                push_constant_ranges: &[],
+            });
+
+            cx.device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some("outline mask pipeline"),
+                layout: Some(&render_pipeline_layout),
+                vertex: wgpu::VertexState {
+                    module: &shader.shader,
+                    entry_point: "vs_main",
+                    buffers: &[],
+                    compilation_options: Default::default(),
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &shader.shader,
+                    entry_point: "fs_mask",
+                    targets: &[Some(wgpu::ColorTargetState {
+                        format: MASK_FORMAT,
+                        blend: None,
+                        write_mask: wgpu::ColorWrites::ALL,
+                    })],
+                    compilation_options: Default::default(),
+                }),
+                primitive,
+                depth_stencil: None,
+                multisample: Default::default(),
+                multiview: None,
+                cache: None,
+            })
+        });
+
+        Self {
+            pipeline,
+        }
+    }
+
+    /// Compute the mask of `targets`, `pass` must render into [`OutlineTargets::mask_attachment`]
+    pub fn render<'a>(
+        &self,
+        cx: &mut RenderContext,
+        pass: &mut Pass<'a>,
+        targets: &OutlineTargets,
+    ) {
+        let bind_group = targets.mask_bind_group.clone();
+
+        let pipeline = self.pipeline.get(cx, pass);
+
+        pass.defer(move |rp, _| {
+            rp.set_pipeline(&pipeline);
+            rp.set_bind_group(0, &bind_group, &[]);
+            rp.draw(0..3, 0..1);
+        });
+    }
+}
+
+/// Draws the outline of the mask of [`OutlineTargets`] over the surface, and highlights
+/// the masked objects, with a full screen triangle.
+pub struct OutlinePipeline {
+    pipeline: Pipeline,
+}
+
+impl Default for OutlinePipeline {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl OutlinePipeline {
+    pub fn new() -> Self {
+        let primitive = PrimitiveState {
+            topology: PrimitiveTopology::TriangleList,
+            ..Default::default()
+        };
+
+        let pipeline = Pipeline::from_builder(move |cx, formats| {
+            let shader = cx.singleton::<OutlineShader>();
+
+            let common = cx.singleton::<OutlineCommon>();
+
+            let render_pipeline_layout =
+            cx.device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: None,
+                bind_group_layouts: &[
+                    common.outline_layout(),
+                ],
+                push_constant_ranges: &[],
+            });
+
+            let targets = formats.color_targets(wgpu::BlendState::ALPHA_BLENDING);
+
+            cx.device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some("outline pipeline"),
+                layout: Some(&render_pipeline_layout),
+                vertex: wgpu::VertexState {
+                    module: &shader.shader,
+                    entry_point: "vs_main",
+                    buffers: &[],
+                    compilation_options: Default::default(),
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &shader.shader,
+                    entry_point: "fs_outline",
+                    targets: &targets,
+                    compilation_options: Default::default(),
+                }),
+                primitive,
+                depth_stencil: formats.depth_format.map(|format| wgpu::DepthStencilState {
+                    format,
+                    depth_write_enabled: false,
+                    depth_compare: wgpu::CompareFunction::Always,
+                    stencil: wgpu::StencilState::default(),
+                    bias: wgpu::DepthBiasState::default(),
+                }),
+                multisample: Default::default(),
+                multiview: None,
+                cache: None,
+            })
+        });
+
+        Self {
+            pipeline,
+        }
+    }
+
+    /// Outline the mask computed into `targets` onto the surface of `pass`
+    pub fn render<'a>(
+        &self,
+        cx: &mut RenderContext,
+        pass: &mut Pass<'a>,
+        targets: &OutlineTargets,
+    ) {
+        let bind_group = targets.outline_bind_group.clone();
+
+        let pipeline = self.pipeline.get(cx, pass);
+
+        pass.defer(move |rp, _| {
+            rp.set_pipeline(&pipeline);
+            rp.set_bind_group(0, &bind_group, &[]);
+            rp.draw(0..3, 0..1);
+        });
+    }
+}
@@ -0,0 +1,123 @@
+// ================================
+//            Inputs
+// ================================
+
+const MAX_SELECTED: u32 = 64u;
+
+struct Outline {
+    color: vec4<f32>,
+    hover_color: vec4<f32>,
+    // in pixels
+    width: u32,
+    // opacity of the color over the selected objects
+    fill_alpha: f32,
+    selected_count: u32,
+    // 0 if nothing is hovered
+    hovered: u32,
+    selected: array<vec4<u32>, 16>,
+};
+
+@group(0) @binding(0)
+var<uniform> outline: Outline;
+
+// only used by `fs_mask`
+@group(0) @binding(1)
+var id_texture: texture_2d<u32>;
+
+// only used by `fs_outline`
+@group(0) @binding(2)
+var mask_texture: texture_2d<f32>;
+
+// ================================
+//            Vertex
+// ================================
+
+// a triangle covering the whole screen
+@vertex
+fn vs_main(@builtin(vertex_index) vertex_index: u32) -> @builtin(position) vec4<f32> {
+    let uv = vec2<f32>(f32((vertex_index << 1u) & 2u), f32(vertex_index & 2u));
+    return vec4<f32>(uv * 2.0 - 1.0, 0.0, 1.0);
+}
+
+// ================================
+//            Mask
+// ================================
+
+fn is_selected(id: u32) -> bool {
+    let count = min(outline.selected_count, MAX_SELECTED);
+    for (var i = 0u; i < count; i++) {
+        if (outline.selected[i / 4u][i % 4u] == id) {
+            return true;
+        }
+    }
+    return false;
+}
+
+// 1 for the selected objects, 0.5 for the hovered one, 0 elsewhere
+@fragment
+fn fs_mask(@builtin(position) position: vec4<f32>) -> @location(0) vec4<f32> {
+    let id = textureLoad(id_texture, vec2<i32>(position.xy), 0).r;
+
+    var mask = 0.0;
+    if (id != 0u) {
+        if (is_selected(id)) {
+            mask = 1.0;
+        } else if (id == outline.hovered) {
+            mask = 0.5;
+        }
+    }
+
+    return vec4<f32>(mask, 0.0, 0.0, 1.0);
+}
+
+// ================================
+//            Outline
+// ================================
+
+fn mask_color(mask: f32) -> vec4<f32> {
+    return select(outline.hover_color, outline.color, mask > 0.75);
+}
+
+// dilates the mask by `width` pixels, the pixels outside of the mask but within
+// the dilation are the outline
+@fragment
+fn fs_outline(@builtin(position) position: vec4<f32>) -> @location(0) vec4<f32> {
+    let coords = vec2<i32>(position.xy);
+    let size = vec2<i32>(textureDimensions(mask_texture));
+
+    let center = textureLoad(mask_texture, coords, 0).r;
+    if (center > 0.0) {
+        let color = mask_color(center);
+        if (outline.fill_alpha <= 0.0) {
+            discard;
+        }
+        return vec4<f32>(color.rgb, color.a * outline.fill_alpha);
+    }
+
+    let r = i32(outline.width);
+    var mask = 0.0;
+    // distance to the nearest pixel of the mask, for an anti-aliased border
+    var distance = f32(r) + 1.0;
+    for (var y = -r; y <= r; y++) {
+        for (var x = -r; x <= r; x++) {
+            let d = length(vec2<f32>(f32(x), f32(y)));
+            if (d > f32(r) + 0.5) {
+                continue;
+            }
+            let c = clamp(coords + vec2<i32>(x, y), vec2<i32>(0), size - 1);
+            let m = textureLoad(mask_texture, c, 0).r;
+            if (m > 0.0) {
+                mask = max(mask, m);
+                distance = min(distance, d);
+            }
+        }
+    }
+
+    if (mask <= 0.0) {
+        discard;
+    }
+
+    let color = mask_color(mask);
+    let coverage = clamp(f32(r) + 0.5 - distance, 0.0, 1.0);
+    return vec4<f32>(color.rgb, color.a * coverage);
+}
@@ -0,0 +1,600 @@
+use std::sync::Arc;
+
+use wgpu::{util::DeviceExt, Device, PrimitiveState, PrimitiveTopology, ShaderModule};
+
+use crate::{decl_vertex_raw_repr, instance::Instance3d, Pass, ProjectionCameraCommon, RenderContext, Res, SingletonResource, Texture2d, Texture2dOptions, VertexBufferSlice, VertexRawRepr};
+
+use super::{shader_with_globals, Pipeline, RenderModeState};
+
+decl_vertex_raw_repr! {
+    #[derive(Debug)]
+    struct Vertex (Vertex step mode) {
+        pub position: [f32; 3] as [7 => Float32x3],
+        pub normal: [f32; 3] as [8 => Float32x3],
+        pub uv: [f32; 2] as [9 => Float32x2],
+        /// Tangent in `xyz` and bitangent sign in `w`, only used with normal maps
+        pub tangent: [f32; 4] as [10 => Float32x4],
+    }
+}
+
+/// The maximum number of lights in a [`PbrLighting`]
+pub const MAX_LIGHTS: usize = 8;
+
+/// A shader for physically based (metallic-roughness) shading
+pub struct PbrShader {
+    shader: ShaderModule,
+}
+
+impl PbrShader {
+    /// Create a new PBR shader
+    pub fn new(
+        device: &Device,
+    ) -> Self {
+        Self {
+            shader: shader_with_globals(device, "pbr.wgsl", include_str!("pbr.wgsl")),
+        }
+    }
+}
+
+impl SingletonResource for PbrShader {
+    fn init(ctx: &mut RenderContext) -> Self {
+        Self::new(ctx.device)
+    }
+}
+
+// ================================
+//            Material
+// ================================
+
+/// The constant factors of a [`PbrMaterial`], multiplied by the texture maps if present
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PbrMaterialSettings {
+    /// Linear RGBA base color
+    pub base_color: [f32; 4],
+    pub metallic: f32,
+    pub roughness: f32,
+    /// Linear RGB emitted radiance
+    pub emissive: [f32; 3],
+    /// Strength of the normal map
+    pub normal_scale: f32,
+}
+
+impl Default for PbrMaterialSettings {
+    fn default() -> Self {
+        Self {
+            base_color: [1.0, 1.0, 1.0, 1.0],
+            metallic: 0.0,
+            roughness: 0.5,
+            emissive: [0.0, 0.0, 0.0],
+            normal_scale: 1.0,
+        }
+    }
+}
+
+/// Optional texture maps of a [`PbrMaterial`], following the glTF conventions
+#[derive(Default, Clone, Copy)]
+pub struct PbrTextures<'a> {
+    /// sRGB base color and alpha
+    pub base_color: Option<&'a Texture2d>,
+    /// Linear, roughness in the green channel and metallic in the blue channel
+    pub metallic_roughness: Option<&'a Texture2d>,
+    /// Linear tangent space normal map
+    pub normal: Option<&'a Texture2d>,
+    /// sRGB emissive color
+    pub emissive: Option<&'a Texture2d>,
+}
+
+const HAS_BASE_COLOR_MAP: u32 = 1;
+const HAS_METALLIC_ROUGHNESS_MAP: u32 = 2;
+const HAS_NORMAL_MAP: u32 = 4;
+const HAS_EMISSIVE_MAP: u32 = 8;
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct PbrMaterialUniform {
+    base_color: [f32; 4],
+    emissive: [f32; 4],
+    metallic: f32,
+    roughness: f32,
+    normal_scale: f32,
+    flags: u32,
+}
+
+impl PbrMaterialUniform {
+    fn new(settings: &PbrMaterialSettings, flags: u32) -> Self {
+        Self {
+            base_color: settings.base_color,
+            emissive: [settings.emissive[0], settings.emissive[1], settings.emissive[2], 0.0],
+            metallic: settings.metallic,
+            roughness: settings.roughness,
+            normal_scale: settings.normal_scale,
+            flags,
+        }
+    }
+}
+
+/// The bind group layout of [`PbrMaterial`]s and the placeholders for missing texture maps
+pub struct PbrMaterialCommon {
+    bind_group_layout: wgpu::BindGroupLayout,
+    white: Texture2d,
+    flat_normal: Texture2d,
+}
+
+impl SingletonResource for PbrMaterialCommon {
+    fn init(ctx: &mut RenderContext) -> Self {
+        let texture_entry = |binding: u32| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Texture {
+                multisampled: false,
+                view_dimension: wgpu::TextureViewDimension::D2,
+                sample_type: wgpu::TextureSampleType::Float { filterable: true },
+            },
+            count: None,
+        };
+        let sampler_entry = |binding: u32| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+            count: None,
+        };
+
+        let bind_group_layout = ctx.device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                texture_entry(1),
+                sampler_entry(2),
+                texture_entry(3),
+                sampler_entry(4),
+                texture_entry(5),
+                sampler_entry(6),
+                texture_entry(7),
+                sampler_entry(8),
+            ],
+            label: Some("pbr_material_bind_group_layout"),
+        });
+
+        let white = Texture2d::solid(ctx, [255, 255, 255, 255]);
+        let flat_normal = Texture2d::from_rgba8(ctx, 1, 1, &[128, 128, 255, 255], &Texture2dOptions {
+            srgb: false,
+            mipmaps: false,
+            ..Default::default()
+        });
+
+        Self {
+            bind_group_layout,
+            white,
+            flat_normal,
+        }
+    }
+}
+
+impl PbrMaterialCommon {
+    pub fn layout(&self) -> &wgpu::BindGroupLayout {
+        &self.bind_group_layout
+    }
+}
+
+/// A metallic-roughness material, bound as `@group(1)` by [`PbrPipeline`]
+pub struct PbrMaterial {
+    buffer: wgpu::Buffer,
+    flags: u32,
+    bind_group: Arc<wgpu::BindGroup>,
+}
+
+impl PbrMaterial {
+    pub fn new(
+        cx: &mut RenderContext,
+        settings: &PbrMaterialSettings,
+        textures: PbrTextures,
+    ) -> Self {
+        let common = cx.singleton::<PbrMaterialCommon>();
+
+        let flags = [
+            (textures.base_color, HAS_BASE_COLOR_MAP),
+            (textures.metallic_roughness, HAS_METALLIC_ROUGHNESS_MAP),
+            (textures.normal, HAS_NORMAL_MAP),
+            (textures.emissive, HAS_EMISSIVE_MAP),
+        ].iter().filter(|(t, _)| t.is_some()).fold(0, |flags, (_, flag)| flags | flag);
+
+        let base_color = textures.base_color.unwrap_or(&common.white);
+        let metallic_roughness = textures.metallic_roughness.unwrap_or(&common.white);
+        let normal = textures.normal.unwrap_or(&common.flat_normal);
+        let emissive = textures.emissive.unwrap_or(&common.white);
+
+        let buffer = cx.device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("pbr material buffer"),
+            contents: bytemuck::cast_slice(&[PbrMaterialUniform::new(settings, flags)]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let bind_group = cx.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: common.layout(),
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry { binding: 1, resource: wgpu::BindingResource::TextureView(base_color.view()) },
+                wgpu::BindGroupEntry { binding: 2, resource: wgpu::BindingResource::Sampler(base_color.sampler()) },
+                wgpu::BindGroupEntry { binding: 3, resource: wgpu::BindingResource::TextureView(metallic_roughness.view()) },
+                wgpu::BindGroupEntry { binding: 4, resource: wgpu::BindingResource::Sampler(metallic_roughness.sampler()) },
+                wgpu::BindGroupEntry { binding: 5, resource: wgpu::BindingResource::TextureView(normal.view()) },
+                wgpu::BindGroupEntry { binding: 6, resource: wgpu::BindingResource::Sampler(normal.sampler()) },
+                wgpu::BindGroupEntry { binding: 7, resource: wgpu::BindingResource::TextureView(emissive.view()) },
+                wgpu::BindGroupEntry { binding: 8, resource: wgpu::BindingResource::Sampler(emissive.sampler()) },
+            ],
+            label: Some("pbr_material_bind_group"),
+        });
+
+        Self {
+            buffer,
+            flags,
+            bind_group: Arc::new(bind_group),
+        }
+    }
+
+    /// Update the constant factors, the texture maps can't be changed
+    pub fn update(&self, queue: &wgpu::Queue, settings: &PbrMaterialSettings) {
+        queue.write_buffer(&self.buffer, 0, bytemuck::cast_slice(&[PbrMaterialUniform::new(settings, self.flags)]));
+    }
+}
+
+// ================================
+//            Lighting
+// ================================
+
+/// A light of a [`PbrLighting`], colors are linear RGB
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PbrLight {
+    /// A light infinitely far away
+    Directional {
+        /// Direction towards the light
+        direction: [f32; 3],
+        color: [f32; 3],
+        intensity: f32,
+    },
+    /// A light emitting from a point, with inverse square falloff
+    Point {
+        position: [f32; 3],
+        color: [f32; 3],
+        intensity: f32,
+        /// Distance at which the light fades out completely, `0` for no limit
+        range: f32,
+    },
+    /// A directional light following [`ProjectionCamera::light_dir`](crate::ProjectionCamera::light_dir)
+    Camera {
+        color: [f32; 3],
+        intensity: f32,
+    },
+}
+
+/// The ambient term of a [`PbrLighting`]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PbrAmbient {
+    /// The same light from every direction
+    Constant([f32; 3]),
+    /// A simple environment: a sky color from above and a ground color from below
+    Hemisphere {
+        sky: [f32; 3],
+        ground: [f32; 3],
+        up: [f32; 3],
+    },
+}
+
+/// The lights used by [`PbrPipeline`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct PbrLighting {
+    /// At most [`MAX_LIGHTS`] lights, the others are ignored
+    pub lights: Vec<PbrLight>,
+    pub ambient: PbrAmbient,
+}
+
+impl Default for PbrLighting {
+    fn default() -> Self {
+        Self {
+            lights: vec![
+                PbrLight::Camera {
+                    color: [1.0, 1.0, 1.0],
+                    intensity: 3.0,
+                },
+            ],
+            ambient: PbrAmbient::Hemisphere {
+                sky: [0.3, 0.3, 0.35],
+                ground: [0.1, 0.1, 0.1],
+                up: [0.0, 1.0, 0.0],
+            },
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct LightRaw {
+    vector: [f32; 3],
+    kind: u32,
+    color: [f32; 3],
+    intensity: f32,
+    range: f32,
+    _padding: [f32; 3],
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct LightingUniform {
+    lights: [LightRaw; MAX_LIGHTS],
+    count: u32,
+    ambient_mode: u32,
+    _padding0: [u32; 2],
+    ambient_sky: [f32; 3],
+    _padding1: f32,
+    ambient_ground: [f32; 3],
+    _padding2: f32,
+    up: [f32; 3],
+    _padding3: f32,
+}
+
+impl From<&PbrLighting> for LightingUniform {
+    fn from(lighting: &PbrLighting) -> Self {
+        let mut uniform: Self = bytemuck::Zeroable::zeroed();
+
+        if lighting.lights.len() > MAX_LIGHTS {
+            log::warn!("Too many PBR lights ({}), only the first {MAX_LIGHTS} are used", lighting.lights.len());
+        }
+
+        for (raw, light) in uniform.lights.iter_mut().zip(&lighting.lights) {
+            *raw = match *light {
+                PbrLight::Directional { direction, color, intensity } => LightRaw { vector: direction, kind: 0, color, intensity, ..bytemuck::Zeroable::zeroed() },
+                PbrLight::Point { position, color, intensity, range } => LightRaw { vector: position, kind: 1, color, intensity, range, ..bytemuck::Zeroable::zeroed() },
+                PbrLight::Camera { color, intensity } => LightRaw { kind: 2, color, intensity, ..bytemuck::Zeroable::zeroed() },
+            };
+        }
+        uniform.count = lighting.lights.len().min(MAX_LIGHTS) as u32;
+
+        match lighting.ambient {
+            PbrAmbient::Constant(color) => {
+                uniform.ambient_mode = 0;
+                uniform.ambient_sky = color;
+            },
+            PbrAmbient::Hemisphere { sky, ground, up } => {
+                uniform.ambient_mode = 1;
+                uniform.ambient_sky = sky;
+                uniform.ambient_ground = ground;
+                uniform.up = up;
+            },
+        }
+
+        uniform
+    }
+}
+
+/// The bind group layout of [`PbrLightingBuffer`]s
+pub struct PbrLightingCommon {
+    bind_group_layout: wgpu::BindGroupLayout,
+}
+
+impl SingletonResource for PbrLightingCommon {
+    fn init(ctx: &mut RenderContext) -> Self {
+        let bind_group_layout = ctx.device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                }
+            ],
+            label: Some("pbr_lighting_bind_group_layout"),
+        });
+
+        Self {
+            bind_group_layout,
+        }
+    }
+}
+
+impl PbrLightingCommon {
+    pub fn layout(&self) -> &wgpu::BindGroupLayout {
+        &self.bind_group_layout
+    }
+}
+
+/// The GPU side of [`PbrLighting`], bound as `@group(2)` by [`PbrPipeline`]
+pub struct PbrLightingBuffer {
+    buffer: wgpu::Buffer,
+    bind_group: Arc<wgpu::BindGroup>,
+}
+
+impl PbrLightingBuffer {
+    pub fn new(
+        cx: &mut RenderContext,
+        lighting: &PbrLighting,
+    ) -> Self {
+        let common = cx.singleton::<PbrLightingCommon>();
+
+        let buffer = cx.device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("pbr lighting buffer"),
+            contents: bytemuck::cast_slice(&[LightingUniform::from(lighting)]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let bind_group = cx.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: common.layout(),
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: buffer.as_entire_binding(),
+                }
+            ],
+            label: Some("pbr_lighting_bind_group"),
+        });
+
+        Self {
+            buffer,
+            bind_group: Arc::new(bind_group),
+        }
+    }
+
+    pub fn update(&self, queue: &wgpu::Queue, lighting: &PbrLighting) {
+        queue.write_buffer(&self.buffer, 0, bytemuck::cast_slice(&[LightingUniform::from(lighting)]));
+    }
+}
+
+// ================================
+//            Pipeline
+// ================================
+
+/// Physically based (metallic-roughness) meshes.
+///
+/// # Remarks
+/// The output is linear radiance, a tone mapping step is needed for
+/// physically plausible light intensities.
+pub struct PbrPipeline {
+    pipeline: Pipeline,
+    order_independent: bool,
+    default_lighting: Res<PbrLightingBuffer>,
+}
+
+impl PbrPipeline {
+    pub fn new(
+        topology: PrimitiveTopology,
+        depth_compare: wgpu::CompareFunction,
+        use_depth_stencil: bool,
+    ) -> Self {
+        let primitive = PrimitiveState {
+            topology,
+            ..Default::default()
+        };
+
+        let pipeline = Pipeline::from_builder(move |cx, formats| {
+            let shader = cx.singleton::<PbrShader>();
+            let render_mode = RenderModeState::new(cx.device, formats.render_mode, primitive.topology);
+
+            let camera_common = cx.singleton::<ProjectionCameraCommon>();
+            let material_common = cx.singleton::<PbrMaterialCommon>();
+            let lighting_common = cx.singleton::<PbrLightingCommon>();
+
+            let render_pipeline_layout =
+            cx.device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: None,
+                bind_group_layouts: &[
+                    camera_common.layout(),
+                    material_common.layout(),
+                    lighting_common.layout(),
+                ],
+                push_constant_ranges: &[],
+            });
+
+            let targets = formats.color_targets(wgpu::BlendState::ALPHA_BLENDING);
+
+            cx.device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some("pbr pipeline"),
+                layout: Some(&render_pipeline_layout),
+                vertex: wgpu::VertexState {
+                    module: &shader.shader,
+                    entry_point: "vs_main",
+                    buffers: &[
+                        Vertex::desc(),
+                        Instance3d::desc(),
+                    ],
+                    compilation_options: Default::default(),
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &shader.shader,
+                    entry_point: formats.fragment_entry_point(),
+                    targets: &targets,
+                    compilation_options: wgpu::PipelineCompilationOptions {
+                        constants: &render_mode.constants,
+                        ..Default::default()
+                    },
+                }),
+                primitive: PrimitiveState {
+                    polygon_mode: render_mode.polygon_mode,
+                    ..primitive
+                },
+                depth_stencil: formats.depth_format.map(|format| wgpu::DepthStencilState {
+                    format,
+                    depth_write_enabled: use_depth_stencil && render_mode.depth_write && formats.depth_write(),
+                    depth_compare: formats.depth_compare(depth_compare),
+                    stencil: formats.stencil(),
+                    bias: formats.depth_bias(),
+                }),
+                multisample: Default::default(),
+                multiview: None,
+                cache: None,
+            })
+        });
+
+        Self {
+            pipeline,
+            order_independent: false,
+            default_lighting: Res::new(|cx: &mut RenderContext| PbrLightingBuffer::new(cx, &PbrLighting::default())),
+        }
+    }
+
+    /// Draw into the order-independent transparency pass, see [`MeshPipeline::order_independent`](super::builder::MeshPipeline::order_independent)
+    pub fn order_independent(self) -> Self {
+        Self {
+            order_independent: true,
+            ..self
+        }
+    }
+
+    /// Render with the default [`PbrLighting`]
+    pub fn render<'a>(
+        &self,
+        cx: &mut RenderContext,
+        pass: &mut Pass<'a>,
+        vertices: impl Into<VertexBufferSlice<Vertex>>,
+        instances: impl Into<VertexBufferSlice<Instance3d>>,
+        material: &PbrMaterial,
+    ) {
+        let lighting = cx.resource(&self.default_lighting);
+        self.render_with_lighting(cx, pass, vertices, instances, material, &lighting);
+    }
+
+    pub fn render_with_lighting<'a>(
+        &self,
+        cx: &mut RenderContext,
+        pass: &mut Pass<'a>,
+        vertices: impl Into<VertexBufferSlice<Vertex>>,
+        instances: impl Into<VertexBufferSlice<Instance3d>>,
+        material: &PbrMaterial,
+        lighting: &PbrLightingBuffer,
+    ) {
+        let vertices: VertexBufferSlice<Vertex> = vertices.into();
+        let instances: VertexBufferSlice<Instance3d> = instances.into();
+        let material = material.bind_group.clone();
+        let lighting = lighting.bind_group.clone();
+
+        let kind = if self.order_independent {
+            pass.transparent_kind()
+        } else {
+            pass.kind()
+        };
+        let pipeline = self.pipeline.get_for(cx, pass, kind);
+
+        pass.defer_for(kind, move |rp, globals| {
+            rp.set_pipeline(&pipeline);
+            rp.set_bind_group(0, globals, &[]);
+            rp.set_bind_group(1, &material, &[]);
+            rp.set_bind_group(2, &lighting, &[]);
+            rp.set_vertex_buffer(0, vertices.buffer.slice(..));
+            rp.set_vertex_buffer(1, instances.buffer.slice(..));
+            rp.draw(vertices.range.clone(), instances.range.clone());
+        });
+    }
+}
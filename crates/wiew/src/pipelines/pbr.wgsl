@@ -0,0 +1,287 @@
+// ================================
+//            Material
+// ================================
+
+const HAS_BASE_COLOR_MAP: u32 = 1u;
+const HAS_METALLIC_ROUGHNESS_MAP: u32 = 2u;
+const HAS_NORMAL_MAP: u32 = 4u;
+const HAS_EMISSIVE_MAP: u32 = 8u;
+
+struct PbrMaterial {
+    base_color: vec4<f32>,
+    emissive: vec4<f32>,
+    metallic: f32,
+    roughness: f32,
+    normal_scale: f32,
+    flags: u32,
+};
+
+@group(1) @binding(0)
+var<uniform> material: PbrMaterial;
+@group(1) @binding(1)
+var base_color_texture: texture_2d<f32>;
+@group(1) @binding(2)
+var base_color_sampler: sampler;
+@group(1) @binding(3)
+var metallic_roughness_texture: texture_2d<f32>;
+@group(1) @binding(4)
+var metallic_roughness_sampler: sampler;
+@group(1) @binding(5)
+var normal_texture: texture_2d<f32>;
+@group(1) @binding(6)
+var normal_sampler: sampler;
+@group(1) @binding(7)
+var emissive_texture: texture_2d<f32>;
+@group(1) @binding(8)
+var emissive_sampler: sampler;
+
+// ================================
+//            Lighting
+// ================================
+
+const LIGHT_DIRECTIONAL: u32 = 0u;
+const LIGHT_POINT: u32 = 1u;
+const LIGHT_CAMERA: u32 = 2u;
+
+const AMBIENT_CONSTANT: u32 = 0u;
+const AMBIENT_HEMISPHERE: u32 = 1u;
+
+const MAX_LIGHTS: u32 = 8u;
+
+struct Light {
+    // direction towards the light (directional) or position (point)
+    vector: vec3<f32>,
+    kind: u32,
+    color: vec3<f32>,
+    intensity: f32,
+    range: f32,
+};
+
+struct Lighting {
+    lights: array<Light, MAX_LIGHTS>,
+    count: u32,
+    ambient_mode: u32,
+    ambient_sky: vec3<f32>,
+    ambient_ground: vec3<f32>,
+    up: vec3<f32>,
+};
+
+@group(2) @binding(0)
+var<uniform> lighting: Lighting;
+
+// ================================
+//            Vertex
+// ================================
+
+struct VertexInput {
+    @location(7) position: vec3<f32>,
+    @location(8) normal: vec3<f32>,
+    @location(9) uv: vec2<f32>,
+    @location(10) tangent: vec4<f32>,
+};
+
+struct InstanceInput {
+    @location(0) model_0: vec4<f32>,
+    @location(1) model_1: vec4<f32>,
+    @location(2) model_2: vec4<f32>,
+    @location(3) model_3: vec4<f32>,
+    @location(4) model_inv_tr_0: vec3<f32>,
+    @location(5) model_inv_tr_1: vec3<f32>,
+    @location(6) model_inv_tr_2: vec3<f32>,
+    @location(13) color: vec4<f32>,
+    @location(14) object_id: u32,
+    @location(15) visible: u32,
+};
+
+@vertex
+fn vs_main(
+    @builtin(vertex_index) vertex_index: u32,
+    model: VertexInput,
+    instance: InstanceInput,
+) -> VertexOutput {
+    let model_matrix = mat4x4<f32>(
+        instance.model_0,
+        instance.model_1,
+        instance.model_2,
+        instance.model_3,
+    );
+    let normal_matrix = mat3x3<f32>(
+        instance.model_inv_tr_0,
+        instance.model_inv_tr_1,
+        instance.model_inv_tr_2,
+    );
+
+    let world_position = model_matrix * vec4<f32>(model.position, 1.0);
+
+    var out: VertexOutput;
+    out.clip_position = camera.proj * camera.view * world_position;
+    out.world_position = world_position.xyz;
+    out.world_normal = normal_matrix * model.normal;
+    out.world_tangent = vec4<f32>((model_matrix * vec4<f32>(model.tangent.xyz, 0.0)).xyz, model.tangent.w);
+    out.uv = model.uv;
+    out.color = instance.color;
+    out.object_id = instance.object_id;
+    out.barycentric = corner_barycentric(vertex_index);
+
+    // hidden instances are moved outside of the clip volume
+    if (instance.visible == 0u) {
+        out.clip_position = vec4<f32>(2.0, 2.0, 2.0, 1.0);
+    }
+
+    return out;
+}
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) color: vec4<f32>,
+    @location(1) @interpolate(flat) object_id: u32,
+    @location(2) world_position: vec3<f32>,
+    @location(3) world_normal: vec3<f32>,
+    @location(4) world_tangent: vec4<f32>,
+    @location(5) uv: vec2<f32>,
+    @location(6) barycentric: vec3<f32>,
+};
+
+// ================================
+//            Fragment
+// ================================
+
+const PI: f32 = 3.14159265359;
+
+fn distribution_ggx(n_dot_h: f32, roughness: f32) -> f32 {
+    let a = roughness * roughness;
+    let a2 = a * a;
+    let d = n_dot_h * n_dot_h * (a2 - 1.0) + 1.0;
+    return a2 / (PI * d * d);
+}
+
+fn geometry_smith(n_dot_v: f32, n_dot_l: f32, roughness: f32) -> f32 {
+    let r = roughness + 1.0;
+    let k = r * r / 8.0;
+    let g_v = n_dot_v / (n_dot_v * (1.0 - k) + k);
+    let g_l = n_dot_l / (n_dot_l * (1.0 - k) + k);
+    return g_v * g_l;
+}
+
+fn fresnel_schlick(cos_theta: f32, f0: vec3<f32>) -> vec3<f32> {
+    return f0 + (1.0 - f0) * pow(clamp(1.0 - cos_theta, 0.0, 1.0), 5.0);
+}
+
+// radiance reflected towards `v` by a light coming from `l`
+fn brdf(
+    n: vec3<f32>,
+    v: vec3<f32>,
+    l: vec3<f32>,
+    albedo: vec3<f32>,
+    metallic: f32,
+    roughness: f32,
+) -> vec3<f32> {
+    let h = normalize(v + l);
+    let n_dot_l = max(dot(n, l), 0.0);
+    let n_dot_v = max(dot(n, v), 1e-4);
+    let n_dot_h = max(dot(n, h), 0.0);
+
+    let f0 = mix(vec3<f32>(0.04), albedo, metallic);
+    let f = fresnel_schlick(max(dot(h, v), 0.0), f0);
+    let specular = distribution_ggx(n_dot_h, roughness) * geometry_smith(n_dot_v, n_dot_l, roughness) * f
+        / max(4.0 * n_dot_v * n_dot_l, 1e-4);
+    let k_d = (vec3<f32>(1.0) - f) * (1.0 - metallic);
+
+    return (k_d * albedo / PI + specular) * n_dot_l;
+}
+
+fn ambient(n: vec3<f32>) -> vec3<f32> {
+    if (lighting.ambient_mode == AMBIENT_HEMISPHERE) {
+        let t = dot(n, normalize(lighting.up)) * 0.5 + 0.5;
+        return mix(lighting.ambient_ground, lighting.ambient_sky, t);
+    }
+    return lighting.ambient_sky;
+}
+
+fn shade(
+    in: VertexOutput,
+    front_facing: bool,
+) -> vec4<f32> {
+    clip_fragment(in.world_position);
+
+    var base_color = material.base_color * in.color;
+    if ((material.flags & HAS_BASE_COLOR_MAP) != 0u) {
+        base_color *= textureSample(base_color_texture, base_color_sampler, in.uv);
+    }
+
+    var metallic = material.metallic;
+    var roughness = material.roughness;
+    if ((material.flags & HAS_METALLIC_ROUGHNESS_MAP) != 0u) {
+        // glTF convention: roughness in green, metallic in blue
+        let mr = textureSample(metallic_roughness_texture, metallic_roughness_sampler, in.uv);
+        roughness *= mr.g;
+        metallic *= mr.b;
+    }
+    roughness = clamp(roughness, 0.04, 1.0);
+
+    var n = normalize(in.world_normal);
+    if ((material.flags & HAS_NORMAL_MAP) != 0u) {
+        let t = normalize(in.world_tangent.xyz - n * dot(n, in.world_tangent.xyz));
+        let b = cross(n, t) * in.world_tangent.w;
+        let tn = textureSample(normal_texture, normal_sampler, in.uv).xyz * 2.0 - 1.0;
+        n = normalize(mat3x3<f32>(t, b, n) * vec3<f32>(tn.xy * material.normal_scale, tn.z));
+    }
+    if (!front_facing) {
+        n = -n;
+    }
+
+    var emissive = material.emissive.rgb;
+    if ((material.flags & HAS_EMISSIVE_MAP) != 0u) {
+        emissive *= textureSample(emissive_texture, emissive_sampler, in.uv).rgb;
+    }
+
+    let v = normalize(camera.view_point - in.world_position);
+
+    var color = ambient(n) * base_color.rgb + emissive;
+
+    for (var i = 0u; i < min(lighting.count, MAX_LIGHTS); i++) {
+        let light = lighting.lights[i];
+
+        var l: vec3<f32>;
+        var attenuation = 1.0;
+        switch light.kind {
+            case LIGHT_POINT: {
+                let d = light.vector - in.world_position;
+                let distance = length(d);
+                l = d / distance;
+                attenuation = 1.0 / (1.0 + distance * distance);
+                if (light.range > 0.0) {
+                    attenuation *= clamp(1.0 - pow(distance / light.range, 4.0), 0.0, 1.0);
+                }
+            }
+            case LIGHT_CAMERA: {
+                l = normalize(camera.light_dir);
+                attenuation = shadow_factor(in.world_position, n);
+            }
+            default: {
+                l = normalize(light.vector);
+            }
+        }
+
+        color += brdf(n, v, l, base_color.rgb, metallic, roughness) * light.color * light.intensity * attenuation;
+    }
+
+    return apply_fog(apply_render_mode(vec4<f32>(color, base_color.a), in.barycentric), in.world_position, in.clip_position.xy);
+}
+
+@fragment
+fn fs_main(
+    in: VertexOutput,
+    @builtin(front_facing) front_facing: bool,
+) -> @location(0) vec4<f32> {
+    return shade(in, front_facing);
+}
+
+// transparent pass of the view, see `oit_output`
+@fragment
+fn fs_oit(
+    in: VertexOutput,
+    @builtin(front_facing) front_facing: bool,
+) -> OitOutput {
+    return oit_output(shade(in, front_facing), in.clip_position.z);
+}
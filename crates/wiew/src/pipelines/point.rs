@@ -0,0 +1,359 @@
+use std::sync::Arc;
+
+use wgpu::{util::DeviceExt, Device, PrimitiveState, PrimitiveTopology, ShaderModule};
+
+use crate::{decl_vertex_raw_repr, instance::Instance3d, Pass, PassKind, ProjectionCameraCommon, RenderContext, Res, SingletonResource, VertexBufferSlice, VertexRawRepr};
+
+use super::{shader_with_globals, Pipeline};
+
+decl_vertex_raw_repr! {
+    /// A point of a point cloud.
+    ///
+    /// Points are stepped per instance: each of them is expanded into a quad
+    /// by the vertex shader.
+    #[derive(Debug)]
+    struct Vertex (Instance step mode) {
+        pub position: [f32; 3] as [7 => Float32x3],
+        /// Multiplies [`PointStyle::size`]
+        pub size: f32 as [8 => Float32],
+        pub color: [f32; 4] as [9 => Float32x4],
+    }
+}
+
+impl Vertex {
+    pub const fn new(position: [f32; 3], color: [f32; 4]) -> Self {
+        Self {
+            position,
+            size: 1.0,
+            color,
+        }
+    }
+}
+
+/// A shader for point sprites
+pub struct PointShader {
+    shader: ShaderModule,
+}
+
+impl PointShader {
+    /// Create a new point shader
+    pub fn new(
+        device: &Device,
+    ) -> Self {
+        Self {
+            shader: shader_with_globals(device, "point.wgsl", include_str!("point.wgsl")),
+        }
+    }
+}
+
+impl SingletonResource for PointShader {
+    fn init(ctx: &mut RenderContext) -> Self {
+        Self::new(ctx.device)
+    }
+}
+
+/// How a point is drawn
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PointShape {
+    Square,
+    /// An antialiased disc
+    Disc,
+    /// A sphere impostor, shaded by the camera light and writing a depth-correct surface
+    Sphere,
+}
+
+impl PointShape {
+    fn entry_point(&self) -> &'static str {
+        match self {
+            PointShape::Square => "fs_square",
+            PointShape::Disc => "fs_disc",
+            PointShape::Sphere => "fs_sphere",
+        }
+    }
+}
+
+/// The diameter of a point
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PointSize {
+    /// Constant size on screen
+    Pixels(f32),
+    /// Size in world units, points get smaller as they go away from the camera
+    World(f32),
+}
+
+/// Appearance of the points drawn by [`PointPipeline`]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PointStyle {
+    pub size: PointSize,
+    /// Limits of the diameter on screen, in pixels
+    pub min_size: f32,
+    pub max_size: f32,
+    /// Constant, linear and quadratic attenuation by the distance `d` to the camera:
+    /// the size is divided by `a[0] + a[1] * d + a[2] * d * d`
+    pub attenuation: [f32; 3],
+}
+
+impl Default for PointStyle {
+    fn default() -> Self {
+        Self {
+            size: PointSize::Pixels(4.0),
+            min_size: 1.0,
+            max_size: 256.0,
+            attenuation: [1.0, 0.0, 0.0],
+        }
+    }
+}
+
+impl PointStyle {
+    pub fn pixels(size: f32) -> Self {
+        Self {
+            size: PointSize::Pixels(size),
+            ..Default::default()
+        }
+    }
+
+    pub fn world(size: f32) -> Self {
+        Self {
+            size: PointSize::World(size),
+            ..Default::default()
+        }
+    }
+
+    pub fn with_attenuation(self, constant: f32, linear: f32, quadratic: f32) -> Self {
+        Self {
+            attenuation: [constant, linear, quadratic],
+            ..self
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct PointStyleUniform {
+    size: f32,
+    size_unit: u32,
+    min_size: f32,
+    max_size: f32,
+    attenuation: [f32; 3],
+    _padding: f32,
+}
+
+impl From<&PointStyle> for PointStyleUniform {
+    fn from(style: &PointStyle) -> Self {
+        let (size, size_unit) = match style.size {
+            PointSize::Pixels(size) => (size, 0),
+            PointSize::World(size) => (size, 1),
+        };
+
+        Self {
+            size,
+            size_unit,
+            min_size: style.min_size,
+            max_size: style.max_size,
+            attenuation: style.attenuation,
+            _padding: 0.0,
+        }
+    }
+}
+
+/// The bind group layout of [`PointMaterial`]s
+pub struct PointMaterialCommon {
+    bind_group_layout: wgpu::BindGroupLayout,
+}
+
+impl SingletonResource for PointMaterialCommon {
+    fn init(ctx: &mut RenderContext) -> Self {
+        let bind_group_layout = ctx.device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::VERTEX,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                }
+            ],
+            label: Some("point_material_bind_group_layout"),
+        });
+
+        Self {
+            bind_group_layout,
+        }
+    }
+}
+
+impl PointMaterialCommon {
+    pub fn layout(&self) -> &wgpu::BindGroupLayout {
+        &self.bind_group_layout
+    }
+}
+
+/// The GPU side of [`PointStyle`], bound as `@group(1)` by [`PointPipeline`]
+pub struct PointMaterial {
+    buffer: wgpu::Buffer,
+    bind_group: Arc<wgpu::BindGroup>,
+}
+
+impl PointMaterial {
+    pub fn new(
+        cx: &mut RenderContext,
+        style: &PointStyle,
+    ) -> Self {
+        let common = cx.singleton::<PointMaterialCommon>();
+
+        let buffer = cx.device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("point material buffer"),
+            contents: bytemuck::cast_slice(&[PointStyleUniform::from(style)]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let bind_group = cx.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: common.layout(),
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: buffer.as_entire_binding(),
+                }
+            ],
+            label: Some("point_material_bind_group"),
+        });
+
+        Self {
+            buffer,
+            bind_group: Arc::new(bind_group),
+        }
+    }
+
+    pub fn update(&self, queue: &wgpu::Queue, style: &PointStyle) {
+        queue.write_buffer(&self.buffer, 0, bytemuck::cast_slice(&[PointStyleUniform::from(style)]));
+    }
+}
+
+/// Point clouds drawn as sized sprites.
+///
+/// Unlike the other pipelines, the points are the instances: the [`Instance3d`]
+/// buffer is bound with a stride of 0, so the first instance of the given slice
+/// places the whole cloud.
+pub struct PointPipeline {
+    pipeline: Pipeline,
+    default_material: Res<PointMaterial>,
+}
+
+impl PointPipeline {
+    pub fn new(
+        shape: PointShape,
+        depth_compare: wgpu::CompareFunction,
+        use_depth_stencil: bool,
+    ) -> Self {
+        let primitive = PrimitiveState {
+            topology: PrimitiveTopology::TriangleList,
+            ..Default::default()
+        };
+
+        let pipeline = Pipeline::from_builder(move |cx, formats| {
+            let shader = cx.singleton::<PointShader>();
+
+            let camera_common = cx.singleton::<ProjectionCameraCommon>();
+            let material_common = cx.singleton::<PointMaterialCommon>();
+
+            let render_pipeline_layout =
+            cx.device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: None,
+                bind_group_layouts: &[
+                    camera_common.layout(),
+                    material_common.layout(),
+                ],
+                push_constant_ranges: &[],
+            });
+
+            let targets = formats.color_targets(wgpu::BlendState::ALPHA_BLENDING);
+
+            let shared_instance = wgpu::VertexBufferLayout {
+                array_stride: 0,
+                ..Instance3d::desc()
+            };
+
+            cx.device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some("point pipeline"),
+                layout: Some(&render_pipeline_layout),
+                vertex: wgpu::VertexState {
+                    module: &shader.shader,
+                    entry_point: "vs_main",
+                    buffers: &[
+                        Vertex::desc(),
+                        shared_instance,
+                    ],
+                    compilation_options: Default::default(),
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &shader.shader,
+                    entry_point: shape.entry_point(),
+                    targets: &targets,
+                    compilation_options: Default::default(),
+                }),
+                primitive,
+                depth_stencil: formats.depth_format.map(|format| wgpu::DepthStencilState {
+                    format,
+                    depth_write_enabled: use_depth_stencil,
+                    depth_compare,
+                    stencil: formats.stencil(),
+                    bias: formats.depth_bias(),
+                }),
+                multisample: Default::default(),
+                multiview: None,
+                cache: None,
+            })
+        });
+
+        Self {
+            pipeline,
+            default_material: Res::new(|cx: &mut RenderContext| PointMaterial::new(cx, &PointStyle::default())),
+        }
+    }
+
+    /// Render with the default [`PointStyle`]
+    pub fn render<'a>(
+        &self,
+        cx: &mut RenderContext,
+        pass: &mut Pass<'a>,
+        points: impl Into<VertexBufferSlice<Vertex>>,
+        instance: impl Into<VertexBufferSlice<Instance3d>>,
+    ) {
+        let material = cx.resource(&self.default_material);
+        self.render_with_material(cx, pass, points, instance, &material);
+    }
+
+    pub fn render_with_material<'a>(
+        &self,
+        cx: &mut RenderContext,
+        pass: &mut Pass<'a>,
+        points: impl Into<VertexBufferSlice<Vertex>>,
+        instance: impl Into<VertexBufferSlice<Instance3d>>,
+        material: &PointMaterial,
+    ) {
+        // points do not bound a volume, they have no section faces
+        if pass.kind() == PassKind::Section {
+            return;
+        }
+
+        let points: VertexBufferSlice<Vertex> = points.into();
+        let instance: VertexBufferSlice<Instance3d> = instance.into();
+        let material = material.bind_group.clone();
+
+        let pipeline = self.pipeline.get(cx, pass);
+
+        pass.defer(move |rp, globals| {
+            let instance_offset = instance.range.start as wgpu::BufferAddress * std::mem::size_of::<Instance3d>() as wgpu::BufferAddress;
+
+            rp.set_pipeline(&pipeline);
+            rp.set_bind_group(0, globals, &[]);
+            rp.set_bind_group(1, &material, &[]);
+            rp.set_vertex_buffer(0, points.buffer.slice(..));
+            rp.set_vertex_buffer(1, instance.buffer.slice(instance_offset..));
+            rp.draw(0..6, points.range.clone());
+        });
+    }
+}
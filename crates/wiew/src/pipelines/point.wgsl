@@ -0,0 +1,163 @@
+// ================================
+//             Style
+// ================================
+
+const SIZE_PIXELS: u32 = 0u;
+const SIZE_WORLD: u32 = 1u;
+
+struct PointStyle {
+    size: f32,
+    size_unit: u32,
+    min_size: f32,
+    max_size: f32,
+    // constant, linear and quadratic attenuation by the distance to the camera
+    attenuation: vec3<f32>,
+};
+
+@group(1) @binding(0)
+var<uniform> style: PointStyle;
+
+// ================================
+//            Vertex
+// ================================
+
+// points are stepped per instance, the vertex index selects the corner of the quad
+struct PointInput {
+    @location(7) position: vec3<f32>,
+    @location(8) size: f32,
+    @location(9) color: vec4<f32>,
+};
+
+// the same transform is used for all the points of a draw call
+struct InstanceInput {
+    @location(0) model_0: vec4<f32>,
+    @location(1) model_1: vec4<f32>,
+    @location(2) model_2: vec4<f32>,
+    @location(3) model_3: vec4<f32>,
+    @location(4) model_inv_tr_0: vec3<f32>,
+    @location(5) model_inv_tr_1: vec3<f32>,
+    @location(6) model_inv_tr_2: vec3<f32>,
+    @location(13) color: vec4<f32>,
+    @location(14) object_id: u32,
+    @location(15) visible: u32,
+};
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) color: vec4<f32>,
+    // position in the quad, in [-1, 1]
+    @location(1) uv: vec2<f32>,
+    // center of the point in view space
+    @location(2) @interpolate(flat) view_center: vec3<f32>,
+    // radius of the point in view space units
+    @location(3) @interpolate(flat) view_radius: f32,
+    // center of the point in world space, the whole point is clipped with it
+    @location(4) @interpolate(flat) world_center: vec3<f32>,
+};
+
+@vertex
+fn vs_main(
+    @builtin(vertex_index) vertex_index: u32,
+    point: PointInput,
+    instance: InstanceInput,
+) -> VertexOutput {
+    let model_matrix = mat4x4<f32>(
+        instance.model_0,
+        instance.model_1,
+        instance.model_2,
+        instance.model_3,
+    );
+
+    let world_center = model_matrix * vec4<f32>(point.position, 1.0);
+    let view_center = camera.view * world_center;
+    let center = camera.proj * view_center;
+
+    // number of pixels per view space unit at the depth of the point
+    let pixels_per_unit = 0.5 * camera.proj[1][1] * camera.viewport_size.y / center.w;
+
+    var diameter = style.size * point.size;
+    if (style.size_unit == SIZE_WORLD) {
+        diameter *= pixels_per_unit;
+    }
+
+    let d = length(view_center.xyz);
+    diameter /= max(style.attenuation.x + style.attenuation.y * d + style.attenuation.z * d * d, 1e-6);
+    diameter = clamp(diameter, style.min_size, style.max_size);
+
+    var corners = array<vec2<f32>, 6>(
+        vec2<f32>(-1.0, -1.0),
+        vec2<f32>(1.0, -1.0),
+        vec2<f32>(1.0, 1.0),
+        vec2<f32>(-1.0, -1.0),
+        vec2<f32>(1.0, 1.0),
+        vec2<f32>(-1.0, 1.0),
+    );
+    let corner = corners[vertex_index % 6u];
+    let offset = corner * diameter / camera.viewport_size;
+
+    var out: VertexOutput;
+    out.clip_position = center + vec4<f32>(offset * center.w, 0.0, 0.0);
+    out.color = point.color * instance.color;
+    out.uv = corner;
+    out.view_center = view_center.xyz;
+    out.view_radius = 0.5 * diameter / pixels_per_unit;
+    out.world_center = world_center.xyz;
+
+    // hidden instances are moved outside of the clip volume
+    if (instance.visible == 0u) {
+        out.clip_position = vec4<f32>(2.0, 2.0, 2.0, 1.0);
+    }
+
+    return out;
+}
+
+// ================================
+//            Fragment
+// ================================
+
+@fragment
+fn fs_square(in: VertexOutput) -> @location(0) vec4<f32> {
+    clip_fragment(in.world_center);
+    return apply_fog(in.color, in.world_center, in.clip_position.xy);
+}
+
+@fragment
+fn fs_disc(in: VertexOutput) -> @location(0) vec4<f32> {
+    clip_fragment(in.world_center);
+
+    let r = length(in.uv);
+    // antialias the edge over about one pixel
+    let coverage = clamp((1.0 - r) / max(fwidth(r), 1e-6), 0.0, 1.0);
+    if (coverage <= 0.0) {
+        discard;
+    }
+    return apply_fog(vec4<f32>(in.color.rgb, in.color.a * coverage), in.world_center, in.clip_position.xy);
+}
+
+struct SphereOutput {
+    @location(0) color: vec4<f32>,
+    @builtin(frag_depth) depth: f32,
+};
+
+// A sphere impostor: the disc is shaded as a sphere and writes the depth of its surface
+@fragment
+fn fs_sphere(in: VertexOutput) -> SphereOutput {
+    clip_fragment(in.world_center);
+
+    let r2 = dot(in.uv, in.uv);
+    if (r2 > 1.0) {
+        discard;
+    }
+
+    let normal = vec3<f32>(in.uv, sqrt(1.0 - r2));
+    let view_position = in.view_center + normal * in.view_radius;
+    let clip = camera.proj * vec4<f32>(view_position, 1.0);
+
+    let light = normalize((camera.view * vec4<f32>(camera.light_dir, 0.0)).xyz);
+    let diffuse = max(dot(normal, light), 0.0);
+
+    var out: SphereOutput;
+    out.color = apply_fog(vec4<f32>(in.color.rgb * (0.3 + 0.7 * diffuse), in.color.a), in.world_center, in.clip_position.xy);
+    out.depth = clip.z / clip.w;
+    return out;
+}
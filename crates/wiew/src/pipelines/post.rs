@@ -0,0 +1,347 @@
+use std::{fmt, sync::Arc};
+
+use wgpu::{PrimitiveState, PrimitiveTopology};
+
+use crate::{Pass, RenderContext, SingletonResource};
+
+use super::Pipeline;
+
+/// WGSL declarations shared by the post-processing effects, prepended to their source
+/// (see [`PostEffect::custom`])
+pub const POST_WGSL: &str = include_str!("post.wgsl");
+
+/// Largest number of effects in a post-processing chain, the next ones are skipped
+pub const MAX_POST_EFFECTS: usize = 16;
+
+/// The format of the textures of a post-processing chain, so that the effects read
+/// colors that are neither clamped nor quantized, e.g. for the exposure and the tone
+/// mapping. Only the last effect writes the format of the surface.
+pub const POST_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+
+/// The tone mapping operator of [`PostEffect::tone_mapping`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ToneMapping {
+    #[default]
+    Reinhard,
+    /// The filmic curve of the Academy Color Encoding System
+    Aces,
+}
+
+/// A full screen effect applied to the output of the previous one, or to the scene.
+///
+/// The effect is cheap to clone, clones share their pipeline.
+#[derive(Clone)]
+pub struct PostEffect {
+    pub name: String,
+    /// `params` in the WGSL of the effect
+    pub params: [f32; 4],
+    pipeline: Arc<Pipeline>,
+}
+
+impl fmt::Debug for PostEffect {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PostEffect")
+            .field("name", &self.name)
+            .field("params", &self.params)
+            .finish_non_exhaustive()
+    }
+}
+
+impl PostEffect {
+    /// An effect whose WGSL `source` defines `fn effect(uv: vec2<f32>) -> vec4<f32>`,
+    /// with the declarations of [`POST_WGSL`] (`input`, `texel_size`, `params`, ...)
+    pub fn custom(
+        name: impl Into<String>,
+        source: impl Into<String>,
+        params: [f32; 4],
+    ) -> Self {
+        let name = name.into();
+        let source = format!("{POST_WGSL}\n{}", source.into());
+
+        let primitive = PrimitiveState {
+            topology: PrimitiveTopology::TriangleList,
+            ..Default::default()
+        };
+
+        let label = name.clone();
+        let pipeline = Pipeline::from_builder(move |cx, formats| {
+            let shader = cx.device.create_shader_module(wgpu::ShaderModuleDescriptor {
+                label: Some(&label),
+                source: wgpu::ShaderSource::Wgsl(source.as_str().into()),
+            });
+
+            let common = cx.singleton::<PostCommon>();
+
+            let render_pipeline_layout =
+            cx.device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: None,
+                bind_group_layouts: &[
+                    common.layout(),
+                ],
+                push_constant_ranges: &[],
+            });
+
+            let targets = formats.color_targets(wgpu::BlendState::REPLACE);
+
+            cx.device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some(&label),
+                layout: Some(&render_pipeline_layout),
+                vertex: wgpu::VertexState {
+                    module: &shader,
+                    entry_point: "vs_main",
+                    buffers: &[],
+                    compilation_options: Default::default(),
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &shader,
+                    entry_point: "fs_main",
+                    targets: &targets,
+                    compilation_options: Default::default(),
+                }),
+                primitive,
+                depth_stencil: None,
+                multisample: Default::default(),
+                multiview: None,
+                cache: None,
+            })
+        });
+
+        Self {
+            name,
+            params,
+            pipeline: Arc::new(pipeline),
+        }
+    }
+
+    /// Fast approximate anti-aliasing
+    pub fn fxaa() -> Self {
+        Self::custom("fxaa", include_str!("post_fxaa.wgsl"), [0.75, 0.166, 0.0833, 0.0])
+    }
+
+    /// Map the colors, multiplied by `exposure`, to the displayable range
+    pub fn tone_mapping(operator: ToneMapping, exposure: f32) -> Self {
+        let operator = match operator {
+            ToneMapping::Reinhard => 0.0,
+            ToneMapping::Aces => 1.0,
+        };
+
+        Self::custom("tone mapping", include_str!("post_tone_mapping.wgsl"), [operator, exposure, 0.0, 0.0])
+    }
+
+    /// Exposure (in stops) and gamma correction, with a neutral contrast and saturation
+    pub fn exposure_gamma(exposure: f32, gamma: f32) -> Self {
+        Self::color_grading(exposure, gamma, 1.0, 1.0)
+    }
+
+    /// Exposure (in stops), gamma correction, contrast and saturation, `1` being neutral for the last three
+    pub fn color_grading(exposure: f32, gamma: f32, contrast: f32, saturation: f32) -> Self {
+        Self::custom("color grading", include_str!("post_color_grading.wgsl"), [exposure, gamma, contrast, saturation])
+    }
+
+    /// Darken the corners, from `radius` (`1` is the middle of the edges) over `smoothness`
+    pub fn vignette(intensity: f32, radius: f32, smoothness: f32) -> Self {
+        Self::custom("vignette", include_str!("post_vignette.wgsl"), [intensity, radius, smoothness, 0.0])
+    }
+
+    /// Apply the effect at the position `index` of the chain, which reads `targets.texture(index)`,
+    /// onto the surface of `pass`
+    pub fn render<'a>(
+        &self,
+        cx: &mut RenderContext,
+        pass: &mut Pass<'a>,
+        targets: &PostTargets,
+        index: usize,
+    ) {
+        let bind_group = targets.bind_groups[index % 2].clone();
+        let offset = targets.params_offset(index);
+
+        let pipeline = self.pipeline.get(cx, pass);
+
+        pass.defer(move |rp, _| {
+            rp.set_pipeline(&pipeline);
+            rp.set_bind_group(0, &bind_group, &[offset]);
+            rp.draw(0..3, 0..1);
+        });
+    }
+}
+
+/// The bind group layout and the sampler of the post-processing effects
+pub struct PostCommon {
+    bind_group_layout: wgpu::BindGroupLayout,
+    sampler: wgpu::Sampler,
+}
+
+impl SingletonResource for PostCommon {
+    fn init(ctx: &mut RenderContext) -> Self {
+        let bind_group_layout = ctx.device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        // the params of each effect of the chain are at their own offset
+                        has_dynamic_offset: true,
+                        min_binding_size: wgpu::BufferSize::new(PARAMS_SIZE),
+                    },
+                    count: None,
+                },
+            ],
+            label: Some("post_bind_group_layout"),
+        });
+
+        let sampler = ctx.device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("post sampler"),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Nearest,
+            ..Default::default()
+        });
+
+        Self {
+            bind_group_layout,
+            sampler,
+        }
+    }
+}
+
+impl PostCommon {
+    pub fn layout(&self) -> &wgpu::BindGroupLayout {
+        &self.bind_group_layout
+    }
+}
+
+const PARAMS_SIZE: u64 = std::mem::size_of::<[f32; 4]>() as u64;
+
+/// The two ping-pong textures of a post-processing chain, and the params of its effects.
+///
+/// The scene is rendered into `texture(0)`, each effect reads one texture and writes
+/// the other, except the last one that writes the final target. The textures have
+/// the [`POST_FORMAT`].
+pub struct PostTargets {
+    views: [wgpu::TextureView; 2],
+    params: wgpu::Buffer,
+    params_stride: u64,
+    bind_groups: [Arc<wgpu::BindGroup>; 2],
+}
+
+impl PostTargets {
+    pub fn new(
+        cx: &mut RenderContext,
+        width: u32,
+        height: u32,
+    ) -> Self {
+        let common = cx.singleton::<PostCommon>();
+
+        let create_view = |label| {
+            cx.device.create_texture(&wgpu::TextureDescriptor {
+                label: Some(label),
+                size: wgpu::Extent3d {
+                    width,
+                    height,
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format: POST_FORMAT,
+                usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+                view_formats: &[],
+            }).create_view(&wgpu::TextureViewDescriptor::default())
+        };
+
+        let views = [create_view("post texture 0"), create_view("post texture 1")];
+
+        let alignment = cx.device.limits().min_uniform_buffer_offset_alignment as u64;
+        let params_stride = PARAMS_SIZE.div_ceil(alignment) * alignment;
+
+        let params = cx.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("post params buffer"),
+            size: params_stride * MAX_POST_EFFECTS as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let create_bind_group = |view| cx.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: common.layout(),
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&common.sampler),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+                        buffer: &params,
+                        offset: 0,
+                        size: wgpu::BufferSize::new(PARAMS_SIZE),
+                    }),
+                },
+            ],
+            label: Some("post_bind_group"),
+        });
+
+        let bind_groups = [
+            Arc::new(create_bind_group(&views[0])),
+            Arc::new(create_bind_group(&views[1])),
+        ];
+
+        Self {
+            views,
+            params,
+            params_stride,
+            bind_groups,
+        }
+    }
+
+    /// One of the two ping-pong textures, `texture(0)` is the input of the first effect
+    pub fn texture(&self, index: usize) -> &wgpu::TextureView {
+        &self.views[index % 2]
+    }
+
+    fn params_offset(&self, index: usize) -> u32 {
+        (self.params_stride * index as u64) as u32
+    }
+
+    /// Write the params of the (at most [`MAX_POST_EFFECTS`]) effects of the chain
+    pub fn update(
+        &self,
+        queue: &wgpu::Queue,
+        effects: &[PostEffect],
+    ) {
+        if effects.len() > MAX_POST_EFFECTS {
+            log::warn!("Too many post-processing effects ({}), only the first {MAX_POST_EFFECTS} are applied", effects.len());
+        }
+
+        let mut data = vec![0u8; (self.params_stride * MAX_POST_EFFECTS as u64) as usize];
+        for (index, effect) in effects.iter().take(MAX_POST_EFFECTS).enumerate() {
+            let offset = self.params_offset(index) as usize;
+            data[offset..offset + PARAMS_SIZE as usize].copy_from_slice(bytemuck::cast_slice(&effect.params));
+        }
+
+        queue.write_buffer(&self.params, 0, &data);
+    }
+}
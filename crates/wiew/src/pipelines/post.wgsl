@@ -0,0 +1,46 @@
+// ================================
+//            Post-processing
+// ================================
+//
+// Shared by all the post-processing effects, it is prepended to their source
+// (see `PostEffect::custom`). An effect defines:
+// ```wgsl
+// fn effect(uv: vec2<f32>) -> vec4<f32>
+// ```
+// that returns the color of the output at `uv`, reading the output of the
+// previous effect with `input`.
+
+// output of the previous effect, or of the scene
+@group(0) @binding(0)
+var input_texture: texture_2d<f32>;
+@group(0) @binding(1)
+var input_sampler: sampler;
+// the `PostEffect::params`
+@group(0) @binding(2)
+var<uniform> params: vec4<f32>;
+
+// color of the input at `uv`, with bilinear filtering
+fn input(uv: vec2<f32>) -> vec4<f32> {
+    return textureSampleLevel(input_texture, input_sampler, uv, 0.0);
+}
+
+// size of a texel of the input, in uv units
+fn texel_size() -> vec2<f32> {
+    return 1.0 / vec2<f32>(textureDimensions(input_texture));
+}
+
+fn luminance(color: vec3<f32>) -> f32 {
+    return dot(color, vec3<f32>(0.2126, 0.7152, 0.0722));
+}
+
+// a triangle covering the whole screen
+@vertex
+fn vs_main(@builtin(vertex_index) vertex_index: u32) -> @builtin(position) vec4<f32> {
+    let uv = vec2<f32>(f32((vertex_index << 1u) & 2u), f32(vertex_index & 2u));
+    return vec4<f32>(uv * 2.0 - 1.0, 0.0, 1.0);
+}
+
+@fragment
+fn fs_main(@builtin(position) position: vec4<f32>) -> @location(0) vec4<f32> {
+    return effect(position.xy * texel_size());
+}
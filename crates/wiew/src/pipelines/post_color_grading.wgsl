@@ -0,0 +1,14 @@
+// Exposure, contrast, saturation and gamma correction.
+//
+// params: (exposure in stops, gamma, contrast, saturation)
+
+fn effect(uv: vec2<f32>) -> vec4<f32> {
+    let color = input(uv);
+
+    var rgb = color.rgb * exp2(params.x);
+    rgb = (rgb - 0.5) * params.z + 0.5;
+    rgb = mix(vec3<f32>(luminance(rgb)), rgb, params.w);
+    rgb = pow(max(rgb, vec3<f32>(0.0)), vec3<f32>(1.0 / params.y));
+
+    return vec4<f32>(rgb, color.a);
+}
@@ -0,0 +1,113 @@
+// FXAA 3.11 (quality preset 10), without the subpixel passes.
+//
+// params: (subpixel quality, edge threshold, minimum edge threshold, unused)
+
+const FXAA_STEPS: i32 = 8;
+
+fn luma_at(uv: vec2<f32>) -> f32 {
+    return luminance(input(uv).rgb);
+}
+
+fn effect(uv: vec2<f32>) -> vec4<f32> {
+    let texel = texel_size();
+    let center = input(uv);
+
+    let luma_m = luminance(center.rgb);
+    let luma_n = luma_at(uv + vec2<f32>(0.0, -texel.y));
+    let luma_s = luma_at(uv + vec2<f32>(0.0, texel.y));
+    let luma_e = luma_at(uv + vec2<f32>(texel.x, 0.0));
+    let luma_w = luma_at(uv + vec2<f32>(-texel.x, 0.0));
+
+    let luma_min = min(luma_m, min(min(luma_n, luma_s), min(luma_e, luma_w)));
+    let luma_max = max(luma_m, max(max(luma_n, luma_s), max(luma_e, luma_w)));
+    let range = luma_max - luma_min;
+
+    // not on an edge
+    if (range < max(params.z, luma_max * params.y)) {
+        return center;
+    }
+
+    let luma_nw = luma_at(uv + vec2<f32>(-texel.x, -texel.y));
+    let luma_ne = luma_at(uv + vec2<f32>(texel.x, -texel.y));
+    let luma_sw = luma_at(uv + vec2<f32>(-texel.x, texel.y));
+    let luma_se = luma_at(uv + vec2<f32>(texel.x, texel.y));
+
+    // sub-pixel blending, from the contrast between the pixel and its neighbors
+    let average = (2.0 * (luma_n + luma_s + luma_e + luma_w) + luma_nw + luma_ne + luma_sw + luma_se) / 12.0;
+    let subpixel = smoothstep(0.0, 1.0, clamp(abs(average - luma_m) / range, 0.0, 1.0));
+    let subpixel_blend = subpixel * subpixel * params.x;
+
+    // orientation of the edge
+    let horizontal = abs(luma_nw + luma_ne - 2.0 * luma_n) + 2.0 * abs(luma_w + luma_e - 2.0 * luma_m) + abs(luma_sw + luma_se - 2.0 * luma_s);
+    let vertical = abs(luma_nw + luma_sw - 2.0 * luma_w) + 2.0 * abs(luma_n + luma_s - 2.0 * luma_m) + abs(luma_ne + luma_se - 2.0 * luma_e);
+    let is_horizontal = horizontal >= vertical;
+
+    // the side of the edge with the largest gradient
+    let luma_positive = select(luma_e, luma_s, is_horizontal);
+    let luma_negative = select(luma_w, luma_n, is_horizontal);
+    let gradient_positive = abs(luma_positive - luma_m);
+    let gradient_negative = abs(luma_negative - luma_m);
+    let positive = gradient_positive >= gradient_negative;
+
+    var step_length = select(texel.x, texel.y, is_horizontal);
+    var luma_side = luma_negative;
+    if (positive) {
+        luma_side = luma_positive;
+    } else {
+        step_length = -step_length;
+    }
+    let gradient = 0.25 * max(gradient_positive, gradient_negative);
+    let luma_edge = 0.5 * (luma_m + luma_side);
+
+    // walk along the edge in both directions until its end
+    var edge_uv = uv;
+    var edge_step = vec2<f32>(texel.x, 0.0);
+    if (is_horizontal) {
+        edge_uv.y += 0.5 * step_length;
+    } else {
+        edge_uv.x += 0.5 * step_length;
+        edge_step = vec2<f32>(0.0, texel.y);
+    }
+
+    var uv_n = edge_uv - edge_step;
+    var uv_p = edge_uv + edge_step;
+    var delta_n = luma_at(uv_n) - luma_edge;
+    var delta_p = luma_at(uv_p) - luma_edge;
+    var done_n = abs(delta_n) >= gradient;
+    var done_p = abs(delta_p) >= gradient;
+
+    for (var i = 0; i < FXAA_STEPS && !(done_n && done_p); i++) {
+        let scale = select(1.0, 1.5 + f32(i), i > 1);
+        if (!done_n) {
+            uv_n -= edge_step * scale;
+            delta_n = luma_at(uv_n) - luma_edge;
+            done_n = abs(delta_n) >= gradient;
+        }
+        if (!done_p) {
+            uv_p += edge_step * scale;
+            delta_p = luma_at(uv_p) - luma_edge;
+            done_p = abs(delta_p) >= gradient;
+        }
+    }
+
+    let distance_n = select(uv.y - uv_n.y, uv.x - uv_n.x, is_horizontal);
+    let distance_p = select(uv_p.y - uv.y, uv_p.x - uv.x, is_horizontal);
+    let closest_n = distance_n < distance_p;
+    let distance = min(distance_n, distance_p);
+    let edge_length = distance_n + distance_p;
+
+    // only blend when the end of the edge is on the other side of the center
+    let delta = select(delta_p, delta_n, closest_n);
+    let correct = (delta < 0.0) != (luma_m - luma_edge < 0.0);
+    let edge_blend = select(0.0, 0.5 - distance / edge_length, correct);
+
+    let blend = max(edge_blend, subpixel_blend);
+    var final_uv = uv;
+    if (is_horizontal) {
+        final_uv.y += blend * step_length;
+    } else {
+        final_uv.x += blend * step_length;
+    }
+
+    return input(final_uv);
+}
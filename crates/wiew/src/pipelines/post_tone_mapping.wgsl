@@ -0,0 +1,29 @@
+// Maps the colors to the displayable range.
+//
+// params: (operator: 0 Reinhard, 1 ACES, exposure, unused, unused)
+
+fn reinhard(color: vec3<f32>) -> vec3<f32> {
+    return color / (1.0 + luminance(color));
+}
+
+// fit of the ACES filmic curve by Krzysztof Narkowicz
+fn aces(color: vec3<f32>) -> vec3<f32> {
+    let a = 2.51;
+    let b = 0.03;
+    let c = 2.43;
+    let d = 0.59;
+    let e = 0.14;
+    return clamp((color * (a * color + b)) / (color * (c * color + d) + e), vec3<f32>(0.0), vec3<f32>(1.0));
+}
+
+fn effect(uv: vec2<f32>) -> vec4<f32> {
+    let color = input(uv);
+    let exposed = color.rgb * params.y;
+
+    var mapped = reinhard(exposed);
+    if (u32(params.x) == 1u) {
+        mapped = aces(exposed);
+    }
+
+    return vec4<f32>(mapped, color.a);
+}
@@ -0,0 +1,13 @@
+// Darkens the corners of the screen.
+//
+// params: (intensity, radius, smoothness, unused)
+
+fn effect(uv: vec2<f32>) -> vec4<f32> {
+    let color = input(uv);
+
+    // distance from the center, 1 at the middle of the edges
+    let d = length((uv - 0.5) * 2.0);
+    let vignette = 1.0 - params.x * smoothstep(params.y, params.y + params.z, d);
+
+    return vec4<f32>(color.rgb * vignette, color.a);
+}
@@ -0,0 +1,291 @@
+//! Section faces of the [`ClipPlane`]s, filled with the stencil buffer.
+//!
+//! For each plane with a cap color, the closed surfaces of the scene are drawn
+//! again into a [`PassKind::Section`] pass: clipped by this plane only, without
+//! depth test and without color writes, each of their fragments inverts the stencil
+//! buffer (see [`SurfaceFormats::stencil`](super::SurfaceFormats::stencil)). A view
+//! ray then crosses an odd number of surfaces, and the stencil buffer is non-zero,
+//! where the plane cuts through the inside of a closed surface. The
+//! [`SectionCapPipeline`] fills these pixels with the plane, at its depth.
+//!
+//! # Remarks
+//! The surfaces must be closed, and must not intersect each other or the near plane
+//! of the camera, for the count to be right.
+
+use std::sync::Arc;
+
+use wgpu::{util::DeviceExt, Device, PrimitiveState, PrimitiveTopology, ShaderModule};
+
+use crate::{CameraUniform, ClipPlane, Pass, PassKind, ProjectionCameraBuffer, ProjectionCameraCommon, RenderContext, SingletonResource, MAX_CLIP_PLANES};
+
+use super::{shader_with_globals, Pipeline};
+
+/// A shader for the section caps
+pub struct SectionShader {
+    shader: ShaderModule,
+}
+
+impl SectionShader {
+    /// Create a new section shader
+    pub fn new(
+        device: &Device,
+    ) -> Self {
+        Self {
+            shader: shader_with_globals(device, "section.wgsl", include_str!("section.wgsl")),
+        }
+    }
+}
+
+impl SingletonResource for SectionShader {
+    fn init(ctx: &mut RenderContext) -> Self {
+        Self::new(ctx.device)
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct SectionUniform {
+    plane: [f32; 4],
+    color: [f32; 4],
+    planes: [[f32; 4]; MAX_CLIP_PLANES],
+    plane_count: u32,
+    _padding: [u32; 3],
+}
+
+impl SectionUniform {
+    fn new(planes: &[ClipPlane], index: usize) -> Self {
+        let plane = &planes[index];
+
+        let mut uniform = Self {
+            plane: plane.equation(),
+            color: plane.cap.unwrap_or([1.0; 4]),
+            planes: [[0.0; 4]; MAX_CLIP_PLANES],
+            plane_count: 0,
+            _padding: [0; 3],
+        };
+
+        let others = planes.iter()
+            .take(MAX_CLIP_PLANES)
+            .enumerate()
+            .filter(|(i, _)| *i != index);
+        for (_, other) in others {
+            uniform.planes[uniform.plane_count as usize] = other.equation();
+            uniform.plane_count += 1;
+        }
+
+        uniform
+    }
+}
+
+/// The bind group layout of [`SectionCap`]s
+pub struct SectionCapCommon {
+    bind_group_layout: wgpu::BindGroupLayout,
+}
+
+impl SingletonResource for SectionCapCommon {
+    fn init(ctx: &mut RenderContext) -> Self {
+        let bind_group_layout = ctx.device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                }
+            ],
+            label: Some("section_cap_bind_group_layout"),
+        });
+
+        Self {
+            bind_group_layout,
+        }
+    }
+}
+
+impl SectionCapCommon {
+    pub fn layout(&self) -> &wgpu::BindGroupLayout {
+        &self.bind_group_layout
+    }
+}
+
+/// The cap of one plane: the globals of its [`PassKind::Section`] pass, which are
+/// clipped by this plane only, and the plane and its color bound as `@group(1)`
+/// by [`SectionCapPipeline`]
+pub struct SectionCap {
+    camera: ProjectionCameraBuffer,
+    buffer: wgpu::Buffer,
+    bind_group: Arc<wgpu::BindGroup>,
+}
+
+impl SectionCap {
+    pub fn new(
+        cx: &mut RenderContext,
+    ) -> Self {
+        let common = cx.singleton::<SectionCapCommon>();
+        let camera = ProjectionCameraBuffer::new(cx);
+
+        let buffer = cx.device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("section cap buffer"),
+            contents: bytemuck::cast_slice(&[SectionUniform::new(&[ClipPlane::new(Default::default(), Default::default())], 0)]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let bind_group = cx.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: common.layout(),
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: buffer.as_entire_binding(),
+                },
+            ],
+            label: Some("section_cap_bind_group"),
+        });
+
+        Self {
+            camera,
+            buffer,
+            bind_group: Arc::new(bind_group),
+        }
+    }
+
+    /// Prepare the cap of `planes[index]`, with the globals of the view (its camera,
+    /// light and fog) in `uniform`
+    pub fn update(
+        &mut self,
+        queue: &wgpu::Queue,
+        uniform: &CameraUniform,
+        planes: &[ClipPlane],
+        index: usize,
+    ) {
+        // the shadow map of the view is not bound
+        self.camera.uniform = *uniform;
+        self.camera.uniform.disable_shadow();
+        self.camera.uniform.set_clip_planes(&planes[index..=index]);
+        queue.write_buffer(&self.camera.buffer, 0, bytemuck::cast_slice(&[self.camera.uniform]));
+
+        queue.write_buffer(&self.buffer, 0, bytemuck::cast_slice(&[SectionUniform::new(planes, index)]));
+    }
+
+    /// The globals of the section pass
+    pub fn globals(&self) -> &wgpu::BindGroup {
+        &self.camera.bind_group
+    }
+}
+
+/// Fills the section face of a [`SectionCap`] where the stencil buffer is non-zero,
+/// after the surfaces were drawn into the section pass
+pub struct SectionCapPipeline {
+    pipeline: Pipeline,
+}
+
+impl Default for SectionCapPipeline {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SectionCapPipeline {
+    pub fn new() -> Self {
+        let primitive = PrimitiveState {
+            topology: PrimitiveTopology::TriangleList,
+            ..Default::default()
+        };
+
+        let pipeline = Pipeline::from_builder(move |cx, formats| {
+            let shader = cx.singleton::<SectionShader>();
+
+            let camera_common = cx.singleton::<ProjectionCameraCommon>();
+            let cap_common = cx.singleton::<SectionCapCommon>();
+
+            let render_pipeline_layout =
+            cx.device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: None,
+                bind_group_layouts: &[
+                    camera_common.layout(),
+                    cap_common.layout(),
+                ],
+                push_constant_ranges: &[],
+            });
+
+            // the surfaces of the section pass do not write the targets, the cap does
+            let targets = formats.target_formats.iter().map(|format| Some(wgpu::ColorTargetState {
+                format: *format,
+                blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                write_mask: wgpu::ColorWrites::ALL,
+            })).collect::<Vec<_>>();
+
+            let stencil_face = wgpu::StencilFaceState {
+                compare: wgpu::CompareFunction::NotEqual,
+                fail_op: wgpu::StencilOperation::Keep,
+                depth_fail_op: wgpu::StencilOperation::Keep,
+                pass_op: wgpu::StencilOperation::Keep,
+            };
+
+            cx.device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some("section cap pipeline"),
+                layout: Some(&render_pipeline_layout),
+                vertex: wgpu::VertexState {
+                    module: &shader.shader,
+                    entry_point: "vs_main",
+                    buffers: &[],
+                    compilation_options: Default::default(),
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &shader.shader,
+                    entry_point: "fs_main",
+                    targets: &targets,
+                    compilation_options: Default::default(),
+                }),
+                primitive,
+                // tested against the stencil reference `0`
+                depth_stencil: formats.depth_format.map(|format| wgpu::DepthStencilState {
+                    format,
+                    depth_write_enabled: true,
+                    depth_compare: wgpu::CompareFunction::LessEqual,
+                    stencil: wgpu::StencilState {
+                        front: stencil_face,
+                        back: stencil_face,
+                        read_mask: 0xff,
+                        write_mask: 0,
+                    },
+                    bias: wgpu::DepthBiasState::default(),
+                }),
+                multisample: Default::default(),
+                multiview: None,
+                cache: None,
+            })
+        });
+
+        Self {
+            pipeline,
+        }
+    }
+
+    /// Draw the cap, only in the section pass and after the surfaces
+    pub fn render<'a>(
+        &self,
+        cx: &mut RenderContext,
+        pass: &mut Pass<'a>,
+        cap: &SectionCap,
+    ) {
+        if pass.kind() != PassKind::Section {
+            return;
+        }
+
+        let bind_group = cap.bind_group.clone();
+
+        let pipeline = self.pipeline.get(cx, pass);
+
+        pass.defer(move |rp, globals| {
+            rp.set_pipeline(&pipeline);
+            rp.set_bind_group(0, globals, &[]);
+            rp.set_bind_group(1, &bind_group, &[]);
+            rp.set_stencil_reference(0);
+            rp.draw(0..3, 0..1);
+        });
+    }
+}
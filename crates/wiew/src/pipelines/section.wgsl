@@ -0,0 +1,88 @@
+// ================================
+//            Inputs
+// ================================
+
+struct Section {
+    // the plane of the cap, the globals of the pass are clipped by it only
+    plane: vec4<f32>,
+    color: vec4<f32>,
+    // the other clipping planes, which cut the cap
+    planes: array<vec4<f32>, MAX_CLIP_PLANES>,
+    plane_count: u32,
+};
+
+@group(1) @binding(0)
+var<uniform> section: Section;
+
+// ================================
+//            Vertex
+// ================================
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) ndc: vec2<f32>,
+};
+
+// a triangle covering the whole screen
+@vertex
+fn vs_main(@builtin(vertex_index) vertex_index: u32) -> VertexOutput {
+    let uv = vec2<f32>(f32((vertex_index << 1u) & 2u), f32(vertex_index & 2u));
+
+    var out: VertexOutput;
+    out.ndc = uv * 2.0 - 1.0;
+    out.clip_position = vec4<f32>(out.ndc, 0.0, 1.0);
+    return out;
+}
+
+// ================================
+//            Fragment
+// ================================
+
+struct FragmentOutput {
+    @location(0) color: vec4<f32>,
+    @builtin(frag_depth) depth: f32,
+};
+
+fn unproject(ndc: vec3<f32>) -> vec3<f32> {
+    let view = camera.inv_proj * vec4<f32>(ndc, 1.0);
+    return (camera.inv_view * vec4<f32>(view.xyz / view.w, 1.0)).xyz;
+}
+
+// The view ray of the fragment is intersected with the plane, the stencil test
+// keeps the fragments where the intersection is inside of a closed surface
+@fragment
+fn fs_main(in: VertexOutput) -> FragmentOutput {
+    let near = unproject(vec3<f32>(in.ndc, 0.0));
+    let far = unproject(vec3<f32>(in.ndc, 1.0));
+    let ray = far - near;
+
+    let normal = section.plane.xyz;
+    let denominator = dot(normal, ray);
+    if (abs(denominator) < 1e-9) {
+        discard;
+    }
+
+    let t = -(dot(normal, near) + section.plane.w) / denominator;
+    if (t < 0.0 || t > 1.0) {
+        discard;
+    }
+
+    let p = near + ray * t;
+    for (var i = 0u; i < min(section.plane_count, MAX_CLIP_PLANES); i++) {
+        let plane = section.planes[i];
+        if (dot(plane.xyz, p) + plane.w < 0.0) {
+            discard;
+        }
+    }
+
+    // lit on the side facing the camera
+    let facing = normal * -sign(denominator);
+    let diffuse = max(dot(facing, normalize(camera.light_dir)), 0.0);
+
+    let clip = camera.proj * camera.view * vec4<f32>(p, 1.0);
+
+    var out: FragmentOutput;
+    out.color = apply_fog(vec4<f32>(section.color.rgb * (0.3 + 0.7 * diffuse), section.color.a), p, in.clip_position.xy);
+    out.depth = clip.z / clip.w;
+    return out;
+}
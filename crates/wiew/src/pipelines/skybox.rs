@@ -0,0 +1,532 @@
+use std::{f32::consts::PI, path::Path, sync::Arc};
+
+use wgpu::{util::DeviceExt, Device, PrimitiveState, PrimitiveTopology, ShaderModule};
+
+use crate::{Pass, PassKind, ProjectionCameraCommon, RenderContext, SingletonResource};
+
+use super::{shader_with_globals, Pipeline};
+
+/// A shader for the sky and environment backgrounds
+pub struct SkyboxShader {
+    shader: ShaderModule,
+}
+
+impl SkyboxShader {
+    /// Create a new skybox shader
+    pub fn new(
+        device: &Device,
+    ) -> Self {
+        Self {
+            shader: shader_with_globals(device, "skybox.wgsl", include_str!("skybox.wgsl")),
+        }
+    }
+}
+
+impl SingletonResource for SkyboxShader {
+    fn init(ctx: &mut RenderContext) -> Self {
+        Self::new(ctx.device)
+    }
+}
+
+/// A procedural sky: a gradient from the horizon to the zenith above it, and to
+/// the ground below it. The up direction is `+Y`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SkyGradient {
+    pub zenith: [f32; 3],
+    pub horizon: [f32; 3],
+    pub ground: [f32; 3],
+    /// Sharpness of the gradients, `1` is linear in the height of the direction
+    /// and smaller values keep the horizon color closer to it
+    pub exponent: f32,
+}
+
+impl Default for SkyGradient {
+    fn default() -> Self {
+        Self {
+            zenith: [0.08, 0.22, 0.52],
+            horizon: [0.62, 0.72, 0.82],
+            ground: [0.16, 0.15, 0.14],
+            exponent: 0.5,
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct SkyboxUniform {
+    zenith: [f32; 4],
+    horizon: [f32; 4],
+    ground: [f32; 4],
+    exponent: f32,
+    mode: u32,
+    intensity: f32,
+    _padding: f32,
+}
+
+impl SkyboxUniform {
+    fn new(sky: &SkyGradient, environment: bool, intensity: f32) -> Self {
+        let [r, g, b] = sky.zenith;
+        let zenith = [r, g, b, 1.0];
+        let [r, g, b] = sky.horizon;
+        let horizon = [r, g, b, 1.0];
+        let [r, g, b] = sky.ground;
+        let ground = [r, g, b, 1.0];
+
+        Self {
+            zenith,
+            horizon,
+            ground,
+            exponent: sky.exponent.max(1e-3),
+            mode: environment as u32,
+            intensity,
+            _padding: 0.0,
+        }
+    }
+}
+
+/// The bind group layout of [`SkyboxMaterial`]s
+pub struct SkyboxMaterialCommon {
+    bind_group_layout: wgpu::BindGroupLayout,
+}
+
+impl SingletonResource for SkyboxMaterialCommon {
+    fn init(ctx: &mut RenderContext) -> Self {
+        let bind_group_layout = ctx.device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                }
+            ],
+            label: Some("skybox_material_bind_group_layout"),
+        });
+
+        Self {
+            bind_group_layout,
+        }
+    }
+}
+
+impl SkyboxMaterialCommon {
+    pub fn layout(&self) -> &wgpu::BindGroupLayout {
+        &self.bind_group_layout
+    }
+}
+
+/// The sky colors and whether an [`EnvironmentMap`] is drawn instead, bound as `@group(1)`
+/// by [`SkyboxPipeline`]
+pub struct SkyboxMaterial {
+    buffer: wgpu::Buffer,
+    bind_group: Arc<wgpu::BindGroup>,
+}
+
+impl SkyboxMaterial {
+    pub fn new(
+        cx: &mut RenderContext,
+        sky: &SkyGradient,
+    ) -> Self {
+        let common = cx.singleton::<SkyboxMaterialCommon>();
+
+        let buffer = cx.device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("skybox material buffer"),
+            contents: bytemuck::cast_slice(&[SkyboxUniform::new(sky, false, 1.0)]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let bind_group = cx.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: common.layout(),
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: buffer.as_entire_binding(),
+                }
+            ],
+            label: Some("skybox_material_bind_group"),
+        });
+
+        Self {
+            buffer,
+            bind_group: Arc::new(bind_group),
+        }
+    }
+
+    /// Draw the `sky`, or the environment map with its colors multiplied by `intensity`
+    pub fn update(&self, queue: &wgpu::Queue, sky: &SkyGradient, environment: bool, intensity: f32) {
+        queue.write_buffer(&self.buffer, 0, bytemuck::cast_slice(&[SkyboxUniform::new(sky, environment, intensity)]));
+    }
+}
+
+/// The bind group layout of [`EnvironmentMap`]s: the cube texture at binding `0`
+/// and its sampler at binding `1`
+pub struct EnvironmentMapCommon {
+    bind_group_layout: wgpu::BindGroupLayout,
+}
+
+impl SingletonResource for EnvironmentMapCommon {
+    fn init(ctx: &mut RenderContext) -> Self {
+        let bind_group_layout = ctx.device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::Cube,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+            label: Some("environment_map_bind_group_layout"),
+        });
+
+        Self {
+            bind_group_layout,
+        }
+    }
+}
+
+impl EnvironmentMapCommon {
+    pub fn layout(&self) -> &wgpu::BindGroupLayout {
+        &self.bind_group_layout
+    }
+}
+
+/// A black environment map, bound when the sky gradient is drawn
+struct PlaceholderEnvironmentMap(EnvironmentMap);
+
+impl SingletonResource for PlaceholderEnvironmentMap {
+    fn init(ctx: &mut RenderContext) -> Self {
+        let black = [0u8, 0, 0, 255];
+        Self(EnvironmentMap::from_faces(ctx, 1, [&black; 6]))
+    }
+}
+
+/// A cube map surrounding the scene, drawn as a background that rotates with the camera.
+///
+/// The faces are in the order `+X`, `-X`, `+Y`, `-Y`, `+Z`, `-Z` and `+Y` is up,
+/// as for [`wgpu::TextureViewDimension::Cube`].
+pub struct EnvironmentMap {
+    texture: wgpu::Texture,
+    bind_group: Arc<wgpu::BindGroup>,
+}
+
+impl EnvironmentMap {
+    /// Create a cube map from the tightly packed, sRGB encoded, RGBA8 pixels of its
+    /// square faces of `size` pixels
+    pub fn from_faces(
+        cx: &mut RenderContext,
+        size: u32,
+        faces: [&[u8]; 6],
+    ) -> Self {
+        let common = cx.singleton::<EnvironmentMapCommon>();
+
+        let texture_size = wgpu::Extent3d {
+            width: size,
+            height: size,
+            depth_or_array_layers: 6,
+        };
+
+        let texture = cx.device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("environment map"),
+            size: texture_size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Rgba8UnormSrgb,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+
+        for (layer, face) in faces.iter().enumerate() {
+            assert_eq!(face.len(), (size * size * 4) as usize, "RGBA8 face size mismatch");
+
+            cx.queue.write_texture(
+                wgpu::ImageCopyTexture {
+                    texture: &texture,
+                    mip_level: 0,
+                    origin: wgpu::Origin3d {
+                        x: 0,
+                        y: 0,
+                        z: layer as u32,
+                    },
+                    aspect: wgpu::TextureAspect::All,
+                },
+                face,
+                wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(4 * size),
+                    rows_per_image: Some(size),
+                },
+                wgpu::Extent3d {
+                    depth_or_array_layers: 1,
+                    ..texture_size
+                },
+            );
+        }
+
+        let view = texture.create_view(&wgpu::TextureViewDescriptor {
+            dimension: Some(wgpu::TextureViewDimension::Cube),
+            ..Default::default()
+        });
+
+        let sampler = cx.device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("environment map sampler"),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Nearest,
+            ..Default::default()
+        });
+
+        let bind_group = cx.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: common.layout(),
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&sampler),
+                },
+            ],
+            label: Some("environment_map_bind_group"),
+        });
+
+        Self {
+            texture,
+            bind_group: Arc::new(bind_group),
+        }
+    }
+
+    /// Create a cube map from six square images of the same size, see [`EnvironmentMap::from_faces`]
+    pub fn from_images(
+        cx: &mut RenderContext,
+        faces: [&image::DynamicImage; 6],
+    ) -> Self {
+        let faces = faces.map(|face| face.to_rgba8());
+        let size = faces[0].width();
+        assert!(faces.iter().all(|face| face.width() == size && face.height() == size), "the faces must be squares of the same size");
+
+        Self::from_faces(cx, size, faces.each_ref().map(|face| face.as_raw().as_slice()))
+    }
+
+    /// Load the six faces (PNG or JPEG) from files, see [`EnvironmentMap::from_faces`]
+    pub fn from_paths(
+        cx: &mut RenderContext,
+        paths: [impl AsRef<Path>; 6],
+    ) -> Result<Self, image::ImageError> {
+        let [px, nx, py, ny, pz, nz] = paths;
+        let faces = [
+            image::open(px)?,
+            image::open(nx)?,
+            image::open(py)?,
+            image::open(ny)?,
+            image::open(pz)?,
+            image::open(nz)?,
+        ];
+
+        Ok(Self::from_images(cx, faces.each_ref()))
+    }
+
+    /// Create a cube map with faces of `face_size` pixels from an equirectangular
+    /// (latitude-longitude) panorama, whose middle looks towards `-Z`
+    pub fn from_equirectangular(
+        cx: &mut RenderContext,
+        image: &image::DynamicImage,
+        face_size: u32,
+    ) -> Self {
+        let image = image.to_rgba8();
+
+        let faces: [Vec<u8>; 6] = std::array::from_fn(|face| {
+            let mut data = Vec::with_capacity((face_size * face_size * 4) as usize);
+            for y in 0..face_size {
+                for x in 0..face_size {
+                    // coordinates of the center of the pixel in [-1, 1], `t` pointing down
+                    let s = (x as f32 + 0.5) / face_size as f32 * 2.0 - 1.0;
+                    let t = (y as f32 + 0.5) / face_size as f32 * 2.0 - 1.0;
+                    let [dx, dy, dz] = cube_direction(face, s, t);
+                    let len = (dx * dx + dy * dy + dz * dz).sqrt();
+
+                    let u = 0.5 + dx.atan2(-dz) / (2.0 * PI);
+                    let v = (dy / len).clamp(-1.0, 1.0).acos() / PI;
+                    data.extend_from_slice(&sample_bilinear(&image, u, v));
+                }
+            }
+            data
+        });
+
+        Self::from_faces(cx, face_size, faces.each_ref().map(|face| face.as_slice()))
+    }
+
+    /// Load an equirectangular panorama (PNG or JPEG) from a file, see [`EnvironmentMap::from_equirectangular`]
+    pub fn from_equirectangular_path(
+        cx: &mut RenderContext,
+        path: impl AsRef<Path>,
+        face_size: u32,
+    ) -> Result<Self, image::ImageError> {
+        let image = image::open(path)?;
+        Ok(Self::from_equirectangular(cx, &image, face_size))
+    }
+
+    pub fn texture(&self) -> &wgpu::Texture {
+        &self.texture
+    }
+
+    /// The bind group with the [`EnvironmentMapCommon`] layout
+    pub fn bind_group(&self) -> &Arc<wgpu::BindGroup> {
+        &self.bind_group
+    }
+}
+
+/// The direction of a point of a face of a cube map
+fn cube_direction(face: usize, s: f32, t: f32) -> [f32; 3] {
+    match face {
+        0 => [1.0, -t, -s],
+        1 => [-1.0, -t, s],
+        2 => [s, 1.0, t],
+        3 => [s, -1.0, -t],
+        4 => [s, -t, 1.0],
+        _ => [-s, -t, -1.0],
+    }
+}
+
+/// Sample an image at normalized coordinates, repeating horizontally
+fn sample_bilinear(image: &image::RgbaImage, u: f32, v: f32) -> [u8; 4] {
+    let (w, h) = (image.width() as i64, image.height() as i64);
+    let x = u * w as f32 - 0.5;
+    let y = v * h as f32 - 0.5;
+    let (x0, y0) = (x.floor(), y.floor());
+    let (fx, fy) = (x - x0, y - y0);
+
+    let pixel = |x: i64, y: i64| image.get_pixel(x.rem_euclid(w) as u32, y.clamp(0, h - 1) as u32).0;
+    let (x0, y0) = (x0 as i64, y0 as i64);
+    let [p00, p10, p01, p11] = [pixel(x0, y0), pixel(x0 + 1, y0), pixel(x0, y0 + 1), pixel(x0 + 1, y0 + 1)];
+
+    std::array::from_fn(|c| {
+        let top = p00[c] as f32 * (1.0 - fx) + p10[c] as f32 * fx;
+        let bottom = p01[c] as f32 * (1.0 - fx) + p11[c] as f32 * fx;
+        (top * (1.0 - fy) + bottom * fy).round() as u8
+    })
+}
+
+/// Draws the [`SkyGradient`] or an [`EnvironmentMap`] behind the scene, with a
+/// full screen triangle
+pub struct SkyboxPipeline {
+    pipeline: Pipeline,
+}
+
+impl Default for SkyboxPipeline {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SkyboxPipeline {
+    pub fn new() -> Self {
+        let primitive = PrimitiveState {
+            topology: PrimitiveTopology::TriangleList,
+            ..Default::default()
+        };
+
+        let pipeline = Pipeline::from_builder(move |cx, formats| {
+            let shader = cx.singleton::<SkyboxShader>();
+
+            let camera_common = cx.singleton::<ProjectionCameraCommon>();
+            let material_common = cx.singleton::<SkyboxMaterialCommon>();
+            let environment_common = cx.singleton::<EnvironmentMapCommon>();
+
+            let render_pipeline_layout =
+            cx.device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: None,
+                bind_group_layouts: &[
+                    camera_common.layout(),
+                    material_common.layout(),
+                    environment_common.layout(),
+                ],
+                push_constant_ranges: &[],
+            });
+
+            let targets = formats.color_targets(wgpu::BlendState::REPLACE);
+
+            cx.device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some("skybox pipeline"),
+                layout: Some(&render_pipeline_layout),
+                vertex: wgpu::VertexState {
+                    module: &shader.shader,
+                    entry_point: "vs_main",
+                    buffers: &[],
+                    compilation_options: Default::default(),
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &shader.shader,
+                    entry_point: "fs_main",
+                    targets: &targets,
+                    compilation_options: Default::default(),
+                }),
+                primitive,
+                // drawn first, behind everything
+                depth_stencil: formats.depth_format.map(|format| wgpu::DepthStencilState {
+                    format,
+                    depth_write_enabled: false,
+                    depth_compare: wgpu::CompareFunction::Always,
+                    stencil: wgpu::StencilState::default(),
+                    bias: wgpu::DepthBiasState::default(),
+                }),
+                multisample: Default::default(),
+                multiview: None,
+                cache: None,
+            })
+        });
+
+        Self {
+            pipeline,
+        }
+    }
+
+    /// Draw the background, only in the main pass.
+    ///
+    /// The `environment` is drawn if the `material` was updated for it.
+    pub fn render<'a>(
+        &self,
+        cx: &mut RenderContext,
+        pass: &mut Pass<'a>,
+        material: &SkyboxMaterial,
+        environment: Option<&EnvironmentMap>,
+    ) {
+        if pass.kind() != PassKind::Main {
+            return;
+        }
+
+        let material_bind_group = material.bind_group.clone();
+        let environment_bind_group = match environment {
+            Some(environment) => environment.bind_group.clone(),
+            None => cx.singleton::<PlaceholderEnvironmentMap>().0.bind_group.clone(),
+        };
+
+        let pipeline = self.pipeline.get(cx, pass);
+
+        pass.defer(move |rp, globals| {
+            rp.set_pipeline(&pipeline);
+            rp.set_bind_group(0, globals, &[]);
+            rp.set_bind_group(1, &material_bind_group, &[]);
+            rp.set_bind_group(2, &environment_bind_group, &[]);
+            rp.draw(0..3, 0..1);
+        });
+    }
+}
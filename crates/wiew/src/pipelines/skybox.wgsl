@@ -0,0 +1,74 @@
+// ================================
+//            Inputs
+// ================================
+
+struct Skybox {
+    zenith: vec4<f32>,
+    horizon: vec4<f32>,
+    ground: vec4<f32>,
+    exponent: f32,
+    // 0: sky gradient, 1: environment map
+    mode: u32,
+    intensity: f32,
+};
+
+@group(1) @binding(0)
+var<uniform> skybox: Skybox;
+
+@group(2) @binding(0)
+var environment: texture_cube<f32>;
+@group(2) @binding(1)
+var environment_sampler: sampler;
+
+// ================================
+//            Vertex
+// ================================
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) ndc: vec2<f32>,
+};
+
+// a triangle covering the whole screen
+@vertex
+fn vs_main(@builtin(vertex_index) vertex_index: u32) -> VertexOutput {
+    let uv = vec2<f32>(f32((vertex_index << 1u) & 2u), f32(vertex_index & 2u));
+
+    var out: VertexOutput;
+    out.ndc = uv * 2.0 - 1.0;
+    out.clip_position = vec4<f32>(out.ndc, 0.0, 1.0);
+    return out;
+}
+
+// ================================
+//            Fragment
+// ================================
+
+// world space direction of the view ray, only the rotation of the camera matters
+fn view_direction(ndc: vec2<f32>) -> vec3<f32> {
+    let p = camera.inv_proj * vec4<f32>(ndc, 0.5, 1.0);
+    let world = camera.inv_view * vec4<f32>(p.xyz / p.w, 0.0);
+    return normalize(world.xyz);
+}
+
+fn sky_gradient(direction: vec3<f32>) -> vec3<f32> {
+    let h = direction.y;
+    if (h >= 0.0) {
+        return mix(skybox.horizon.rgb, skybox.zenith.rgb, pow(h, skybox.exponent));
+    }
+    return mix(skybox.horizon.rgb, skybox.ground.rgb, pow(-h, skybox.exponent));
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let direction = view_direction(in.ndc);
+
+    var color: vec3<f32>;
+    if (skybox.mode == 1u) {
+        color = textureSample(environment, environment_sampler, direction).rgb;
+    } else {
+        color = sky_gradient(direction);
+    }
+
+    return vec4<f32>(color * skybox.intensity, 1.0);
+}
@@ -0,0 +1,443 @@
+use std::sync::Arc;
+
+use wgpu::{util::DeviceExt, Device, PrimitiveState, PrimitiveTopology, ShaderModule};
+
+use crate::{Pass, ProjectionCameraCommon, RenderContext, SingletonResource};
+
+use super::{shader_with_globals, Pipeline};
+
+/// Format of the occlusion target: `1` where the ambient light is not occluded
+pub const OCCLUSION_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::R8Unorm;
+
+/// Largest [`SsaoSettings::samples`]
+pub const MAX_SAMPLES: u32 = 64;
+
+/// Screen-space ambient occlusion, computed from the depth buffer of the view
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SsaoSettings {
+    /// Radius of the hemisphere sampled around each point, in world units
+    pub radius: f32,
+    /// Number of samples per pixel (at most [`MAX_SAMPLES`])
+    pub samples: u32,
+    /// Exponent applied to the ambient light, higher values give darker occlusion
+    pub intensity: f32,
+    /// Minimum depth difference for a sample to be occluded, in world units, against self-occlusion
+    pub bias: f32,
+    /// Radius of the box blur that removes the noise of the sampling, in pixels
+    pub blur_radius: u32,
+}
+
+impl Default for SsaoSettings {
+    fn default() -> Self {
+        Self {
+            radius: 0.5,
+            samples: 16,
+            intensity: 1.0,
+            bias: 0.025,
+            blur_radius: 2,
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct SsaoUniform {
+    radius: f32,
+    bias: f32,
+    intensity: f32,
+    samples: u32,
+    blur_radius: u32,
+    _padding: [u32; 3],
+}
+
+impl From<&SsaoSettings> for SsaoUniform {
+    fn from(settings: &SsaoSettings) -> Self {
+        Self {
+            radius: settings.radius,
+            bias: settings.bias,
+            intensity: settings.intensity,
+            samples: settings.samples.clamp(1, MAX_SAMPLES),
+            blur_radius: settings.blur_radius,
+            _padding: [0; 3],
+        }
+    }
+}
+
+/// A shader for the occlusion and the blur passes
+pub struct SsaoShader {
+    shader: ShaderModule,
+}
+
+impl SsaoShader {
+    /// Create a new ssao shader
+    pub fn new(
+        device: &Device,
+    ) -> Self {
+        Self {
+            shader: shader_with_globals(device, "ssao.wgsl", include_str!("ssao.wgsl")),
+        }
+    }
+}
+
+impl SingletonResource for SsaoShader {
+    fn init(ctx: &mut RenderContext) -> Self {
+        Self::new(ctx.device)
+    }
+}
+
+/// The bind group layouts of [`SsaoTargets`]: the settings and the depth buffer
+/// as `@group(1)`, the occlusion as `@group(2)`
+pub struct SsaoCommon {
+    inputs_layout: wgpu::BindGroupLayout,
+    occlusion_layout: wgpu::BindGroupLayout,
+}
+
+impl SingletonResource for SsaoCommon {
+    fn init(ctx: &mut RenderContext) -> Self {
+        let inputs_layout = ctx.device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Depth,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+            ],
+            label: Some("ssao_inputs_bind_group_layout"),
+        });
+
+        let occlusion_layout = ctx.device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+            ],
+            label: Some("ssao_occlusion_bind_group_layout"),
+        });
+
+        Self {
+            inputs_layout,
+            occlusion_layout,
+        }
+    }
+}
+
+impl SsaoCommon {
+    pub fn inputs_layout(&self) -> &wgpu::BindGroupLayout {
+        &self.inputs_layout
+    }
+
+    pub fn occlusion_layout(&self) -> &wgpu::BindGroupLayout {
+        &self.occlusion_layout
+    }
+}
+
+/// The occlusion target of a view, and the bind groups reading its depth buffer
+pub struct SsaoTargets {
+    buffer: wgpu::Buffer,
+    occlusion: wgpu::TextureView,
+    inputs_bind_group: Arc<wgpu::BindGroup>,
+    occlusion_bind_group: Arc<wgpu::BindGroup>,
+}
+
+impl SsaoTargets {
+    /// `depth` is the depth buffer of the view, of size `width` x `height`
+    pub fn new(
+        cx: &mut RenderContext,
+        depth: &wgpu::TextureView,
+        width: u32,
+        height: u32,
+    ) -> Self {
+        let common = cx.singleton::<SsaoCommon>();
+
+        let buffer = cx.device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("ssao buffer"),
+            contents: bytemuck::cast_slice(&[SsaoUniform::from(&SsaoSettings::default())]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let occlusion = cx.device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("ssao occlusion texture"),
+            size: wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: OCCLUSION_FORMAT,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        }).create_view(&wgpu::TextureViewDescriptor::default());
+
+        let inputs_bind_group = cx.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: common.inputs_layout(),
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(depth),
+                },
+            ],
+            label: Some("ssao_inputs_bind_group"),
+        });
+
+        let occlusion_bind_group = cx.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: common.occlusion_layout(),
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&occlusion),
+                },
+            ],
+            label: Some("ssao_occlusion_bind_group"),
+        });
+
+        Self {
+            buffer,
+            occlusion,
+            inputs_bind_group: Arc::new(inputs_bind_group),
+            occlusion_bind_group: Arc::new(occlusion_bind_group),
+        }
+    }
+
+    pub fn update(
+        &self,
+        queue: &wgpu::Queue,
+        settings: &SsaoSettings,
+    ) {
+        queue.write_buffer(&self.buffer, 0, bytemuck::cast_slice(&[SsaoUniform::from(settings)]));
+    }
+
+    /// The color attachment of the occlusion pass: nothing occluded
+    pub fn color_attachment(&self) -> wgpu::RenderPassColorAttachment<'_> {
+        wgpu::RenderPassColorAttachment {
+            view: &self.occlusion,
+            resolve_target: None,
+            ops: wgpu::Operations {
+                load: wgpu::LoadOp::Clear(wgpu::Color::WHITE),
+                store: wgpu::StoreOp::Store,
+            },
+        }
+    }
+}
+
+/// Computes the ambient occlusion of the depth buffer into the occlusion target of
+/// [`SsaoTargets`], with a full screen triangle.
+pub struct SsaoPipeline {
+    pipeline: Pipeline,
+}
+
+impl Default for SsaoPipeline {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SsaoPipeline {
+    pub fn new() -> Self {
+        let primitive = PrimitiveState {
+            topology: PrimitiveTopology::TriangleList,
+            ..Default::default()
+        };
+
+        let pipeline = Pipeline::from_builder(move |cx, _formats| {
+            let shader = cx.singleton::<SsaoShader>();
+
+            let camera_common = cx.singleton::<ProjectionCameraCommon>();
+            let ssao_common = cx.singleton::<SsaoCommon>();
+
+            let render_pipeline_layout =
+            cx.device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: None,
+                bind_group_layouts: &[
+                    camera_common.layout(),
+                    ssao_common.inputs_layout(),
+                ],
+                push_constant_ranges: &[],
+            });
+
+            cx.device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some("ssao pipeline"),
+                layout: Some(&render_pipeline_layout),
+                vertex: wgpu::VertexState {
+                    module: &shader.shader,
+                    entry_point: "vs_main",
+                    buffers: &[],
+                    compilation_options: Default::default(),
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &shader.shader,
+                    entry_point: "fs_occlusion",
+                    targets: &[Some(wgpu::ColorTargetState {
+                        format: OCCLUSION_FORMAT,
+                        blend: None,
+                        write_mask: wgpu::ColorWrites::ALL,
+                    })],
+                    compilation_options: Default::default(),
+                }),
+                primitive,
+                depth_stencil: None,
+                multisample: Default::default(),
+                multiview: None,
+                cache: None,
+            })
+        });
+
+        Self {
+            pipeline,
+        }
+    }
+
+    /// Compute the occlusion of `targets`, `pass` must render into [`SsaoTargets::color_attachment`]
+    pub fn render<'a>(
+        &self,
+        cx: &mut RenderContext,
+        pass: &mut Pass<'a>,
+        targets: &SsaoTargets,
+    ) {
+        let inputs_bind_group = targets.inputs_bind_group.clone();
+
+        let pipeline = self.pipeline.get(cx, pass);
+
+        pass.defer(move |rp, globals| {
+            rp.set_pipeline(&pipeline);
+            rp.set_bind_group(0, globals, &[]);
+            rp.set_bind_group(1, &inputs_bind_group, &[]);
+            rp.draw(0..3, 0..1);
+        });
+    }
+}
+
+/// Blurs the occlusion of [`SsaoTargets`] and multiplies the surface by it,
+/// with a full screen triangle.
+pub struct SsaoApplyPipeline {
+    pipeline: Pipeline,
+}
+
+impl Default for SsaoApplyPipeline {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SsaoApplyPipeline {
+    pub fn new() -> Self {
+        let primitive = PrimitiveState {
+            topology: PrimitiveTopology::TriangleList,
+            ..Default::default()
+        };
+
+        let pipeline = Pipeline::from_builder(move |cx, formats| {
+            let shader = cx.singleton::<SsaoShader>();
+
+            let camera_common = cx.singleton::<ProjectionCameraCommon>();
+            let ssao_common = cx.singleton::<SsaoCommon>();
+
+            let render_pipeline_layout =
+            cx.device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: None,
+                bind_group_layouts: &[
+                    camera_common.layout(),
+                    ssao_common.inputs_layout(),
+                    ssao_common.occlusion_layout(),
+                ],
+                push_constant_ranges: &[],
+            });
+
+            // the color is multiplied by the occlusion, the alpha is kept
+            let multiply = wgpu::BlendState {
+                color: wgpu::BlendComponent {
+                    src_factor: wgpu::BlendFactor::Zero,
+                    dst_factor: wgpu::BlendFactor::Src,
+                    operation: wgpu::BlendOperation::Add,
+                },
+                alpha: wgpu::BlendComponent {
+                    src_factor: wgpu::BlendFactor::Zero,
+                    dst_factor: wgpu::BlendFactor::One,
+                    operation: wgpu::BlendOperation::Add,
+                },
+            };
+
+            let targets = formats.color_targets(multiply);
+
+            cx.device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some("ssao apply pipeline"),
+                layout: Some(&render_pipeline_layout),
+                vertex: wgpu::VertexState {
+                    module: &shader.shader,
+                    entry_point: "vs_main",
+                    buffers: &[],
+                    compilation_options: Default::default(),
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &shader.shader,
+                    entry_point: "fs_apply",
+                    targets: &targets,
+                    compilation_options: Default::default(),
+                }),
+                primitive,
+                depth_stencil: formats.depth_format.map(|format| wgpu::DepthStencilState {
+                    format,
+                    depth_write_enabled: false,
+                    depth_compare: wgpu::CompareFunction::Always,
+                    stencil: wgpu::StencilState::default(),
+                    bias: wgpu::DepthBiasState::default(),
+                }),
+                multisample: Default::default(),
+                multiview: None,
+                cache: None,
+            })
+        });
+
+        Self {
+            pipeline,
+        }
+    }
+
+    /// Darken the surface of `pass` with the occlusion computed into `targets`
+    pub fn render<'a>(
+        &self,
+        cx: &mut RenderContext,
+        pass: &mut Pass<'a>,
+        targets: &SsaoTargets,
+    ) {
+        let inputs_bind_group = targets.inputs_bind_group.clone();
+        let occlusion_bind_group = targets.occlusion_bind_group.clone();
+
+        let pipeline = self.pipeline.get(cx, pass);
+
+        pass.defer(move |rp, globals| {
+            rp.set_pipeline(&pipeline);
+            rp.set_bind_group(0, globals, &[]);
+            rp.set_bind_group(1, &inputs_bind_group, &[]);
+            rp.set_bind_group(2, &occlusion_bind_group, &[]);
+            rp.draw(0..3, 0..1);
+        });
+    }
+}
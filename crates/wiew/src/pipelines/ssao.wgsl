@@ -0,0 +1,157 @@
+// ================================
+//            Inputs
+// ================================
+
+const MAX_SAMPLES: u32 = 64u;
+const PI: f32 = 3.14159265359;
+
+struct SsaoSettings {
+    // radius of the sampled hemisphere, in world units
+    radius: f32,
+    bias: f32,
+    intensity: f32,
+    samples: u32,
+    blur_radius: u32,
+};
+
+@group(1) @binding(0)
+var<uniform> ssao: SsaoSettings;
+@group(1) @binding(1)
+var depth_texture: texture_depth_2d;
+
+// only used by `fs_apply`
+@group(2) @binding(0)
+var occlusion_texture: texture_2d<f32>;
+
+// ================================
+//            Vertex
+// ================================
+
+// a triangle covering the whole screen
+@vertex
+fn vs_main(@builtin(vertex_index) vertex_index: u32) -> @builtin(position) vec4<f32> {
+    let uv = vec2<f32>(f32((vertex_index << 1u) & 2u), f32(vertex_index & 2u));
+    return vec4<f32>(uv * 2.0 - 1.0, 0.0, 1.0);
+}
+
+// ================================
+//            Occlusion
+// ================================
+
+fn load_depth(coords: vec2<i32>) -> f32 {
+    let size = vec2<i32>(textureDimensions(depth_texture));
+    return textureLoad(depth_texture, clamp(coords, vec2<i32>(0), size - 1), 0);
+}
+
+// view space position of a point of the screen, in pixels
+fn view_position(uv: vec2<f32>, depth: f32) -> vec3<f32> {
+    let ndc = vec3<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, depth);
+    let p = camera.inv_proj * vec4<f32>(ndc, 1.0);
+    return p.xyz / p.w;
+}
+
+fn view_position_at(coords: vec2<i32>) -> vec3<f32> {
+    let uv = (vec2<f32>(coords) + 0.5) / camera.viewport_size;
+    return view_position(uv, load_depth(coords));
+}
+
+// normal reconstructed from the neighbors, on the side with the smallest depth
+// difference so that the edges of the objects are preserved
+fn view_normal(coords: vec2<i32>, p: vec3<f32>) -> vec3<f32> {
+    let right = view_position_at(coords + vec2<i32>(1, 0)) - p;
+    let left = p - view_position_at(coords - vec2<i32>(1, 0));
+    let down = view_position_at(coords + vec2<i32>(0, 1)) - p;
+    let up = p - view_position_at(coords - vec2<i32>(0, 1));
+
+    let dx = select(right, left, abs(left.z) < abs(right.z));
+    let dy = select(down, up, abs(up.z) < abs(down.z));
+
+    let n = normalize(cross(dy, dx));
+    // facing the camera, which is at the origin
+    return select(n, -n, dot(n, p) > 0.0);
+}
+
+// PCG hash
+fn hash(v: u32) -> u32 {
+    let state = v * 747796405u + 2891336453u;
+    let word = ((state >> ((state >> 28u) + 4u)) ^ state) * 277803737u;
+    return (word >> 22u) ^ word;
+}
+
+fn random(seed: ptr<function, u32>) -> f32 {
+    *seed = hash(*seed);
+    return f32(*seed) / 4294967295.0;
+}
+
+@fragment
+fn fs_occlusion(@builtin(position) position: vec4<f32>) -> @location(0) vec4<f32> {
+    let coords = vec2<i32>(position.xy);
+    let depth = load_depth(coords);
+
+    // background
+    if (depth >= 1.0) {
+        return vec4<f32>(1.0);
+    }
+
+    let p = view_position_at(coords);
+    let n = view_normal(coords, p);
+
+    // a random frame around the normal for each pixel, the noise is removed by the blur
+    var seed = hash(u32(coords.x) + hash(u32(coords.y)));
+    let a = vec3<f32>(random(&seed), random(&seed), random(&seed)) * 2.0 - 1.0;
+    let t = normalize(select(a, vec3<f32>(1.0, 0.0, 0.0), abs(dot(a, n)) > 0.99 * length(a)) - n * dot(a, n));
+    let b = cross(n, t);
+
+    let samples = clamp(ssao.samples, 1u, MAX_SAMPLES);
+    var occlusion = 0.0;
+    for (var i = 0u; i < samples; i++) {
+        // cosine-weighted direction in the hemisphere, closer samples are denser
+        let phi = 2.0 * PI * random(&seed);
+        let cos_theta = sqrt(random(&seed));
+        let sin_theta = sqrt(1.0 - cos_theta * cos_theta);
+        var scale = (f32(i) + random(&seed)) / f32(samples);
+        scale = mix(0.1, 1.0, scale * scale);
+
+        let direction = t * cos(phi) * sin_theta + b * sin(phi) * sin_theta + n * cos_theta;
+        let sample = p + direction * ssao.radius * scale;
+
+        let clip = camera.proj * vec4<f32>(sample, 1.0);
+        let ndc = clip.xy / clip.w;
+        let uv = vec2<f32>(ndc.x * 0.5 + 0.5, 0.5 - ndc.y * 0.5);
+        let scene = view_position_at(vec2<i32>(uv * camera.viewport_size));
+
+        // occluders farther than the radius do not count
+        let range = smoothstep(0.0, 1.0, ssao.radius / max(abs(p.z - scene.z), 1e-4));
+        occlusion += select(0.0, range, scene.z >= sample.z + ssao.bias);
+    }
+
+    let ao = pow(1.0 - occlusion / f32(samples), ssao.intensity);
+    return vec4<f32>(ao, ao, ao, 1.0);
+}
+
+// ================================
+//            Apply
+// ================================
+
+// blurs the occlusion and multiplies the color of the target by it
+@fragment
+fn fs_apply(@builtin(position) position: vec4<f32>) -> @location(0) vec4<f32> {
+    let coords = vec2<i32>(position.xy);
+
+    if (load_depth(coords) >= 1.0) {
+        discard;
+    }
+
+    let size = vec2<i32>(textureDimensions(occlusion_texture));
+    let r = i32(ssao.blur_radius);
+    var sum = 0.0;
+    for (var y = -r; y <= r; y++) {
+        for (var x = -r; x <= r; x++) {
+            let c = clamp(coords + vec2<i32>(x, y), vec2<i32>(0), size - 1);
+            sum += textureLoad(occlusion_texture, c, 0).r;
+        }
+    }
+    let ao = sum / f32((2 * r + 1) * (2 * r + 1));
+
+    return vec4<f32>(ao, ao, ao, 1.0);
+}
@@ -0,0 +1,168 @@
+use std::collections::HashMap;
+
+use wgpu::{Device, PrimitiveState, PrimitiveTopology, ShaderModule};
+
+use crate::{decl_vertex_raw_repr, instance::Instance3d, Pass, ProjectionCameraCommon, RenderContext, SingletonResource, Texture2d, Texture2dCommon, VertexBufferSlice, VertexRawRepr};
+
+use super::{shader_with_globals, Pipeline, RenderModeState};
+
+decl_vertex_raw_repr! {
+    #[derive(Debug)]
+    struct Vertex (Vertex step mode) {
+        pub position: [f32; 3] as [7 => Float32x3],
+        pub normal: [f32; 3] as [8 => Float32x3],
+        pub uv: [f32; 2] as [9 => Float32x2],
+    }
+}
+
+/// A shader for textured meshes
+pub struct TexturedShader {
+    shader: ShaderModule,
+}
+
+impl TexturedShader {
+    /// Create a new textured shader
+    pub fn new(
+        device: &Device,
+    ) -> Self {
+        Self {
+            shader: shader_with_globals(device, "textured.wgsl", include_str!("textured.wgsl")),
+        }
+    }
+}
+
+impl SingletonResource for TexturedShader {
+    fn init(ctx: &mut RenderContext) -> Self {
+        Self::new(ctx.device)
+    }
+}
+
+/// Meshes colored by a [`Texture2d`], multiplied by the instance color.
+///
+/// The texture is bound as `@group(1)` with the [`Texture2dCommon`] layout.
+pub struct TexturedPipeline {
+    pipeline: Pipeline,
+    order_independent: bool,
+}
+
+impl TexturedPipeline {
+    /// Create a new textured pipeline
+    ///
+    /// If `lit` is true, the texture is shaded by the camera light, otherwise
+    /// its colors are shown as they are (e.g. for colormaps).
+    pub fn new(
+        topology: PrimitiveTopology,
+        depth_compare: wgpu::CompareFunction,
+        use_depth_stencil: bool,
+        lit: bool,
+    ) -> Self {
+        let primitive = PrimitiveState {
+            topology,
+            ..Default::default()
+        };
+
+        let pipeline = Pipeline::from_builder(move |cx, formats| {
+            let shader = cx.singleton::<TexturedShader>();
+            let render_mode = RenderModeState::new(cx.device, formats.render_mode, primitive.topology);
+
+            let camera_common = cx.singleton::<ProjectionCameraCommon>();
+            let texture_common = cx.singleton::<Texture2dCommon>();
+
+            let render_pipeline_layout =
+            cx.device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: None,
+                bind_group_layouts: &[
+                    camera_common.layout(),
+                    texture_common.layout(),
+                ],
+                push_constant_ranges: &[],
+            });
+
+            let targets = formats.color_targets(wgpu::BlendState::ALPHA_BLENDING);
+
+            let mut constants = HashMap::from([
+                ("LIT".to_string(), lit as u32 as f64),
+            ]);
+            constants.extend(render_mode.constants.clone());
+
+            cx.device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some("textured pipeline"),
+                layout: Some(&render_pipeline_layout),
+                vertex: wgpu::VertexState {
+                    module: &shader.shader,
+                    entry_point: "vs_main",
+                    buffers: &[
+                        Vertex::desc(),
+                        Instance3d::desc(),
+                    ],
+                    compilation_options: Default::default(),
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &shader.shader,
+                    entry_point: formats.fragment_entry_point(),
+                    targets: &targets,
+                    compilation_options: wgpu::PipelineCompilationOptions {
+                        constants: &constants,
+                        ..Default::default()
+                    },
+                }),
+                primitive: PrimitiveState {
+                    polygon_mode: render_mode.polygon_mode,
+                    ..primitive
+                },
+                depth_stencil: formats.depth_format.map(|format| wgpu::DepthStencilState {
+                    format,
+                    depth_write_enabled: use_depth_stencil && render_mode.depth_write && formats.depth_write(),
+                    depth_compare: formats.depth_compare(depth_compare),
+                    stencil: formats.stencil(),
+                    bias: formats.depth_bias(),
+                }),
+                multisample: Default::default(),
+                multiview: None,
+                cache: None,
+            })
+        });
+
+        Self {
+            pipeline,
+            order_independent: false,
+        }
+    }
+
+    /// Draw into the order-independent transparency pass, see [`MeshPipeline::order_independent`](super::builder::MeshPipeline::order_independent)
+    pub fn order_independent(self) -> Self {
+        Self {
+            order_independent: true,
+            ..self
+        }
+    }
+
+    pub fn render<'a>(
+        &self,
+        cx: &mut RenderContext,
+        pass: &mut Pass<'a>,
+        vertices: impl Into<VertexBufferSlice<Vertex>>,
+        instances: impl Into<VertexBufferSlice<Instance3d>>,
+        texture: &Texture2d,
+    ) {
+        let vertices: VertexBufferSlice<Vertex> = vertices.into();
+        let instances: VertexBufferSlice<Instance3d> = instances.into();
+        let texture = texture.bind_group().clone();
+
+        let kind = if self.order_independent {
+            pass.transparent_kind()
+        } else {
+            pass.kind()
+        };
+        let pipeline = self.pipeline.get_for(cx, pass, kind);
+
+        pass.defer_for(kind, move |rp, globals| {
+            rp.set_pipeline(&pipeline);
+            rp.set_bind_group(0, globals, &[]);
+            rp.set_bind_group(1, &texture, &[]);
+            rp.set_vertex_buffer(0, vertices.buffer.slice(..));
+            rp.set_vertex_buffer(1, instances.buffer.slice(..));
+            rp.draw(vertices.range.clone(), instances.range.clone());
+        });
+    }
+}
@@ -0,0 +1,122 @@
+// ================================
+//            Material
+// ================================
+
+@group(1) @binding(0)
+var material_texture: texture_2d<f32>;
+@group(1) @binding(1)
+var material_sampler: sampler;
+
+// shade with the camera light (lambertian) instead of showing the plain texture
+override LIT: bool = false;
+
+// ================================
+//            Vertex
+// ================================
+
+struct VertexInput {
+    @location(7) position: vec3<f32>,
+    @location(8) normal: vec3<f32>,
+    @location(9) uv: vec2<f32>,
+};
+
+struct InstanceInput {
+    @location(0) model_0: vec4<f32>,
+    @location(1) model_1: vec4<f32>,
+    @location(2) model_2: vec4<f32>,
+    @location(3) model_3: vec4<f32>,
+    @location(4) model_inv_tr_0: vec3<f32>,
+    @location(5) model_inv_tr_1: vec3<f32>,
+    @location(6) model_inv_tr_2: vec3<f32>,
+    @location(13) color: vec4<f32>,
+    @location(14) object_id: u32,
+    @location(15) visible: u32,
+};
+
+@vertex
+fn vs_main(
+    @builtin(vertex_index) vertex_index: u32,
+    model: VertexInput,
+    instance: InstanceInput,
+) -> VertexOutput {
+    let model_matrix = mat4x4<f32>(
+        instance.model_0,
+        instance.model_1,
+        instance.model_2,
+        instance.model_3,
+    );
+    let normal_matrix = mat3x3<f32>(
+        instance.model_inv_tr_0,
+        instance.model_inv_tr_1,
+        instance.model_inv_tr_2,
+    );
+
+    let world_position = model_matrix * vec4<f32>(model.position, 1.0);
+
+    var out: VertexOutput;
+    out.clip_position = camera.proj * camera.view * world_position;
+    out.world_position = world_position.xyz;
+    out.world_normal = normal_matrix * model.normal;
+    out.uv = model.uv;
+    out.color = instance.color;
+    out.object_id = instance.object_id;
+    out.barycentric = corner_barycentric(vertex_index);
+
+    // hidden instances are moved outside of the clip volume
+    if (instance.visible == 0u) {
+        out.clip_position = vec4<f32>(2.0, 2.0, 2.0, 1.0);
+    }
+
+    return out;
+}
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) color: vec4<f32>,
+    @location(1) @interpolate(flat) object_id: u32,
+    @location(2) uv: vec2<f32>,
+    @location(3) world_normal: vec3<f32>,
+    @location(4) barycentric: vec3<f32>,
+    @location(5) world_position: vec3<f32>,
+};
+
+// ================================
+//            Fragment
+// ================================
+
+fn shade(
+    in: VertexOutput,
+    front_facing: bool,
+) -> vec4<f32> {
+    clip_fragment(in.world_position);
+
+    var color = textureSample(material_texture, material_sampler, in.uv) * in.color;
+
+    if (LIT) {
+        var normal = normalize(in.world_normal);
+        if (!front_facing) {
+            normal = -normal;
+        }
+        let diffuse = max(dot(normal, normalize(camera.light_dir)), 0.0) * shadow_factor(in.world_position, normal);
+        color = vec4<f32>(color.rgb * (0.2 + 0.8 * diffuse), color.a);
+    }
+
+    return apply_fog(apply_render_mode(color, in.barycentric), in.world_position, in.clip_position.xy);
+}
+
+@fragment
+fn fs_main(
+    in: VertexOutput,
+    @builtin(front_facing) front_facing: bool,
+) -> @location(0) vec4<f32> {
+    return shade(in, front_facing);
+}
+
+// transparent pass of the view, see `oit_output`
+@fragment
+fn fs_oit(
+    in: VertexOutput,
+    @builtin(front_facing) front_facing: bool,
+) -> OitOutput {
+    return oit_output(shade(in, front_facing), in.clip_position.z);
+}
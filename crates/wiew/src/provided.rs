@@ -1,9 +1,9 @@
 use std::{ops::Deref, sync::{Arc, Mutex}};
 
-use rotation3::Placement3;
+use nalgebra::{Matrix4, Point3};
 use wgpu::{util::DeviceExt, Buffer, PrimitiveTopology};
 
-use crate::{instance::Instance3d, pipelines::flat::{self, FlatIdentityPipeline, FlatPipeline}, Pass, ProjectionCameraBuffer, Render, RenderContext, Res, SurfaceInfo, Trackball, TrackballCamera, VertexBuffer, View};
+use crate::{instance::Instance3d, pipelines::{flat::{self, FlatIdentityPipeline}, infinite_grid::{InfiniteGridMaterial, InfiniteGridPipeline}, line::{Lines, LineStyle}, object_id::ObjectIdPipeline, oit::{OitCompositePipeline, OitTargets}, outline::{OutlineMaskPipeline, OutlinePipeline, OutlineSettings, OutlineTargets, Selection, MASK_FORMAT}, post::{PostEffect, PostTargets, MAX_POST_EFFECTS, POST_FORMAT}, section::{SectionCap, SectionCapPipeline}, skybox::{EnvironmentMap, SkyGradient, SkyboxMaterial, SkyboxPipeline}, ssao::{SsaoApplyPipeline, SsaoPipeline, SsaoSettings, SsaoTargets, OCCLUSION_FORMAT}}, Aabb, ClipPlane, FogSettings, Frustum, GridSettings, LightCamera, Pass, PassCamera, PassKind, ProjectionCamera, ProjectionCameraBuffer, Render, RenderContext, RenderMode, Res, ShadowMap, ShadowSettings, SurfaceInfo, Trackball, TrackballCamera, VertexBuffer, View, MAX_CLIP_PLANES, SHADOW_MAP_FORMAT};
 
 
 pub trait Scene3d: 'static + Send + Sync {
@@ -17,8 +17,94 @@ pub trait Scene3d: 'static + Send + Sync {
         Scene3dBackground::DEFAULT_BG_RAINBOW
     }
 
-    fn grid(&self) -> bool {
-        true
+    /// What is drawn behind the scene, by default the gradient of [`Scene3d::background_color`]
+    fn background(&self) -> Background {
+        Background::Gradient(self.background_color())
+    }
+
+    /// The grid drawn under the scene, `None` to hide it
+    fn grid(&self) -> Option<GridSettings> {
+        Some(GridSettings::default())
+    }
+
+    /// Bounds of the scene, the shadow map of the camera light covers them.
+    ///
+    /// There are no shadows without bounds.
+    fn bounds(&self) -> Option<Aabb> {
+        None
+    }
+
+    /// Draw the objects that cast shadows into the depth-only pass of the camera
+    /// light ([`PassKind::Shadow`]), by default the whole scene is drawn.
+    fn raster_shadow(
+        &mut self,
+        cx: &mut RenderContext,
+        pass: &mut Pass,
+    ) {
+        self.raster(cx, pass);
+    }
+
+    /// Draw the closed surfaces into the stencil-only pass of the section faces of a
+    /// clipping plane ([`PassKind::Section`]), by default the whole scene is drawn.
+    ///
+    /// Only the planes with a cap color have this pass, see [`ClipPlane::cap`].
+    fn raster_sections(
+        &mut self,
+        cx: &mut RenderContext,
+        pass: &mut Pass,
+    ) {
+        self.raster(cx, pass);
+    }
+
+    /// Draw the selectable objects with an [`ObjectIdPipeline`], into the object id
+    /// pass of the outline of the [`Selection`]. By default nothing is drawn and
+    /// nothing can be outlined.
+    fn raster_object_ids(
+        &mut self,
+        _cx: &mut RenderContext,
+        _pass: &mut Pass,
+    ) {
+    }
+}
+
+/// The background of a [`Scene3d`]
+pub enum Background {
+    /// A gradient between the corners of the screen
+    Gradient(Scene3dBackground),
+    /// A procedural sky that rotates with the camera
+    Sky(SkyGradient),
+    /// A cube map that rotates with the camera, with its colors multiplied by `intensity`
+    Environment {
+        map: Res<EnvironmentMap>,
+        intensity: f32,
+    },
+}
+
+impl Background {
+    /// An environment map drawn with its own colors, it can be loaded in a [`Res`]
+    /// with [`EnvironmentMap::from_equirectangular_path`] for example
+    pub fn environment(map: Res<EnvironmentMap>) -> Self {
+        Background::Environment {
+            map,
+            intensity: 1.0,
+        }
+    }
+
+    /// The colors of the top left, top right, bottom left and bottom right corners,
+    /// which the fog blends toward (see [`CameraUniform::set_fog`](crate::CameraUniform::set_fog)):
+    /// the horizon of a sky and [`FogSettings::DEFAULT_COLOR`] for an environment map
+    pub fn fog_colors(&self) -> [[f32; 4]; 4] {
+        match self {
+            Background::Gradient(gradient) => [gradient.top_left, gradient.top_right, gradient.bottom_left, gradient.bottom_right],
+            Background::Sky(sky) => {
+                let [r, g, b] = sky.horizon;
+                [[r, g, b, 1.0]; 4]
+            },
+            Background::Environment { .. } => {
+                let [r, g, b] = FogSettings::DEFAULT_COLOR;
+                [[r, g, b, 1.0]; 4]
+            },
+        }
     }
 }
 
@@ -99,36 +185,224 @@ impl Scene3dBackground {
     }
 }
 
+/// Settings of a [`MyView3d`] that can be changed while it is displayed
+#[derive(Debug, Clone)]
+pub struct View3dSettings {
+    pub render_mode: RenderMode,
+    /// Draw the pipelines that opted into it (e.g. [`MeshPipeline::order_independent`](crate::pipelines::builder::MeshPipeline::order_independent))
+    /// with weighted blended order-independent transparency
+    pub order_independent_transparency: bool,
+    /// Shadows of the camera light, only if the scene has [`Scene3d::bounds`]
+    pub shadows: Option<ShadowSettings>,
+    /// Screen-space ambient occlusion of the opaque surfaces
+    pub ssao: Option<SsaoSettings>,
+    /// Full screen effects applied in order to the rendered image, see [`MyView3d::add_post_effect`]
+    pub post_effects: Vec<PostEffect>,
+    /// The outlined objects, see [`MyView3d::select`]
+    pub selection: Selection,
+    pub outline: OutlineSettings,
+    /// Draw the [`Scene3d::grid`] as an [`InfiniteGrid`] instead of a fixed size [`Grid`]
+    pub infinite_grid: bool,
+    /// Planes that cut the scene, the first [`MAX_CLIP_PLANES`] are used
+    pub clip_planes: Vec<ClipPlane>,
+    /// Fog that fades the distant surfaces into the [`Scene3d::background`]
+    pub fog: Option<FogSettings>,
+}
+
+impl Default for View3dSettings {
+    fn default() -> Self {
+        Self {
+            render_mode: RenderMode::default(),
+            order_independent_transparency: true,
+            shadows: Some(ShadowSettings::default()),
+            ssao: None,
+            post_effects: Vec::new(),
+            selection: Selection::default(),
+            outline: OutlineSettings::default(),
+            infinite_grid: false,
+            clip_planes: Vec::new(),
+            fog: None,
+        }
+    }
+}
+
+/// The depth-stencil attachment of a view
+struct DepthTarget {
+    view: wgpu::TextureView,
+    /// The view of the depth aspect, for the passes that sample it
+    depth_view: wgpu::TextureView,
+}
+
+impl DepthTarget {
+    fn new(
+        cx: &mut RenderContext,
+        format: wgpu::TextureFormat,
+        width: u32,
+        height: u32,
+    ) -> Self {
+        let texture = cx.device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Depth Texture"),
+            size: wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_SRC,
+            view_formats: &[],
+        });
+
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let depth_view = texture.create_view(&wgpu::TextureViewDescriptor {
+            aspect: wgpu::TextureAspect::DepthOnly,
+            ..Default::default()
+        });
+
+        Self {
+            view,
+            depth_view,
+        }
+    }
+}
+
 pub struct MyView3d {
     camera: Arc<Mutex<TrackballCamera>>,
-
-    depth_texture: Option<(u32, u32, Res<(wgpu::Texture, wgpu::TextureView)>)>,
+    settings: Arc<Mutex<View3dSettings>>,
+
+    depth_texture: Option<(u32, u32, Res<DepthTarget>)>,
+    oit_targets: Option<(u32, u32, Res<OitTargets>)>,
+    oit_composite: OitCompositePipeline,
+    shadow_map: Option<(u32, Res<ShadowMap>)>,
+    ssao_targets: Option<(u32, u32, Res<SsaoTargets>)>,
+    ssao: SsaoPipeline,
+    ssao_apply: SsaoApplyPipeline,
+    post_targets: Option<(u32, u32, Res<PostTargets>)>,
+    outline_targets: Option<(u32, u32, Res<OutlineTargets>)>,
+    outline_mask: OutlineMaskPipeline,
+    outline: OutlinePipeline,
+    section_caps: Vec<Res<Mutex<SectionCap>>>,
+    section_cap: SectionCapPipeline,
 
     camera_buffer: Res<Mutex<ProjectionCameraBuffer>>,
+    light_camera_buffer: Res<Mutex<ProjectionCameraBuffer>>,
     //triangle: Resource<stupid_triangle::Triangle>,
     trackball: Trackball,
 
     grid: Grid,
+    infinite_grid: InfiniteGrid,
     bg: Bg,
+    sky: Sky,
 
     scene: Mutex<Box<dyn Scene3d>>,
+    culled_draws: u32,
 }
 
 impl MyView3d {
-    pub const DEPTH_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth32Float;
+    /// With a stencil for the section faces of the clipping planes.
+    ///
+    /// The depth of this format can't be copied to a buffer, [`RenderContext::read_texture`]
+    /// copies it with a compute shader instead, so the depth texture is also bound.
+    pub const DEPTH_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth24PlusStencil8;
 
     pub fn new(scene: impl Scene3d, camera: Arc<Mutex<TrackballCamera>>) -> Self {
+        Self::with_settings(scene, camera, Default::default())
+    }
+
+    pub fn with_settings(
+        scene: impl Scene3d,
+        camera: Arc<Mutex<TrackballCamera>>,
+        settings: Arc<Mutex<View3dSettings>>,
+    ) -> Self {
         Self {
             camera,
+            settings,
             depth_texture: None,
+            oit_targets: None,
+            oit_composite: OitCompositePipeline::new(),
+            shadow_map: None,
+            ssao_targets: None,
+            ssao: SsaoPipeline::new(),
+            ssao_apply: SsaoApplyPipeline::new(),
+            post_targets: None,
+            outline_targets: None,
+            outline_mask: OutlineMaskPipeline::new(),
+            outline: OutlinePipeline::new(),
+            section_caps: Vec::new(),
+            section_cap: SectionCapPipeline::new(),
             camera_buffer: Res::new(|cx: &mut RenderContext| Mutex::new(ProjectionCameraBuffer::new(cx))),
+            light_camera_buffer: Res::new(|cx: &mut RenderContext| Mutex::new(ProjectionCameraBuffer::new(cx))),
             //triangle: Resource::new(move |cx: &mut wiew::RenderContext| stupid_triangle::Triangle::new(cx, &[presentation_target_format])),
             trackball: Trackball::new(),
             bg: Bg::new(),
+            sky: Sky::new(),
             grid: Grid::new(10),
+            infinite_grid: InfiniteGrid::new(),
             scene: Mutex::new(Box::new(scene)),
+            culled_draws: 0,
+        }
+    }
+
+    /// The settings of the view, changes are applied on the next frame
+    pub fn settings(&self) -> &Arc<Mutex<View3dSettings>> {
+        &self.settings
+    }
+
+    /// Append an effect to the post-processing chain
+    pub fn add_post_effect(&self, effect: PostEffect) {
+        self.settings.lock().unwrap().post_effects.push(effect);
+    }
+
+    /// Insert an effect in the post-processing chain, before the effect at `index`
+    pub fn insert_post_effect(&self, index: usize, effect: PostEffect) {
+        self.settings.lock().unwrap().post_effects.insert(index, effect);
+    }
+
+    /// Remove the first effect named `name` from the post-processing chain
+    pub fn remove_post_effect(&self, name: &str) -> Option<PostEffect> {
+        let mut settings = self.settings.lock().unwrap();
+        let index = settings.post_effects.iter().position(|effect| effect.name == name)?;
+        Some(settings.post_effects.remove(index))
+    }
+
+    /// Remove all the effects of the post-processing chain
+    pub fn clear_post_effects(&self) {
+        self.settings.lock().unwrap().post_effects.clear();
+    }
+
+    /// Outline the object with the given [`Instance3d::object_id`], in addition to the selected ones
+    pub fn select(&self, object_id: u32) {
+        let mut settings = self.settings.lock().unwrap();
+        if !settings.selection.selected.contains(&object_id) {
+            settings.selection.selected.push(object_id);
         }
     }
+
+    pub fn deselect(&self, object_id: u32) {
+        self.settings.lock().unwrap().selection.selected.retain(|id| *id != object_id);
+    }
+
+    /// Replace the selected objects
+    pub fn set_selection(&self, object_ids: impl IntoIterator<Item = u32>) {
+        self.settings.lock().unwrap().selection.selected = object_ids.into_iter().collect();
+    }
+
+    pub fn clear_selection(&self) {
+        self.settings.lock().unwrap().selection.selected.clear();
+    }
+
+    /// Outline the hovered object, with [`OutlineSettings::hover_color`]
+    pub fn set_hovered(&self, object_id: Option<u32>) {
+        self.settings.lock().unwrap().selection.hovered = object_id;
+    }
+
+    /// The number of objects skipped in the last frame because they were outside
+    /// of the view frustum, see [`Pass::is_culled`]
+    pub fn culled_draws(&self) -> u32 {
+        self.culled_draws
+    }
 }
 
 impl View for MyView3d {
@@ -137,29 +411,12 @@ impl View for MyView3d {
         cx: &mut RenderContext,
     ) -> Vec<wgpu::CommandBuffer> {
         let camera = self.camera.lock().unwrap();
+        let settings = self.settings.lock().unwrap().clone();
 
         // create depth texture if it doesn't exist or if the size has changed
         if self.depth_texture.is_none() || self.depth_texture.as_ref().unwrap().0 != cx.w || self.depth_texture.as_ref().unwrap().1 != cx.h {
-            let depth_texture = Res::new(|cx: &mut RenderContext| {
-                let texture = cx.device.create_texture(&wgpu::TextureDescriptor {
-                    label: Some("Depth Texture"),
-                    size: wgpu::Extent3d {
-                        width: cx.w,
-                        height: cx.h,
-                        depth_or_array_layers: 1,
-                    },
-                    mip_level_count: 1,
-                    sample_count: 1,
-                    dimension: wgpu::TextureDimension::D2,
-                    format: Self::DEPTH_FORMAT,
-                    usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
-                    view_formats: &[],
-                });
-
-                let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
-
-                (texture, view)
-            });
+            let (w, h) = (cx.w, cx.h);
+            let depth_texture = Res::new(move |cx: &mut RenderContext| DepthTarget::new(cx, Self::DEPTH_FORMAT, w, h));
 
             self.depth_texture = Some((cx.w, cx.h, depth_texture));
         }
@@ -167,23 +424,141 @@ impl View for MyView3d {
         let depth_texture = &self.depth_texture.as_ref().unwrap().2;
         let depth_texture = cx.resource(&depth_texture);
 
+        // same for the targets of the transparent pass
+        let oit_targets = if settings.order_independent_transparency {
+            if !matches!(&self.oit_targets, Some((w, h, _)) if *w == cx.w && *h == cx.h) {
+                let (w, h) = (cx.w, cx.h);
+                self.oit_targets = Some((w, h, Res::new(move |cx: &mut RenderContext| OitTargets::new(cx, w, h))));
+            }
+
+            Some(cx.resource(&self.oit_targets.as_ref().unwrap().2))
+        } else {
+            None
+        };
+
+        // and for the occlusion, which reads the depth texture
+        let ssao = settings.ssao.map(|ssao| {
+            if !matches!(&self.ssao_targets, Some((w, h, _)) if *w == cx.w && *h == cx.h) {
+                let (w, h) = (cx.w, cx.h);
+                let depth_texture = depth_texture.clone();
+                self.ssao_targets = Some((w, h, Res::new(move |cx: &mut RenderContext| SsaoTargets::new(cx, &depth_texture.depth_view, w, h))));
+            }
+
+            let ssao_targets = cx.resource(&self.ssao_targets.as_ref().unwrap().2);
+            ssao_targets.update(cx.queue, &ssao);
+            ssao_targets
+        });
+
+        // with post-processing, the scene is rendered into the first texture of the chain
+        let post_targets = if settings.post_effects.is_empty() {
+            None
+        } else {
+            if !matches!(&self.post_targets, Some((w, h, _)) if *w == cx.w && *h == cx.h) {
+                let (w, h) = (cx.w, cx.h);
+                self.post_targets = Some((w, h, Res::new(move |cx: &mut RenderContext| PostTargets::new(cx, w, h))));
+            }
+
+            let post_targets = cx.resource(&self.post_targets.as_ref().unwrap().2);
+            post_targets.update(cx.queue, &settings.post_effects);
+            Some(post_targets)
+        };
+        let (scene_target, scene_format) = match &post_targets {
+            Some(post_targets) => (post_targets.texture(0), POST_FORMAT),
+            None => (cx.target, *cx.target_format),
+        };
+
         let cam = cx.resource(&self.camera_buffer);
         let mut cam = cam.lock().unwrap();
-        cam.prepare(cx.queue, camera.deref(), cx.w as f32 / cx.h as f32);
+
+        let mut scene = self.scene.lock().unwrap();
+
+        let pass_camera = PassCamera::from_camera(camera.deref(), cx.w as f32 / cx.h as f32);
+        let background = scene.background();
+        self.culled_draws = 0;
+
+        // the shadow map is rendered first, the main pass samples it
+        let shadow_map = match settings.shadows.zip(scene.bounds()) {
+            Some((shadows, bounds)) => {
+                let resolution = shadows.resolution;
+                if !matches!(&self.shadow_map, Some((r, _)) if *r == resolution) {
+                    self.shadow_map = Some((resolution, Res::new(move |cx: &mut RenderContext| ShadowMap::new(cx, resolution))));
+                }
+                let shadow_map = cx.resource(&self.shadow_map.as_ref().unwrap().1);
+
+                let light = LightCamera::fit(camera.light_dir(), &bounds);
+                cam.uniform.set_shadow(&light, &shadows);
+
+                let light_cam = cx.resource(&self.light_camera_buffer);
+                let mut light_cam = light_cam.lock().unwrap();
+                // the parts that are cut away cast no shadows
+                light_cam.uniform.set_clip_planes(&settings.clip_planes);
+                light_cam.prepare_light(cx.queue, &light, resolution);
+
+                let shadow_info = SurfaceInfo {
+                    width: resolution,
+                    height: resolution,
+                    format: *cx.target_format,
+                    depth_format: Some(SHADOW_MAP_FORMAT),
+                    render_mode: RenderMode::Shaded,
+                    order_independent_transparency: false,
+                    kind: PassKind::Shadow,
+                };
+
+                let mut shadow_pass = Pass::new(shadow_info, &light_cam.bind_group, |encoder| encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                    label: Some("Shadow Pass"),
+                    color_attachments: &[],
+                    depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                        view: shadow_map.view(),
+                        depth_ops: Some(wgpu::Operations {
+                            load: wgpu::LoadOp::Clear(1.0),
+                            store: wgpu::StoreOp::Store,
+                        }),
+                        stencil_ops: None,
+                    }),
+                    timestamp_writes: None,
+                    occlusion_query_set: None,
+                }))
+                    // the levels of detail are the ones of the view
+                    .with_camera(pass_camera)
+                    .with_frustum(Frustum::new(light.view, light.proj));
+
+                scene.raster_shadow(cx, &mut shadow_pass);
+
+                self.culled_draws += shadow_pass.culled_draws();
+                shadow_pass.exec(cx.encoder);
+
+                Some(shadow_map)
+            },
+            None => {
+                cam.uniform.disable_shadow();
+                None
+            },
+        };
+
+        cam.set_shadow_map(cx, shadow_map.as_deref());
+        cam.uniform.set_clip_planes(&settings.clip_planes);
+        match &settings.fog {
+            Some(fog) => cam.uniform.set_fog(fog, background.fog_colors()),
+            None => cam.uniform.disable_fog(),
+        }
+        cam.prepare_viewport(cx.queue, camera.deref(), cx.w, cx.h);
 
         let surface_info = SurfaceInfo {
             width: cx.w,
             height: cx.h,
-            format: cx.target_format.clone(),
+            format: scene_format,
             depth_format: Some(Self::DEPTH_FORMAT),
+            render_mode: settings.render_mode,
+            order_independent_transparency: oit_targets.is_some(),
+            kind: PassKind::Main,
         };
 
-        let mut pass = Pass::new(surface_info, &cam.bind_group, |encoder| encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+        let mut pass = Pass::new(surface_info.clone(), &cam.bind_group, |encoder| encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
             label: Some("My Render Pass"),
             color_attachments: &[
                 // This is what @location(0) in the fragment shader targets
                 Some(wgpu::RenderPassColorAttachment {
-                    view: cx.target,
+                    view: scene_target,
                     resolve_target: None,
                     ops: wgpu::Operations {
                         load: wgpu::LoadOp::Clear(wgpu::Color {
@@ -197,7 +572,7 @@ impl View for MyView3d {
                 }),
             ],
             depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
-                view: &depth_texture.1,
+                view: &depth_texture.view,
                 depth_ops: Some(wgpu::Operations {
                     load: wgpu::LoadOp::Clear(1.0),
                     store: wgpu::StoreOp::Store,
@@ -206,7 +581,7 @@ impl View for MyView3d {
             }),
             timestamp_writes: None,
             occlusion_query_set: None,
-        }));
+        })).with_camera(pass_camera);
 
         //let tri = ctx.resource(&self.triangle);
         //tri.prepare(ctx.device, ctx.queue, self.angle);
@@ -214,9 +589,14 @@ impl View for MyView3d {
         //    t.render(rp);
         //});
 
-        let mut scene = self.scene.lock().unwrap();
-
-        self.bg.render(cx, &mut pass, scene.background_color());
+        match background {
+            Background::Gradient(background) => self.bg.render(cx, &mut pass, background),
+            Background::Sky(sky) => self.sky.render(cx, &mut pass, &sky, None),
+            Background::Environment { map, intensity } => {
+                let map = cx.resource(&map);
+                self.sky.render(cx, &mut pass, &SkyGradient::default(), Some((&map, intensity)));
+            },
+        }
 
         camera.render(cx, &mut pass, &self.trackball);
 
@@ -224,14 +604,263 @@ impl View for MyView3d {
         //    rp.set_pipeline(todo!());
         //});
 
-        if scene.grid() {
-            self.grid.render(cx, &mut pass);
+        if let Some(grid) = scene.grid() {
+            if settings.infinite_grid {
+                self.infinite_grid.set_settings(grid);
+                self.infinite_grid.render(cx, &mut pass);
+            } else {
+                self.grid.set_settings(grid);
+                self.grid.render(cx, &mut pass);
+            }
         }
 
         scene.raster(cx, &mut pass);
 
+        let transparent_pass = oit_targets.as_ref().and_then(|oit_targets| {
+            pass.split_transparent(&cam.bind_group, |encoder| encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Transparent Pass"),
+                color_attachments: &oit_targets.color_attachments(),
+                // tested against the opaque surfaces, but not written
+                depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                    view: &depth_texture.view,
+                    depth_ops: Some(wgpu::Operations {
+                        load: wgpu::LoadOp::Load,
+                        store: wgpu::StoreOp::Store,
+                    }),
+                    stencil_ops: None,
+                }),
+                timestamp_writes: None,
+                occlusion_query_set: None,
+            }))
+        });
+
+        self.culled_draws += pass.culled_draws();
         pass.exec(cx.encoder);
 
+        // the section faces are opaque, before the occlusion and the transparent surfaces
+        let planes = &settings.clip_planes[..settings.clip_planes.len().min(MAX_CLIP_PLANES)];
+        let capped = planes.iter().enumerate().filter(|(_, plane)| plane.cap.is_some()).map(|(index, _)| index).collect::<Vec<_>>();
+        while self.section_caps.len() < capped.len() {
+            self.section_caps.push(Res::new(|cx: &mut RenderContext| Mutex::new(SectionCap::new(cx))));
+        }
+
+        for (index, section_cap) in capped.into_iter().zip(&self.section_caps) {
+            let section_cap = cx.resource(section_cap);
+            let mut section_cap = section_cap.lock().unwrap();
+            section_cap.update(cx.queue, &cam.uniform, planes, index);
+
+            let section_info = SurfaceInfo {
+                render_mode: RenderMode::Shaded,
+                order_independent_transparency: false,
+                kind: PassKind::Section,
+                ..surface_info.clone()
+            };
+
+            let mut section_pass = Pass::new(section_info, section_cap.globals(), |encoder| encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Section Pass"),
+                color_attachments: &[
+                    Some(wgpu::RenderPassColorAttachment {
+                        view: scene_target,
+                        resolve_target: None,
+                        ops: wgpu::Operations {
+                            load: wgpu::LoadOp::Load,
+                            store: wgpu::StoreOp::Store,
+                        },
+                    }),
+                ],
+                depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                    view: &depth_texture.view,
+                    depth_ops: Some(wgpu::Operations {
+                        load: wgpu::LoadOp::Load,
+                        store: wgpu::StoreOp::Store,
+                    }),
+                    stencil_ops: Some(wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(0),
+                        store: wgpu::StoreOp::Discard,
+                    }),
+                }),
+                timestamp_writes: None,
+                occlusion_query_set: None,
+            })).with_camera(pass_camera);
+
+            scene.raster_sections(cx, &mut section_pass);
+            self.section_cap.render(cx, &mut section_pass, &section_cap);
+
+            self.culled_draws += section_pass.culled_draws();
+            section_pass.exec(cx.encoder);
+        }
+
+        // the occlusion only darkens the opaque surfaces, before the transparent ones are composited
+        if let Some(ssao_targets) = &ssao {
+            let occlusion_info = SurfaceInfo {
+                format: OCCLUSION_FORMAT,
+                depth_format: None,
+                ..surface_info.clone()
+            };
+
+            let mut occlusion_pass = Pass::new(occlusion_info, &cam.bind_group, |encoder| encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Occlusion Pass"),
+                color_attachments: &[Some(ssao_targets.color_attachment())],
+                depth_stencil_attachment: None,
+                timestamp_writes: None,
+                occlusion_query_set: None,
+            }));
+
+            self.ssao.render(cx, &mut occlusion_pass, ssao_targets);
+
+            occlusion_pass.exec(cx.encoder);
+
+            let mut apply_pass = Pass::new(SurfaceInfo { depth_format: None, ..surface_info.clone() }, &cam.bind_group, |encoder| encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Occlusion Apply Pass"),
+                color_attachments: &[
+                    Some(wgpu::RenderPassColorAttachment {
+                        view: scene_target,
+                        resolve_target: None,
+                        ops: wgpu::Operations {
+                            load: wgpu::LoadOp::Load,
+                            store: wgpu::StoreOp::Store,
+                        },
+                    }),
+                ],
+                depth_stencil_attachment: None,
+                timestamp_writes: None,
+                occlusion_query_set: None,
+            }));
+
+            self.ssao_apply.render(cx, &mut apply_pass, ssao_targets);
+
+            apply_pass.exec(cx.encoder);
+        }
+
+        // consumed on every path, a pass borrows the encoder until it is dropped
+        let has_transparent = transparent_pass.map(|transparent_pass| transparent_pass.exec(cx.encoder)).is_some();
+
+        if let Some(oit_targets) = oit_targets.as_ref().filter(|_| has_transparent) {
+            let mut composite_pass = Pass::new(SurfaceInfo { depth_format: None, ..surface_info.clone() }, &cam.bind_group, |encoder| encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Transparent Composite Pass"),
+                color_attachments: &[
+                    Some(wgpu::RenderPassColorAttachment {
+                        view: scene_target,
+                        resolve_target: None,
+                        ops: wgpu::Operations {
+                            load: wgpu::LoadOp::Load,
+                            store: wgpu::StoreOp::Store,
+                        },
+                    }),
+                ],
+                depth_stencil_attachment: None,
+                timestamp_writes: None,
+                occlusion_query_set: None,
+            }));
+
+            self.oit_composite.render(cx, &mut composite_pass, oit_targets);
+
+            composite_pass.exec(cx.encoder);
+        }
+
+        // the outline is drawn over everything, but before the post-processing
+        if !settings.selection.is_empty() {
+            if !matches!(&self.outline_targets, Some((w, h, _)) if *w == cx.w && *h == cx.h) {
+                let (w, h) = (cx.w, cx.h);
+                self.outline_targets = Some((w, h, Res::new(move |cx: &mut RenderContext| OutlineTargets::new(cx, Self::DEPTH_FORMAT, w, h))));
+            }
+
+            let outline_targets = cx.resource(&self.outline_targets.as_ref().unwrap().2);
+            outline_targets.update(cx.queue, &settings.selection, &settings.outline);
+
+            let ids_info = SurfaceInfo {
+                format: ObjectIdPipeline::FORMAT,
+                render_mode: RenderMode::Shaded,
+                order_independent_transparency: false,
+                ..surface_info.clone()
+            };
+
+            let mut ids_pass = Pass::new(ids_info, &cam.bind_group, |encoder| encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Object Id Pass"),
+                color_attachments: &[Some(outline_targets.ids_attachment())],
+                depth_stencil_attachment: Some(outline_targets.depth_attachment()),
+                timestamp_writes: None,
+                occlusion_query_set: None,
+            })).with_camera(pass_camera);
+
+            scene.raster_object_ids(cx, &mut ids_pass);
+
+            self.culled_draws += ids_pass.culled_draws();
+            ids_pass.exec(cx.encoder);
+
+            let mask_info = SurfaceInfo {
+                format: MASK_FORMAT,
+                depth_format: None,
+                ..surface_info.clone()
+            };
+
+            let mut mask_pass = Pass::new(mask_info, &cam.bind_group, |encoder| encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Outline Mask Pass"),
+                color_attachments: &[Some(outline_targets.mask_attachment())],
+                depth_stencil_attachment: None,
+                timestamp_writes: None,
+                occlusion_query_set: None,
+            }));
+
+            self.outline_mask.render(cx, &mut mask_pass, &outline_targets);
+
+            mask_pass.exec(cx.encoder);
+
+            let mut outline_pass = Pass::new(SurfaceInfo { depth_format: None, ..surface_info.clone() }, &cam.bind_group, |encoder| encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Outline Pass"),
+                color_attachments: &[
+                    Some(wgpu::RenderPassColorAttachment {
+                        view: scene_target,
+                        resolve_target: None,
+                        ops: wgpu::Operations {
+                            load: wgpu::LoadOp::Load,
+                            store: wgpu::StoreOp::Store,
+                        },
+                    }),
+                ],
+                depth_stencil_attachment: None,
+                timestamp_writes: None,
+                occlusion_query_set: None,
+            }));
+
+            self.outline.render(cx, &mut outline_pass, &outline_targets);
+
+            outline_pass.exec(cx.encoder);
+        }
+
+        if let Some(post_targets) = &post_targets {
+            let effects = &settings.post_effects[..settings.post_effects.len().min(MAX_POST_EFFECTS)];
+            for (index, effect) in effects.iter().enumerate() {
+                // the last effect writes the target, the others the next texture of the chain
+                let (output, format) = if index + 1 == effects.len() {
+                    (cx.target, *cx.target_format)
+                } else {
+                    (post_targets.texture(index + 1), POST_FORMAT)
+                };
+
+                let mut post_pass = Pass::new(SurfaceInfo { format, depth_format: None, ..surface_info.clone() }, &cam.bind_group, |encoder| encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                    label: Some("Post Process Pass"),
+                    color_attachments: &[
+                        Some(wgpu::RenderPassColorAttachment {
+                            view: output,
+                            resolve_target: None,
+                            ops: wgpu::Operations {
+                                load: wgpu::LoadOp::Load,
+                                store: wgpu::StoreOp::Store,
+                            },
+                        }),
+                    ],
+                    depth_stencil_attachment: None,
+                    timestamp_writes: None,
+                    occlusion_query_set: None,
+                }));
+
+                effect.render(cx, &mut post_pass, post_targets, index);
+
+                post_pass.exec(cx.encoder);
+            }
+        }
+
         Vec::new()
     }
 }
@@ -308,14 +937,80 @@ impl Bg {
 
 
 
+/// The sky and environment backgrounds
+struct Sky {
+    material: Res<SkyboxMaterial>,
+    pipeline: SkyboxPipeline,
+}
+
+impl Sky {
+    fn new() -> Self {
+        Self {
+            material: Res::new(|cx: &mut RenderContext| SkyboxMaterial::new(cx, &SkyGradient::default())),
+            pipeline: SkyboxPipeline::new(),
+        }
+    }
+
+    fn render(
+        &self,
+        cx: &mut RenderContext,
+        pass: &mut Pass,
+        sky: &SkyGradient,
+        environment: Option<(&EnvironmentMap, f32)>,
+    ) {
+        let material = cx.resource(&self.material);
+        let intensity = environment.map_or(1.0, |(_, intensity)| intensity);
+        material.update(cx.queue, sky, environment.is_some(), intensity);
+
+        self.pipeline.render(cx, pass, &material, environment.map(|(map, _)| map));
+    }
+}
+
 pub struct Grid {
+    settings: GridSettings,
+    bounds: Aabb,
     resources: Res<GridResources>,
 }
 
 impl Grid {
+    /// A grid of `2 * n` units drawn with hairlines
     pub fn new(n: u16) -> Self {
+        Self::with_settings(GridSettings {
+            extent: n,
+            ..Default::default()
+        })
+    }
+
+    /// A grid of `2 * n` units drawn with thick lines, see [`LinePipeline`](crate::pipelines::line::LinePipeline)
+    pub fn with_line_style(n: u16, style: LineStyle) -> Self {
+        Self::with_settings(GridSettings {
+            extent: n,
+            line_style: Some(style),
+            ..Default::default()
+        })
+    }
+
+    pub fn with_settings(settings: GridSettings) -> Self {
+        let n = settings.extent as f32;
+        let model: [[f32; 4]; 4] = settings.model_matrix().into();
+        let bounds = Aabb::new(Point3::new(-n, 0.0, -n), Point3::new(n, 0.0, n))
+            .transformed(&Matrix4::from(model));
+
         Self {
-            resources: Res::new(move |cx: &mut RenderContext| GridResources::new(cx, n)),
+            settings,
+            bounds,
+            resources: Res::new(move |cx: &mut RenderContext| GridResources::new(cx, &settings)),
+        }
+    }
+
+    pub fn settings(&self) -> &GridSettings {
+        &self.settings
+    }
+
+    /// Change the settings, the grid is only rebuilt if they are different
+    pub fn set_settings(&mut self, settings: GridSettings) {
+        if settings != self.settings {
+            *self = Self::with_settings(settings);
         }
     }
 }
@@ -326,34 +1021,89 @@ impl Render for Grid {
         cx: &mut RenderContext,
         pass: &mut Pass,
     ) {
+        if pass.is_culled(&self.bounds) {
+            return;
+        }
+
         let res = cx.resource(&self.resources);
 
-        res.flat_pipeline.render(
-            cx,
-            pass,
-            res.vertex_buffer.slice(..),
-            res.instance_buffer.slice(..),
-        );
+        res.lines.render(cx, pass, &res.instance_buffer);
+    }
+}
+
+/// A grid covering a whole plane, with a spacing that adapts to the distance
+/// of the camera, see [`InfiniteGridPipeline`]
+pub struct InfiniteGrid {
+    settings: GridSettings,
+    material: Res<InfiniteGridMaterial>,
+    pipeline: InfiniteGridPipeline,
+}
+
+impl Default for InfiniteGrid {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl InfiniteGrid {
+    pub fn new() -> Self {
+        Self::with_settings(GridSettings::default())
+    }
+
+    /// [`GridSettings::extent`] and [`GridSettings::line_style`] are ignored
+    pub fn with_settings(settings: GridSettings) -> Self {
+        Self {
+            settings,
+            material: Res::new(move |cx: &mut RenderContext| InfiniteGridMaterial::new(cx, &settings)),
+            pipeline: InfiniteGridPipeline::new(),
+        }
+    }
+
+    pub fn settings(&self) -> &GridSettings {
+        &self.settings
+    }
+
+    pub fn set_settings(&mut self, settings: GridSettings) {
+        self.settings = settings;
+    }
+}
+
+impl Render for InfiniteGrid {
+    fn render(
+        &self,
+        cx: &mut RenderContext,
+        pass: &mut Pass,
+    ) {
+        let material = cx.resource(&self.material);
+        material.update(cx.queue, &self.settings);
+
+        self.pipeline.render(cx, pass, &material);
     }
 }
 
 struct GridResources {
-    vertex_buffer: VertexBuffer<flat::Vertex>,
+    lines: Lines,
     instance_buffer: VertexBuffer<Instance3d>,
-    flat_pipeline: FlatPipeline,
 }
 
 impl GridResources {
     pub fn new(
         cx: &mut RenderContext,
-        n: u16,
+        settings: &GridSettings,
     ) -> Self {
         use flat::Vertex;
         let mut vertices: Vec<Vertex> = Vec::new();
 
-        const N_DIV: isize = 5;
+        let n = settings.extent;
+        let n_div = settings.minor_divisions.max(1);
+
+        let a = settings.line_alpha;
+        let [r, g, b] = settings.major_color;
+        let major_color = [r, g, b, a];
+        let [r, g, b] = settings.minor_color;
+        let minor_color = [r, g, b, a];
 
-        const A: f32 = 0.25;
+        // in grid space the first axis is x and the second one is z, see `GridSettings::model_matrix`
 
         for i in -(n as i32)..=(n as i32) {
             let major = i as f32;
@@ -361,36 +1111,36 @@ impl GridResources {
 
             vertices.push(Vertex {
                 position: [-l, 0.0, major],
-                color: [0.5, 0.5, 0.5, A],
+                color: major_color,
             });
             vertices.push(Vertex {
                 position: [if i != 0 { l } else { 0.0 }, 0.0, major],
-                color: [0.5, 0.5, 0.5, A],
+                color: major_color,
             });
             vertices.push(Vertex {
                 position: [major, 0.0, -l],
-                color: [0.5, 0.5, 0.5, A],
+                color: major_color,
             });
             vertices.push(Vertex {
                 position: [major, 0.0, if i != 0 { l } else { 0.0 }],
-                color: [0.5, 0.5, 0.5, A],
+                color: major_color,
             });
             if i == 0 {
                 vertices.push(Vertex {
                     position: [0.0, 0.0, major],
-                    color: [1.0, 0.25, 0.25, 1.0],
+                    color: settings.axis_colors[0],
                 });
                 vertices.push(Vertex {
                     position: [l, 0.0, major],
-                    color: [1.0, 0.25, 0.25, 1.0],
+                    color: settings.axis_colors[0],
                 });
                 vertices.push(Vertex {
                     position: [major, 0.0, 0.0],
-                    color: [0.25, 0.25, 1.0, 1.0],
+                    color: settings.axis_colors[1],
                 });
                 vertices.push(Vertex {
                     position: [major, 0.0, l],
-                    color: [0.25, 0.25, 1.0, 1.0],
+                    color: settings.axis_colors[1],
                 });
             }
     
@@ -398,47 +1148,44 @@ impl GridResources {
                 break;
             }
     
-            for minor in 1..N_DIV {
-                let t = major + minor as f32 / N_DIV as f32;
+            for minor in 1..n_div {
+                let t = major + minor as f32 / n_div as f32;
                 vertices.push(Vertex {
                     position: [-l, 0.0, t],
-                    color: [0.25, 0.25, 0.25, A],
+                    color: minor_color,
                 });
                 vertices.push(Vertex {
                     position: [l, 0.0, t],
-                    color: [0.25, 0.25, 0.25, A],
+                    color: minor_color,
                 });
                 vertices.push(Vertex {
                     position: [t, 0.0, -l],
-                    color: [0.25, 0.25, 0.25, A],
+                    color: minor_color,
                 });
                 vertices.push(Vertex {
                     position: [t, 0.0, l],
-                    color: [0.25, 0.25, 0.25, A],
+                    color: minor_color,
                 });
             }
         }
 
-        let vertex_buffer = VertexBuffer::from_slice(
-            cx.device,
+        let lines = Lines::new(
+            cx,
             &vertices,
-            None,
+            settings.line_style.as_ref(),
+            wgpu::CompareFunction::Less,
+            true,
         );
 
         let instance_buffer = VertexBuffer::single(
             cx.device,
-            Instance3d::from_placement(&Placement3::default()),
+            Instance3d::from_matrix(settings.model_matrix()),
             None,
         );
 
         Self {
-            vertex_buffer,
+            lines,
             instance_buffer,
-            flat_pipeline: FlatPipeline::new(
-                wgpu::PrimitiveTopology::LineList,
-                wgpu::CompareFunction::Less,
-                true,
-            ),
         }
     }
 }
\ No newline at end of file
@@ -0,0 +1,460 @@
+use std::{future::Future, ops::Range, pin::Pin, sync::{Arc, Mutex}, task::{Context, Poll, Waker}};
+
+use wgpu::util::DeviceExt;
+
+use crate::{RenderContext, SingletonResource};
+
+/// Data that is being copied back from the GPU.
+///
+/// A readback is created by [`RenderContext::read_buffer`] or [`RenderContext::read_texture`]:
+/// the copy is recorded in the frame encoder and the staging buffer is mapped
+/// once the frame has been submitted (see [`ResourceRegistry::map_readbacks`]).
+///
+/// The data can be obtained by awaiting the readback, by polling it with
+/// [`Readback::try_take`] or by registering a callback with [`Readback::then`].
+///
+/// # Remarks
+/// As for any [`wgpu`] mapping, the result is only delivered when the device
+/// is polled (for example by `queue.submit` or `device.poll`).
+///
+/// [`ResourceRegistry::map_readbacks`]: crate::ResourceRegistry::map_readbacks
+pub struct Readback {
+    state: Arc<Mutex<ReadbackState>>,
+}
+
+/// The result of a [`Readback`]
+pub type ReadbackResult = Result<Vec<u8>, wgpu::BufferAsyncError>;
+
+type ReadbackCallback = Box<dyn FnOnce(ReadbackResult) + Send>;
+
+enum ReadbackState {
+    Pending {
+        waker: Option<Waker>,
+        callback: Option<ReadbackCallback>,
+    },
+    Ready(ReadbackResult),
+    Taken,
+}
+
+impl ReadbackState {
+    fn complete(&mut self, result: ReadbackResult) {
+        let old = std::mem::replace(self, ReadbackState::Taken);
+        match old {
+            ReadbackState::Pending { waker, callback } => {
+                match callback {
+                    Some(callback) => callback(result),
+                    None => *self = ReadbackState::Ready(result),
+                }
+                if let Some(waker) = waker {
+                    waker.wake();
+                }
+            },
+            other => {
+                log::error!("Readback completed twice");
+                *self = other;
+            },
+        }
+    }
+}
+
+impl Readback {
+    fn new() -> Self {
+        Self {
+            state: Arc::new(Mutex::new(ReadbackState::Pending {
+                waker: None,
+                callback: None,
+            })),
+        }
+    }
+
+    /// Whether the data is available
+    pub fn is_ready(&self) -> bool {
+        matches!(*self.state.lock().unwrap(), ReadbackState::Ready(_))
+    }
+
+    /// Take the data if it is available.
+    ///
+    /// Returns `None` if the data is not available yet or if it was already taken.
+    pub fn try_take(&self) -> Option<ReadbackResult> {
+        let mut state = self.state.lock().unwrap();
+        match &*state {
+            ReadbackState::Ready(_) => match std::mem::replace(&mut *state, ReadbackState::Taken) {
+                ReadbackState::Ready(result) => Some(result),
+                _ => unreachable!(),
+            },
+            _ => None,
+        }
+    }
+
+    /// Same as [`Readback::try_take`], but reinterprets the bytes as `T` values
+    pub fn try_take_as<T: bytemuck::Pod>(&self) -> Option<Result<Vec<T>, wgpu::BufferAsyncError>> {
+        self.try_take().map(|r| r.map(|data| bytemuck::pod_collect_to_vec(&data)))
+    }
+
+    /// Call `callback` with the data once it is available.
+    ///
+    /// If the data is already available, the callback is called immediately.
+    pub fn then(self, callback: impl FnOnce(ReadbackResult) + Send + 'static) {
+        let mut state = self.state.lock().unwrap();
+        match &mut *state {
+            ReadbackState::Pending { callback: c, .. } => {
+                *c = Some(Box::new(callback));
+            },
+            ReadbackState::Ready(_) => match std::mem::replace(&mut *state, ReadbackState::Taken) {
+                ReadbackState::Ready(result) => {
+                    drop(state);
+                    callback(result);
+                },
+                _ => unreachable!(),
+            },
+            ReadbackState::Taken => log::error!("Readback data was already taken"),
+        }
+    }
+}
+
+impl Future for Readback {
+    type Output = ReadbackResult;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut state = self.state.lock().unwrap();
+        match &mut *state {
+            ReadbackState::Pending { waker, .. } => {
+                *waker = Some(cx.waker().clone());
+                Poll::Pending
+            },
+            ReadbackState::Ready(_) => match std::mem::replace(&mut *state, ReadbackState::Taken) {
+                ReadbackState::Ready(result) => Poll::Ready(result),
+                _ => unreachable!(),
+            },
+            ReadbackState::Taken => panic!("Readback polled after completion"),
+        }
+    }
+}
+
+/// How the data is laid out inside a staging buffer
+#[derive(Debug, Clone, Copy)]
+enum StagingLayout {
+    /// The first `n` bytes
+    Bytes(u64),
+    /// Texture rows, padded to [`wgpu::COPY_BYTES_PER_ROW_ALIGNMENT`]
+    Rows {
+        unpadded_bytes_per_row: u32,
+        padded_bytes_per_row: u32,
+        rows: u32,
+    },
+}
+
+/// A readback whose copy has been recorded but whose staging buffer is not mapped yet
+pub(crate) struct PendingReadback {
+    staging: Arc<wgpu::Buffer>,
+    layout: StagingLayout,
+    state: Arc<Mutex<ReadbackState>>,
+}
+
+impl PendingReadback {
+    /// Start mapping the staging buffer, the commands that fill it must have been submitted
+    pub(crate) fn map(self) {
+        let Self { staging, layout, state } = self;
+
+        staging.clone().slice(..).map_async(wgpu::MapMode::Read, move |result| {
+            let result = result.map(|()| {
+                let data = layout.extract(&staging.slice(..).get_mapped_range());
+                staging.unmap();
+                data
+            });
+
+            state.lock().unwrap().complete(result);
+        });
+    }
+}
+
+impl StagingLayout {
+    fn extract(&self, data: &[u8]) -> Vec<u8> {
+        match *self {
+            StagingLayout::Bytes(n) => data[..n as usize].to_vec(),
+            StagingLayout::Rows { unpadded_bytes_per_row, padded_bytes_per_row, rows } => {
+                let mut out = Vec::with_capacity((unpadded_bytes_per_row * rows) as usize);
+                for row in data.chunks(padded_bytes_per_row as usize).take(rows as usize) {
+                    out.extend_from_slice(&row[..unpadded_bytes_per_row as usize]);
+                }
+                out
+            },
+        }
+    }
+}
+
+impl<'a> RenderContext<'a> {
+    /// Copy a region of `buffer` back to the CPU.
+    ///
+    /// The copy is recorded in [`RenderContext::encoder`], so it will contain the
+    /// results of the commands recorded before this call. Note that a [`Pass`](crate::Pass)
+    /// is only recorded when it is [`exec`](crate::Pass::exec)-ed.
+    ///
+    /// # Remarks
+    /// `buffer` must have the [`COPY_SRC`](wgpu::BufferUsages::COPY_SRC) usage and
+    /// `range.start` must be a multiple of [`wgpu::COPY_BUFFER_ALIGNMENT`]. The copied
+    /// size is rounded up to it, without going past the end of the buffer: the last
+    /// bytes of a buffer whose size is not aligned can't be copied and are left out.
+    pub fn read_buffer(
+        &mut self,
+        buffer: &wgpu::Buffer,
+        range: Range<wgpu::BufferAddress>,
+    ) -> Readback {
+        let align = wgpu::COPY_BUFFER_ALIGNMENT;
+        let end = range.end.min(buffer.size());
+        let len = end.saturating_sub(range.start);
+        // the aligned bytes that are left in the buffer
+        let available = buffer.size().saturating_sub(range.start) / align * align;
+        let size = len.next_multiple_of(align).min(available);
+
+        let staging = self.staging_buffer(size);
+        self.encoder.copy_buffer_to_buffer(buffer, range.start, &staging, 0, size);
+
+        self.push_readback(staging, StagingLayout::Bytes(len.min(size)))
+    }
+
+    /// Copy the first mip level of `texture` back to the CPU.
+    ///
+    /// See [`RenderContext::read_texture_region`].
+    pub fn read_texture(
+        &mut self,
+        texture: &wgpu::Texture,
+    ) -> Readback {
+        self.read_texture_region(texture, wgpu::Origin3d::ZERO, texture.size())
+    }
+
+    /// Copy a region of the first mip level of `texture` back to the CPU.
+    ///
+    /// The rows of the result are tightly packed, the alignment required by
+    /// [`wgpu::COPY_BYTES_PER_ROW_ALIGNMENT`] is removed.
+    /// For depth-stencil textures, only the depth aspect is read.
+    ///
+    /// All the color, depth and stencil formats are supported. The depth of `Depth24Plus`
+    /// and `Depth24PlusStencil8` can't be copied to a buffer, it is copied by a compute
+    /// shader instead and read as `f32`s, the same as `Depth32Float`.
+    ///
+    /// # Remarks
+    /// `texture` must have the [`COPY_SRC`](wgpu::TextureUsages::COPY_SRC) usage,
+    /// or the [`TEXTURE_BINDING`](wgpu::TextureUsages::TEXTURE_BINDING) usage and not
+    /// be multisampled for the depth formats that are copied by a shader (compute shaders
+    /// are not available with WebGL).
+    ///
+    /// # Panics
+    /// If the format is multi-planar, e.g. [`wgpu::TextureFormat::NV12`].
+    pub fn read_texture_region(
+        &mut self,
+        texture: &wgpu::Texture,
+        origin: wgpu::Origin3d,
+        size: wgpu::Extent3d,
+    ) -> Readback {
+        let format = texture.format();
+        let aspect = if format.has_depth_aspect() {
+            wgpu::TextureAspect::DepthOnly
+        } else {
+            wgpu::TextureAspect::All
+        };
+        if aspect == wgpu::TextureAspect::DepthOnly && format.block_copy_size(Some(aspect)).is_none() {
+            return self.read_depth_with_shader(texture, origin, size);
+        }
+        let block_size = format
+            .block_copy_size(Some(aspect))
+            .expect("multi-planar textures can't be copied");
+        let (block_w, block_h) = format.block_dimensions();
+
+        let rows = size.height.div_ceil(block_h) * size.depth_or_array_layers;
+        let unpadded_bytes_per_row = size.width.div_ceil(block_w) * block_size;
+        let padded_bytes_per_row = unpadded_bytes_per_row.next_multiple_of(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT);
+
+        let staging = self.staging_buffer(padded_bytes_per_row as u64 * rows as u64);
+
+        self.encoder.copy_texture_to_buffer(
+            wgpu::ImageCopyTexture {
+                texture,
+                mip_level: 0,
+                origin,
+                aspect,
+            },
+            wgpu::ImageCopyBuffer {
+                buffer: &staging,
+                layout: wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(padded_bytes_per_row),
+                    rows_per_image: Some(size.height.div_ceil(block_h)),
+                },
+            },
+            size,
+        );
+
+        self.push_readback(staging, StagingLayout::Rows {
+            unpadded_bytes_per_row,
+            padded_bytes_per_row,
+            rows,
+        })
+    }
+
+    /// Copy the depth aspect of a region of `texture` into a buffer with [`DepthCopyCommon`],
+    /// then read the buffer
+    fn read_depth_with_shader(
+        &mut self,
+        texture: &wgpu::Texture,
+        origin: wgpu::Origin3d,
+        size: wgpu::Extent3d,
+    ) -> Readback {
+        let common = self.singleton::<DepthCopyCommon>();
+
+        let len = size.width as u64 * size.height as u64 * size.depth_or_array_layers as u64 * 4;
+
+        // empty bindings are not allowed
+        let output = self.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("depth copy buffer"),
+            size: len.max(4),
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        });
+
+        let region = self.device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("depth copy region buffer"),
+            contents: bytemuck::cast_slice(&[
+                origin.x, origin.y, origin.z, 0,
+                size.width, size.height, size.depth_or_array_layers, 0,
+            ]),
+            usage: wgpu::BufferUsages::UNIFORM,
+        });
+
+        let view = texture.create_view(&wgpu::TextureViewDescriptor {
+            label: Some("depth copy view"),
+            dimension: Some(wgpu::TextureViewDimension::D2Array),
+            aspect: wgpu::TextureAspect::DepthOnly,
+            mip_level_count: Some(1),
+            ..Default::default()
+        });
+
+        let bind_group = self.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &common.bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: region.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: output.as_entire_binding(),
+                },
+            ],
+            label: Some("depth_copy_bind_group"),
+        });
+
+        {
+            let mut cp = self.encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                label: Some("depth copy pass"),
+                timestamp_writes: None,
+            });
+            cp.set_pipeline(&common.pipeline);
+            cp.set_bind_group(0, &bind_group, &[]);
+            cp.dispatch_workgroups(
+                size.width.div_ceil(DEPTH_COPY_WORKGROUP_SIZE),
+                size.height.div_ceil(DEPTH_COPY_WORKGROUP_SIZE),
+                size.depth_or_array_layers,
+            );
+        }
+
+        self.read_buffer(&output, 0..len)
+    }
+
+    fn staging_buffer(&self, size: wgpu::BufferAddress) -> wgpu::Buffer {
+        self.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("readback staging buffer"),
+            size,
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        })
+    }
+
+    fn push_readback(&mut self, staging: wgpu::Buffer, layout: StagingLayout) -> Readback {
+        let readback = Readback::new();
+
+        self.resource_registry.push_readback(PendingReadback {
+            staging: Arc::new(staging),
+            layout,
+            state: readback.state.clone(),
+        });
+
+        readback
+    }
+}
+
+
+/// The size of the workgroups of `depth_copy.wgsl` along `x` and `y`
+const DEPTH_COPY_WORKGROUP_SIZE: u32 = 8;
+
+/// The compute pipeline that copies the depth aspects which can't be copied to a buffer
+pub(crate) struct DepthCopyCommon {
+    bind_group_layout: wgpu::BindGroupLayout,
+    pipeline: wgpu::ComputePipeline,
+}
+
+impl SingletonResource for DepthCopyCommon {
+    fn init(ctx: &mut RenderContext) -> Self {
+        let bind_group_layout = ctx.device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2Array,
+                        sample_type: wgpu::TextureSampleType::Depth,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Storage { read_only: false },
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+            label: Some("depth_copy_bind_group_layout"),
+        });
+
+        let shader = ctx.device.create_shader_module(wgpu::include_wgsl!("readback/depth_copy.wgsl"));
+
+        let pipeline_layout = ctx.device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: None,
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        let pipeline = ctx.device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("depth copy pipeline"),
+            layout: Some(&pipeline_layout),
+            module: &shader,
+            entry_point: "cs_main",
+            compilation_options: Default::default(),
+            cache: None,
+        });
+
+        Self {
+            bind_group_layout,
+            pipeline,
+        }
+    }
+}
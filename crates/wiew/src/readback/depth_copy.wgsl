@@ -0,0 +1,27 @@
+// Copies the depth aspect of a texture whose depth can't be copied to a buffer
+// (`Depth24Plus`, `Depth24PlusStencil8`) as tightly packed `f32`s
+
+struct Region {
+    // x, y and first layer
+    origin: vec4<u32>,
+    // width, height and layers
+    size: vec4<u32>,
+};
+
+@group(0) @binding(0)
+var depth: texture_depth_2d_array;
+@group(0) @binding(1)
+var<uniform> region: Region;
+@group(0) @binding(2)
+var<storage, read_write> output: array<f32>;
+
+@compute @workgroup_size(8, 8, 1)
+fn cs_main(@builtin(global_invocation_id) id: vec3<u32>) {
+    if (id.x >= region.size.x || id.y >= region.size.y || id.z >= region.size.z) {
+        return;
+    }
+
+    let texel = region.origin.xy + id.xy;
+    let index = (id.z * region.size.y + id.y) * region.size.x + id.x;
+    output[index] = textureLoad(depth, texel, region.origin.z + id.z, 0);
+}
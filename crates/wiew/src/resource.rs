@@ -2,11 +2,12 @@ use std::{any::{Any, TypeId}, collections::HashMap, ops::Deref, sync::{atomic::A
 
 //use type_map::TypeMap;
 
-use crate::{RenderContext, ResId};
+use crate::{readback::PendingReadback, RenderContext, ResId};
 
 pub struct ResourceRegistry {
     id_maps: HashMap<ResId, ResourceHold>,
     singletons: HashMap<TypeId, ResourceHold>,
+    readbacks: Vec<PendingReadback>,
 }
 
 struct ResourceHold {
@@ -20,6 +21,24 @@ impl ResourceRegistry {
         Self {
             id_maps: HashMap::new(),
             singletons: HashMap::new(),
+            readbacks: Vec::new(),
+        }
+    }
+
+    pub(crate) fn push_readback(&mut self, readback: PendingReadback) {
+        self.readbacks.push(readback);
+    }
+
+    /// Start mapping the staging buffers of the pending [`Readback`](crate::Readback)s.
+    ///
+    /// # Remarks
+    /// This must be called **after** the command buffers recorded with the
+    /// [`RenderContext`] have been submitted, for example at the beginning of
+    /// the next frame. Mapping a buffer that is used by unsubmitted commands
+    /// is a validation error.
+    pub fn map_readbacks(&mut self) {
+        for readback in self.readbacks.drain(..) {
+            readback.map();
         }
     }
 
@@ -0,0 +1,174 @@
+use cgmath::InnerSpace;
+use nalgebra::Vector3;
+
+use crate::{Aabb, RenderContext, SingletonResource, OPENGL_TO_WGPU_MATRIX, PUID};
+
+/// Format of the [`ShadowMap`]s
+pub const SHADOW_MAP_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth32Float;
+
+/// Largest [`ShadowSettings::pcf_radius`], the kernel has `(2 * r + 1)²` taps
+pub const MAX_PCF_RADIUS: u32 = 4;
+
+/// Shadows cast by the camera light (see [`ProjectionCamera::light_dir`](crate::ProjectionCamera::light_dir))
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ShadowSettings {
+    /// Width and height of the shadow map, in texels
+    pub resolution: u32,
+    /// Subtracted from the depth of the receivers, in the `[0, 1]` depth range of the light
+    pub bias: f32,
+    /// Offset of the receivers along their normal, in texels of the shadow map
+    pub normal_bias: f32,
+    /// Radius of the percentage-closer filtering kernel, in texels (at most [`MAX_PCF_RADIUS`])
+    pub pcf_radius: u32,
+    /// How much light the shadows block, between `0` and `1`
+    pub strength: f32,
+}
+
+impl Default for ShadowSettings {
+    fn default() -> Self {
+        Self {
+            resolution: 2048,
+            bias: 0.001,
+            normal_bias: 1.0,
+            pcf_radius: 1,
+            strength: 0.8,
+        }
+    }
+}
+
+/// The comparison sampler of the shadow maps, and a placeholder bound when there are no shadows
+pub struct ShadowMapCommon {
+    sampler: wgpu::Sampler,
+    placeholder: wgpu::TextureView,
+}
+
+impl SingletonResource for ShadowMapCommon {
+    fn init(ctx: &mut RenderContext) -> Self {
+        let sampler = ctx.device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("shadow map sampler"),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Nearest,
+            compare: Some(wgpu::CompareFunction::LessEqual),
+            ..Default::default()
+        });
+
+        let placeholder = ShadowMap::create_texture(ctx.device, 1)
+            .create_view(&wgpu::TextureViewDescriptor::default());
+
+        Self {
+            sampler,
+            placeholder,
+        }
+    }
+}
+
+impl ShadowMapCommon {
+    pub fn sampler(&self) -> &wgpu::Sampler {
+        &self.sampler
+    }
+
+    /// A 1x1 shadow map, for the passes that do not sample shadows
+    pub fn placeholder(&self) -> &wgpu::TextureView {
+        &self.placeholder
+    }
+}
+
+/// The depth of the scene seen from the camera light
+pub struct ShadowMap {
+    id: PUID,
+    resolution: u32,
+    view: wgpu::TextureView,
+}
+
+impl ShadowMap {
+    pub fn new(
+        cx: &mut RenderContext,
+        resolution: u32,
+    ) -> Self {
+        let view = Self::create_texture(cx.device, resolution)
+            .create_view(&wgpu::TextureViewDescriptor::default());
+
+        Self {
+            id: PUID::new(),
+            resolution,
+            view,
+        }
+    }
+
+    fn create_texture(device: &wgpu::Device, resolution: u32) -> wgpu::Texture {
+        device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("shadow map"),
+            size: wgpu::Extent3d {
+                width: resolution,
+                height: resolution,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: SHADOW_MAP_FORMAT,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        })
+    }
+
+    /// Identifies the texture, to know when a bind group must be recreated
+    pub fn id(&self) -> PUID {
+        self.id
+    }
+
+    pub fn resolution(&self) -> u32 {
+        self.resolution
+    }
+
+    pub fn view(&self) -> &wgpu::TextureView {
+        &self.view
+    }
+}
+
+/// The orthographic camera of a directional light, covering some bounds
+#[derive(Debug, Clone, Copy)]
+pub struct LightCamera {
+    pub view: cgmath::Matrix4<f32>,
+    pub proj: cgmath::Matrix4<f32>,
+    pub position: cgmath::Point3<f32>,
+    /// Direction towards the light
+    pub direction: cgmath::Vector3<f32>,
+    /// Width and height of the area seen by the camera, in world units
+    pub extent: f32,
+}
+
+impl LightCamera {
+    /// Look at the bounding sphere of `bounds` from the direction `light_dir` (towards the light)
+    pub fn fit(light_dir: Vector3<f32>, bounds: &Aabb) -> Self {
+        let center = bounds.center();
+        let center = cgmath::Point3::new(center.x, center.y, center.z);
+        let radius = bounds.radius().max(1e-3);
+
+        let direction = cgmath::Vector3::new(light_dir.x, light_dir.y, light_dir.z);
+        let direction = if direction.magnitude2() > 0.0 { direction.normalize() } else { cgmath::Vector3::unit_y() };
+
+        let up = if direction.y.abs() > 0.99 { cgmath::Vector3::unit_z() } else { cgmath::Vector3::unit_y() };
+
+        // the sphere lies between the near and far planes
+        let position = center + direction * 2.0 * radius;
+        let view = cgmath::Matrix4::look_at_rh(position, center, up);
+        let proj = OPENGL_TO_WGPU_MATRIX * cgmath::ortho(-radius, radius, -radius, radius, radius, 3.0 * radius);
+
+        Self {
+            view,
+            proj,
+            position,
+            direction,
+            extent: 2.0 * radius,
+        }
+    }
+
+    pub fn view_proj(&self) -> cgmath::Matrix4<f32> {
+        self.proj * self.view
+    }
+}
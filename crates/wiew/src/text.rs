@@ -0,0 +1,705 @@
+use std::{collections::HashMap, sync::{atomic::{AtomicU64, Ordering}, Arc, Mutex}};
+
+use ab_glyph::{Font as _, FontArc, GlyphId, PxScale, ScaleFont};
+use wgpu::{util::DeviceExt, PrimitiveState, PrimitiveTopology};
+
+use crate::{decl_vertex_raw_repr, instance::Instance3d, pipelines::{shader_with_globals, Pipeline}, Pass, PassKind, ProjectionCameraCommon, RenderContext, Res, SamplerOptions, SingletonResource, Texture2d, Texture2dCommon, VertexBuffer, VertexBufferSlice, VertexRawRepr};
+
+/// Side of the glyph atlas texture, in pixels
+const ATLAS_SIZE: u32 = 1024;
+/// Size at which the glyphs are rasterized in the atlas, in pixels
+const RASTER_SIZE: f32 = 48.0;
+/// Distance covered by the signed distance field on each side of the outline, in pixels
+const SDF_SPREAD: u32 = 6;
+
+/// A font used to render text, see [`TextPipeline::with_font`]
+#[derive(Clone)]
+pub struct Font {
+    font: FontArc,
+}
+
+impl Font {
+    /// Load a TrueType or OpenType font
+    pub fn from_bytes(data: Vec<u8>) -> Result<Self, ab_glyph::InvalidFont> {
+        Ok(Self {
+            font: FontArc::try_from_vec(data)?,
+        })
+    }
+}
+
+impl Default for Font {
+    /// The bundled font (Ubuntu Light)
+    fn default() -> Self {
+        Self {
+            font: FontArc::try_from_slice(epaint_default_fonts::UBUNTU_LIGHT).expect("the bundled font is valid"),
+        }
+    }
+}
+
+decl_vertex_raw_repr! {
+    /// A glyph quad, produced by the layout of a [`TextBuffer`].
+    ///
+    /// Glyphs are stepped per instance: each of them is expanded into a quad
+    /// by the vertex shader.
+    #[derive(Debug)]
+    struct GlyphVertex (Instance step mode) {
+        /// The anchor of the label
+        pub position: [f32; 3] as [7 => Float32x3],
+        /// The quad relative to the anchor, in units of the text size, y up
+        pub rect: [f32; 4] as [8 => Float32x4],
+        /// The quad in the atlas texture
+        pub uv: [f32; 4] as [9 => Float32x4],
+        pub color: [f32; 4] as [10 => Float32x4],
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct GlyphInfo {
+    uv: [f32; 4],
+    /// Quad relative to the pen position on the baseline, in units of the text size
+    plane: [f32; 4],
+}
+
+static NEXT_ATLAS_ID: AtomicU64 = AtomicU64::new(0);
+
+/// Signed distance fields of the glyphs of a [`Font`], rasterized on demand
+pub struct GlyphAtlas {
+    id: u64,
+    font: Font,
+    texture: Texture2d,
+    pixels: Vec<u8>,
+    glyphs: HashMap<char, Option<GlyphInfo>>,
+    cursor: (u32, u32),
+    row_height: u32,
+    /// Rows of `pixels` that have not been uploaded yet
+    dirty_rows: Option<(u32, u32)>,
+}
+
+impl GlyphAtlas {
+    pub fn new(
+        cx: &mut RenderContext,
+        font: Font,
+    ) -> Self {
+        let texture = cx.device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("glyph atlas"),
+            size: wgpu::Extent3d {
+                width: ATLAS_SIZE,
+                height: ATLAS_SIZE,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::R8Unorm,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+
+        Self {
+            id: NEXT_ATLAS_ID.fetch_add(1, Ordering::Relaxed),
+            font,
+            texture: Texture2d::from_texture(cx, texture, &SamplerOptions::default().clamped()),
+            pixels: vec![0; (ATLAS_SIZE * ATLAS_SIZE) as usize],
+            glyphs: HashMap::new(),
+            cursor: (0, 0),
+            row_height: 0,
+            dirty_rows: None,
+        }
+    }
+
+    pub fn texture(&self) -> &Texture2d {
+        &self.texture
+    }
+
+    /// Upload the glyphs added since the last upload
+    pub fn upload(&mut self, queue: &wgpu::Queue) {
+        let Some((first, last)) = self.dirty_rows.take() else {
+            return;
+        };
+
+        queue.write_texture(
+            wgpu::ImageCopyTexture {
+                texture: self.texture.texture(),
+                mip_level: 0,
+                origin: wgpu::Origin3d { x: 0, y: first, z: 0 },
+                aspect: wgpu::TextureAspect::All,
+            },
+            &self.pixels[(first * ATLAS_SIZE) as usize..(last * ATLAS_SIZE) as usize],
+            wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(ATLAS_SIZE),
+                rows_per_image: None,
+            },
+            wgpu::Extent3d {
+                width: ATLAS_SIZE,
+                height: last - first,
+                depth_or_array_layers: 1,
+            },
+        );
+    }
+
+    fn glyph(&mut self, c: char) -> Option<GlyphInfo> {
+        if let Some(info) = self.glyphs.get(&c) {
+            return *info;
+        }
+
+        let info = self.rasterize(c);
+        self.glyphs.insert(c, info);
+        info
+    }
+
+    fn rasterize(&mut self, c: char) -> Option<GlyphInfo> {
+        let font = &self.font.font;
+        let glyph = font.as_scaled(PxScale::from(RASTER_SIZE)).scaled_glyph(c);
+        // whitespace has no outline
+        let outline = font.outline_glyph(glyph)?;
+        let bounds = outline.px_bounds();
+
+        let pad = SDF_SPREAD as usize;
+        let w = bounds.width().ceil() as usize + 2 * pad;
+        let h = bounds.height().ceil() as usize + 2 * pad;
+
+        // shelf packing, with a pixel of margin between the glyphs
+        if self.cursor.0 + w as u32 > ATLAS_SIZE {
+            self.cursor = (0, self.cursor.1 + self.row_height + 1);
+            self.row_height = 0;
+        }
+        if self.cursor.1 + h as u32 > ATLAS_SIZE {
+            log::warn!("glyph atlas is full, {c:?} will not be rendered");
+            return None;
+        }
+        let (x, y) = self.cursor;
+        self.cursor.0 += w as u32 + 1;
+        self.row_height = self.row_height.max(h as u32);
+
+        let mut inside = vec![false; w * h];
+        outline.draw(|gx, gy, coverage| {
+            inside[(gy as usize + pad) * w + gx as usize + pad] = coverage >= 0.5;
+        });
+
+        let outside = inside.iter().map(|i| !i).collect::<Vec<_>>();
+        let to_inside = distance_transform(&inside, w, h);
+        let to_outside = distance_transform(&outside, w, h);
+
+        for j in 0..h {
+            for i in 0..w {
+                let k = j * w + i;
+                // pixel centers next to the outline are half a pixel away from it
+                let d = if inside[k] { to_outside[k] - 0.5 } else { 0.5 - to_inside[k] };
+                let v = 0.5 + d / (2.0 * SDF_SPREAD as f32);
+                self.pixels[(y as usize + j) * ATLAS_SIZE as usize + x as usize + i] = (v.clamp(0.0, 1.0) * 255.0).round() as u8;
+            }
+        }
+
+        let (first, last) = self.dirty_rows.unwrap_or((y, y + h as u32));
+        self.dirty_rows = Some((first.min(y), last.max(y + h as u32)));
+
+        let left = (bounds.min.x - pad as f32) / RASTER_SIZE;
+        let top = -(bounds.min.y - pad as f32) / RASTER_SIZE;
+        let s = ATLAS_SIZE as f32;
+
+        Some(GlyphInfo {
+            uv: [x as f32 / s, y as f32 / s, (x as usize + w) as f32 / s, (y as usize + h) as f32 / s],
+            plane: [left, top - h as f32 / RASTER_SIZE, left + w as f32 / RASTER_SIZE, top],
+        })
+    }
+}
+
+/// Distance from each pixel to the nearest `seed` pixel (8-neighbor dead reckoning)
+fn distance_transform(seeds: &[bool], w: usize, h: usize) -> Vec<f32> {
+    const FAR: (i32, i32) = (i32::MAX / 4, i32::MAX / 4);
+
+    let mut nearest = seeds.iter().enumerate().map(|(k, s)| {
+        if *s { ((k % w) as i32, (k / w) as i32) } else { FAR }
+    }).collect::<Vec<_>>();
+
+    let d2 = |x: i32, y: i32, p: (i32, i32)| {
+        let (dx, dy) = ((p.0 - x) as i64, (p.1 - y) as i64);
+        dx * dx + dy * dy
+    };
+
+    let mut relax = |x: usize, y: usize, offsets: &[(isize, isize)]| {
+        let k = y * w + x;
+        for (ox, oy) in offsets {
+            let (nx, ny) = (x as isize + ox, y as isize + oy);
+            if nx < 0 || ny < 0 || nx >= w as isize || ny >= h as isize {
+                continue;
+            }
+            let candidate = nearest[ny as usize * w + nx as usize];
+            if candidate != FAR && d2(x as i32, y as i32, candidate) < d2(x as i32, y as i32, nearest[k]) {
+                nearest[k] = candidate;
+            }
+        }
+    };
+
+    for y in 0..h {
+        for x in 0..w {
+            relax(x, y, &[(-1, -1), (0, -1), (1, -1), (-1, 0)]);
+        }
+    }
+    for y in (0..h).rev() {
+        for x in (0..w).rev() {
+            relax(x, y, &[(1, 0), (-1, 1), (0, 1), (1, 1)]);
+        }
+    }
+
+    nearest.iter().enumerate().map(|(k, p)| {
+        if *p == FAR {
+            f32::MAX
+        } else {
+            (d2((k % w) as i32, (k / w) as i32, *p) as f32).sqrt()
+        }
+    }).collect()
+}
+
+/// Which point of the text box is placed at the label position.
+///
+/// `(0, 0)` is the bottom left corner and `(1, 1)` the top right one.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TextAnchor {
+    pub x: f32,
+    pub y: f32,
+}
+
+impl TextAnchor {
+    pub const BOTTOM_LEFT: Self = Self { x: 0.0, y: 0.0 };
+    pub const BOTTOM: Self = Self { x: 0.5, y: 0.0 };
+    pub const LEFT: Self = Self { x: 0.0, y: 0.5 };
+    pub const CENTER: Self = Self { x: 0.5, y: 0.5 };
+    pub const RIGHT: Self = Self { x: 1.0, y: 0.5 };
+    pub const TOP: Self = Self { x: 0.5, y: 1.0 };
+}
+
+/// A string placed in the scene
+#[derive(Debug, Clone, PartialEq)]
+pub struct TextLabel {
+    /// May contain several lines, they are aligned according to `anchor.x`
+    pub text: String,
+    pub position: [f32; 3],
+    pub color: [f32; 4],
+    pub anchor: TextAnchor,
+}
+
+impl TextLabel {
+    pub fn new(text: impl Into<String>, position: [f32; 3]) -> Self {
+        Self {
+            text: text.into(),
+            position,
+            color: [1.0, 1.0, 1.0, 1.0],
+            anchor: TextAnchor::BOTTOM_LEFT,
+        }
+    }
+
+    pub fn with_color(self, color: [f32; 4]) -> Self {
+        Self { color, ..self }
+    }
+
+    pub fn with_anchor(self, anchor: TextAnchor) -> Self {
+        Self { anchor, ..self }
+    }
+}
+
+fn layout(
+    atlas: &mut GlyphAtlas,
+    label: &TextLabel,
+    out: &mut Vec<GlyphVertex>,
+) {
+    let font = atlas.font.font.clone();
+    let scaled = font.as_scaled(PxScale::from(RASTER_SIZE));
+    let ascent = scaled.ascent() / RASTER_SIZE;
+    let descent = scaled.descent() / RASTER_SIZE;
+    let line_height = (scaled.ascent() - scaled.descent() + scaled.line_gap()) / RASTER_SIZE;
+
+    let mut lines = Vec::new();
+
+    for (i, line) in label.text.lines().enumerate() {
+        let baseline = -(i as f32) * line_height;
+        let line_start = out.len();
+        let mut pen = 0.0;
+        let mut prev: Option<GlyphId> = None;
+
+        for c in line.chars() {
+            let id = font.glyph_id(c);
+            if let Some(prev) = prev {
+                pen += scaled.kern(prev, id) / RASTER_SIZE;
+            }
+
+            if let Some(info) = atlas.glyph(c) {
+                out.push(GlyphVertex {
+                    position: label.position,
+                    rect: [
+                        pen + info.plane[0],
+                        baseline + info.plane[1],
+                        pen + info.plane[2],
+                        baseline + info.plane[3],
+                    ],
+                    uv: info.uv,
+                    color: label.color,
+                });
+            }
+
+            pen += scaled.h_advance(id) / RASTER_SIZE;
+            prev = Some(id);
+        }
+
+        lines.push((line_start..out.len(), pen));
+    }
+
+    let bottom = descent - (lines.len().max(1) - 1) as f32 * line_height;
+
+    for (glyphs, line_width) in lines {
+        let dx = -line_width * label.anchor.x;
+        let dy = -(bottom + (ascent - bottom) * label.anchor.y);
+        for glyph in &mut out[glyphs] {
+            glyph.rect[0] += dx;
+            glyph.rect[2] += dx;
+            glyph.rect[1] += dy;
+            glyph.rect[3] += dy;
+        }
+    }
+}
+
+/// A set of [`TextLabel`]s drawn by a [`TextPipeline`].
+///
+/// The layout is computed when the buffer is rendered and kept until the labels
+/// or the glyph atlas change.
+pub struct TextBuffer {
+    labels: Vec<TextLabel>,
+    glyphs: Option<(u64, Option<VertexBuffer<GlyphVertex>>)>,
+}
+
+impl TextBuffer {
+    pub fn new(labels: Vec<TextLabel>) -> Self {
+        Self {
+            labels,
+            glyphs: None,
+        }
+    }
+
+    pub fn labels(&self) -> &[TextLabel] {
+        &self.labels
+    }
+
+    pub fn set_labels(&mut self, labels: Vec<TextLabel>) {
+        self.labels = labels;
+        self.glyphs = None;
+    }
+
+    fn prepare(
+        &mut self,
+        cx: &mut RenderContext,
+        atlas: &mut GlyphAtlas,
+    ) -> Option<VertexBufferSlice<GlyphVertex>> {
+        if !matches!(self.glyphs, Some((id, _)) if id == atlas.id) {
+            let mut vertices = Vec::new();
+            for label in &self.labels {
+                layout(atlas, label, &mut vertices);
+            }
+
+            let buffer = (!vertices.is_empty()).then(|| VertexBuffer::from_slice(cx.device, &vertices, Some("text glyphs")));
+            self.glyphs = Some((atlas.id, buffer));
+        }
+
+        self.glyphs.as_ref().and_then(|(_, buffer)| buffer.as_ref()).map(|b| b.slice(..))
+    }
+}
+
+/// The size of the text, i.e. the nominal height of a line
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TextSize {
+    /// Constant size on screen
+    Pixels(f32),
+    /// Size in world units
+    World(f32),
+}
+
+/// How the labels are oriented
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextOrientation {
+    /// Always facing the camera
+    Billboard,
+    /// In the xy plane of the instance, readable from +z
+    World,
+}
+
+/// Appearance of the text drawn by [`TextPipeline`]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TextStyle {
+    pub size: TextSize,
+    pub orientation: TextOrientation,
+}
+
+impl Default for TextStyle {
+    fn default() -> Self {
+        Self {
+            size: TextSize::Pixels(16.0),
+            orientation: TextOrientation::Billboard,
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct TextStyleUniform {
+    size: f32,
+    size_unit: u32,
+    orientation: u32,
+    _padding: u32,
+}
+
+impl From<&TextStyle> for TextStyleUniform {
+    fn from(style: &TextStyle) -> Self {
+        let (size, size_unit) = match style.size {
+            TextSize::Pixels(size) => (size, 0),
+            TextSize::World(size) => (size, 1),
+        };
+
+        Self {
+            size,
+            size_unit,
+            orientation: match style.orientation {
+                TextOrientation::Billboard => 0,
+                TextOrientation::World => 1,
+            },
+            _padding: 0,
+        }
+    }
+}
+
+/// The bind group layout of [`TextMaterial`]s
+pub struct TextMaterialCommon {
+    bind_group_layout: wgpu::BindGroupLayout,
+}
+
+impl SingletonResource for TextMaterialCommon {
+    fn init(ctx: &mut RenderContext) -> Self {
+        let bind_group_layout = ctx.device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::VERTEX,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                }
+            ],
+            label: Some("text_material_bind_group_layout"),
+        });
+
+        Self {
+            bind_group_layout,
+        }
+    }
+}
+
+impl TextMaterialCommon {
+    pub fn layout(&self) -> &wgpu::BindGroupLayout {
+        &self.bind_group_layout
+    }
+}
+
+/// The GPU side of [`TextStyle`], bound as `@group(1)` by [`TextPipeline`]
+pub struct TextMaterial {
+    buffer: wgpu::Buffer,
+    bind_group: Arc<wgpu::BindGroup>,
+}
+
+impl TextMaterial {
+    pub fn new(
+        cx: &mut RenderContext,
+        style: &TextStyle,
+    ) -> Self {
+        let common = cx.singleton::<TextMaterialCommon>();
+
+        let buffer = cx.device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("text material buffer"),
+            contents: bytemuck::cast_slice(&[TextStyleUniform::from(style)]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let bind_group = cx.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: common.layout(),
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: buffer.as_entire_binding(),
+                }
+            ],
+            label: Some("text_material_bind_group"),
+        });
+
+        Self {
+            buffer,
+            bind_group: Arc::new(bind_group),
+        }
+    }
+
+    pub fn update(&self, queue: &wgpu::Queue, style: &TextStyle) {
+        queue.write_buffer(&self.buffer, 0, bytemuck::cast_slice(&[TextStyleUniform::from(style)]));
+    }
+}
+
+/// A shader for SDF text
+pub struct TextShader {
+    shader: wgpu::ShaderModule,
+}
+
+impl SingletonResource for TextShader {
+    fn init(ctx: &mut RenderContext) -> Self {
+        Self {
+            shader: shader_with_globals(ctx.device, "text.wgsl", include_str!("text/text.wgsl")),
+        }
+    }
+}
+
+/// Text labels rendered from a signed distance field glyph atlas.
+///
+/// As for the [`PointPipeline`](crate::pipelines::point::PointPipeline), the glyphs
+/// are the instances and the first instance of the given [`Instance3d`] slice
+/// places all the labels.
+pub struct TextPipeline {
+    pipeline: Pipeline,
+    atlas: Res<Mutex<GlyphAtlas>>,
+    default_material: Res<TextMaterial>,
+}
+
+impl TextPipeline {
+    /// A text pipeline using the bundled font
+    pub fn new(
+        depth_compare: wgpu::CompareFunction,
+        use_depth_stencil: bool,
+    ) -> Self {
+        Self::with_font(Font::default(), depth_compare, use_depth_stencil)
+    }
+
+    pub fn with_font(
+        font: Font,
+        depth_compare: wgpu::CompareFunction,
+        use_depth_stencil: bool,
+    ) -> Self {
+        let primitive = PrimitiveState {
+            topology: PrimitiveTopology::TriangleList,
+            ..Default::default()
+        };
+
+        let pipeline = Pipeline::from_builder(move |cx, formats| {
+            let shader = cx.singleton::<TextShader>();
+
+            let camera_common = cx.singleton::<ProjectionCameraCommon>();
+            let material_common = cx.singleton::<TextMaterialCommon>();
+            let texture_common = cx.singleton::<Texture2dCommon>();
+
+            let render_pipeline_layout =
+            cx.device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: None,
+                bind_group_layouts: &[
+                    camera_common.layout(),
+                    material_common.layout(),
+                    texture_common.layout(),
+                ],
+                push_constant_ranges: &[],
+            });
+
+            let targets = formats.color_targets(wgpu::BlendState::ALPHA_BLENDING);
+
+            let shared_instance = wgpu::VertexBufferLayout {
+                array_stride: 0,
+                ..Instance3d::desc()
+            };
+
+            cx.device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some("text pipeline"),
+                layout: Some(&render_pipeline_layout),
+                vertex: wgpu::VertexState {
+                    module: &shader.shader,
+                    entry_point: "vs_main",
+                    buffers: &[
+                        GlyphVertex::desc(),
+                        shared_instance,
+                    ],
+                    compilation_options: Default::default(),
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &shader.shader,
+                    entry_point: formats.fragment_entry_point(),
+                    targets: &targets,
+                    compilation_options: Default::default(),
+                }),
+                primitive,
+                depth_stencil: formats.depth_format.map(|format| wgpu::DepthStencilState {
+                    format,
+                    depth_write_enabled: use_depth_stencil,
+                    depth_compare,
+                    stencil: formats.stencil(),
+                    bias: formats.depth_bias(),
+                }),
+                multisample: Default::default(),
+                multiview: None,
+                cache: None,
+            })
+        });
+
+        Self {
+            pipeline,
+            atlas: Res::new(move |cx: &mut RenderContext| Mutex::new(GlyphAtlas::new(cx, font.clone()))),
+            default_material: Res::new(|cx: &mut RenderContext| TextMaterial::new(cx, &TextStyle::default())),
+        }
+    }
+
+    /// Render with the default [`TextStyle`]
+    pub fn render<'a>(
+        &self,
+        cx: &mut RenderContext,
+        pass: &mut Pass<'a>,
+        text: &mut TextBuffer,
+        instance: impl Into<VertexBufferSlice<Instance3d>>,
+    ) {
+        let material = cx.resource(&self.default_material);
+        self.render_with_material(cx, pass, text, instance, &material);
+    }
+
+    pub fn render_with_material<'a>(
+        &self,
+        cx: &mut RenderContext,
+        pass: &mut Pass<'a>,
+        text: &mut TextBuffer,
+        instance: impl Into<VertexBufferSlice<Instance3d>>,
+        material: &TextMaterial,
+    ) {
+        // labels do not bound a volume, they have no section faces
+        if pass.kind() == PassKind::Section {
+            return;
+        }
+
+        let atlas = cx.resource(&self.atlas);
+        let mut atlas = atlas.lock().unwrap();
+
+        let glyphs = text.prepare(cx, &mut atlas);
+        atlas.upload(cx.queue);
+
+        let Some(glyphs) = glyphs else {
+            return;
+        };
+
+        let instance: VertexBufferSlice<Instance3d> = instance.into();
+        let material = material.bind_group.clone();
+        let atlas = atlas.texture().bind_group().clone();
+
+        let pipeline = self.pipeline.get(cx, pass);
+
+        pass.defer(move |rp, globals| {
+            let instance_offset = instance.range.start as wgpu::BufferAddress * std::mem::size_of::<Instance3d>() as wgpu::BufferAddress;
+
+            rp.set_pipeline(&pipeline);
+            rp.set_bind_group(0, globals, &[]);
+            rp.set_bind_group(1, &material, &[]);
+            rp.set_bind_group(2, &atlas, &[]);
+            rp.set_vertex_buffer(0, glyphs.buffer.slice(..));
+            rp.set_vertex_buffer(1, instance.buffer.slice(instance_offset..));
+            rp.draw(0..6, glyphs.range.clone());
+        });
+    }
+}
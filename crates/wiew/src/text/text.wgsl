@@ -0,0 +1,146 @@
+// ================================
+//             Style
+// ================================
+
+const SIZE_PIXELS: u32 = 0u;
+const SIZE_WORLD: u32 = 1u;
+
+const ORIENTATION_BILLBOARD: u32 = 0u;
+const ORIENTATION_WORLD: u32 = 1u;
+
+struct TextStyle {
+    size: f32,
+    size_unit: u32,
+    orientation: u32,
+    _padding: u32,
+};
+
+@group(1) @binding(0)
+var<uniform> style: TextStyle;
+
+// signed distance field of the glyphs, 0.5 is the outline
+@group(2) @binding(0)
+var atlas_texture: texture_2d<f32>;
+@group(2) @binding(1)
+var atlas_sampler: sampler;
+
+// ================================
+//            Vertex
+// ================================
+
+// glyphs are stepped per instance, the vertex index selects the corner of the quad
+struct GlyphInput {
+    // the anchor of the label
+    @location(7) position: vec3<f32>,
+    // quad of the glyph relative to the anchor, in units of the text size (y up)
+    @location(8) rect: vec4<f32>,
+    // atlas coordinates of the quad (v down)
+    @location(9) uv: vec4<f32>,
+    @location(10) color: vec4<f32>,
+};
+
+// the same transform is used for all the glyphs of a draw call
+struct InstanceInput {
+    @location(0) model_0: vec4<f32>,
+    @location(1) model_1: vec4<f32>,
+    @location(2) model_2: vec4<f32>,
+    @location(3) model_3: vec4<f32>,
+    @location(4) model_inv_tr_0: vec3<f32>,
+    @location(5) model_inv_tr_1: vec3<f32>,
+    @location(6) model_inv_tr_2: vec3<f32>,
+    @location(13) color: vec4<f32>,
+    @location(14) object_id: u32,
+    @location(15) visible: u32,
+};
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) color: vec4<f32>,
+    @location(1) uv: vec2<f32>,
+    // world position of the glyph, or of the anchor for billboarded labels
+    @location(2) world_position: vec3<f32>,
+};
+
+@vertex
+fn vs_main(
+    @builtin(vertex_index) vertex_index: u32,
+    glyph: GlyphInput,
+    instance: InstanceInput,
+) -> VertexOutput {
+    let model_matrix = mat4x4<f32>(
+        instance.model_0,
+        instance.model_1,
+        instance.model_2,
+        instance.model_3,
+    );
+    let view_proj = camera.proj * camera.view * model_matrix;
+
+    var corners = array<vec2<f32>, 6>(
+        vec2<f32>(0.0, 0.0),
+        vec2<f32>(1.0, 0.0),
+        vec2<f32>(1.0, 1.0),
+        vec2<f32>(0.0, 0.0),
+        vec2<f32>(1.0, 1.0),
+        vec2<f32>(0.0, 1.0),
+    );
+    let corner = corners[vertex_index % 6u];
+    let local = mix(glyph.rect.xy, glyph.rect.zw, corner);
+
+    let anchor = view_proj * vec4<f32>(glyph.position, 1.0);
+
+    // number of pixels per world unit at the depth of the anchor
+    let pixels_per_unit = 0.5 * camera.proj[1][1] * camera.viewport_size.y / anchor.w;
+
+    var out: VertexOutput;
+    if (style.orientation == ORIENTATION_WORLD) {
+        // the text lies in the xy plane of the instance
+        var size = style.size;
+        if (style.size_unit == SIZE_PIXELS) {
+            size /= pixels_per_unit;
+        }
+        let position = vec4<f32>(glyph.position + vec3<f32>(local * size, 0.0), 1.0);
+        out.clip_position = view_proj * position;
+        out.world_position = (model_matrix * position).xyz;
+    } else {
+        var size = style.size;
+        if (style.size_unit == SIZE_WORLD) {
+            size *= pixels_per_unit;
+        }
+        let offset = local * size * 2.0 / camera.viewport_size;
+        out.clip_position = anchor + vec4<f32>(offset * anchor.w, 0.0, 0.0);
+        out.world_position = (model_matrix * vec4<f32>(glyph.position, 1.0)).xyz;
+    }
+
+    out.color = glyph.color * instance.color;
+    out.uv = vec2<f32>(
+        mix(glyph.uv.x, glyph.uv.z, corner.x),
+        mix(glyph.uv.w, glyph.uv.y, corner.y),
+    );
+
+    // hidden instances are moved outside of the clip volume
+    if (instance.visible == 0u) {
+        out.clip_position = vec4<f32>(2.0, 2.0, 2.0, 1.0);
+    }
+
+    return out;
+}
+
+// ================================
+//            Fragment
+// ================================
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    clip_fragment(in.world_position);
+
+    let d = textureSample(atlas_texture, atlas_sampler, in.uv).r;
+
+    // antialias over about one pixel, whatever the scale
+    let w = max(fwidth(d), 1e-4) * 0.5;
+    let alpha = smoothstep(0.5 - w, 0.5 + w, d);
+    if (alpha <= 0.0) {
+        discard;
+    }
+
+    return apply_fog(vec4<f32>(in.color.rgb, in.color.a * alpha), in.world_position, in.clip_position.xy);
+}
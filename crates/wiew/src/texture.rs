@@ -0,0 +1,439 @@
+use std::{collections::HashMap, path::Path, sync::{Arc, Mutex}};
+
+use crate::{RenderContext, SingletonResource};
+
+/// How a [`Texture2d`] is sampled
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SamplerOptions {
+    pub address_mode_u: wgpu::AddressMode,
+    pub address_mode_v: wgpu::AddressMode,
+    pub mag_filter: wgpu::FilterMode,
+    pub min_filter: wgpu::FilterMode,
+    pub mipmap_filter: wgpu::FilterMode,
+    /// Anisotropic filtering, `1` disables it
+    pub anisotropy_clamp: u16,
+}
+
+impl Default for SamplerOptions {
+    fn default() -> Self {
+        Self {
+            address_mode_u: wgpu::AddressMode::Repeat,
+            address_mode_v: wgpu::AddressMode::Repeat,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Linear,
+            anisotropy_clamp: 1,
+        }
+    }
+}
+
+impl SamplerOptions {
+    /// Nearest neighbor sampling, useful for colormaps and pixel data
+    pub fn nearest() -> Self {
+        Self {
+            mag_filter: wgpu::FilterMode::Nearest,
+            min_filter: wgpu::FilterMode::Nearest,
+            mipmap_filter: wgpu::FilterMode::Nearest,
+            ..Default::default()
+        }
+    }
+
+    /// Clamp the texture coordinates to the edges instead of repeating the texture
+    pub fn clamped(mut self) -> Self {
+        self.address_mode_u = wgpu::AddressMode::ClampToEdge;
+        self.address_mode_v = wgpu::AddressMode::ClampToEdge;
+        self
+    }
+
+    fn create_sampler(&self, device: &wgpu::Device) -> wgpu::Sampler {
+        device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("texture sampler"),
+            address_mode_u: self.address_mode_u,
+            address_mode_v: self.address_mode_v,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: self.mag_filter,
+            min_filter: self.min_filter,
+            mipmap_filter: self.mipmap_filter,
+            anisotropy_clamp: self.anisotropy_clamp,
+            ..Default::default()
+        })
+    }
+}
+
+/// Options for the creation of a [`Texture2d`]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Texture2dOptions {
+    /// Whether the pixel data is sRGB encoded (usually true for images, false for data)
+    pub srgb: bool,
+    /// Generate the full mip chain
+    pub mipmaps: bool,
+    pub sampler: SamplerOptions,
+}
+
+impl Default for Texture2dOptions {
+    fn default() -> Self {
+        Self {
+            srgb: true,
+            mipmaps: true,
+            sampler: Default::default(),
+        }
+    }
+}
+
+/// The bind group layout of [`Texture2d`]s: the texture at binding `0` and its sampler at binding `1`
+pub struct Texture2dCommon {
+    bind_group_layout: wgpu::BindGroupLayout,
+}
+
+impl SingletonResource for Texture2dCommon {
+    fn init(ctx: &mut RenderContext) -> Self {
+        let bind_group_layout = ctx.device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+            label: Some("texture_2d_bind_group_layout"),
+        });
+
+        Self {
+            bind_group_layout,
+        }
+    }
+}
+
+impl Texture2dCommon {
+    pub fn layout(&self) -> &wgpu::BindGroupLayout {
+        &self.bind_group_layout
+    }
+}
+
+/// A 2D texture with its sampler, ready to be bound with the [`Texture2dCommon`] layout.
+pub struct Texture2d {
+    texture: wgpu::Texture,
+    view: wgpu::TextureView,
+    sampler: wgpu::Sampler,
+    bind_group: Arc<wgpu::BindGroup>,
+}
+
+impl Texture2d {
+    /// Create a texture from tightly packed RGBA8 pixels, row by row from the top
+    pub fn from_rgba8(
+        cx: &mut RenderContext,
+        width: u32,
+        height: u32,
+        data: &[u8],
+        options: &Texture2dOptions,
+    ) -> Self {
+        assert_eq!(data.len(), (width * height * 4) as usize, "RGBA8 data size mismatch");
+
+        let format = if options.srgb {
+            wgpu::TextureFormat::Rgba8UnormSrgb
+        } else {
+            wgpu::TextureFormat::Rgba8Unorm
+        };
+
+        let size = wgpu::Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        };
+
+        let mip_level_count = if options.mipmaps {
+            size.max_mips(wgpu::TextureDimension::D2)
+        } else {
+            1
+        };
+
+        let mut usage = wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST | wgpu::TextureUsages::COPY_SRC;
+        if mip_level_count > 1 {
+            usage |= wgpu::TextureUsages::RENDER_ATTACHMENT;
+        }
+
+        let texture = cx.device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("texture 2d"),
+            size,
+            mip_level_count,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage,
+            view_formats: &[],
+        });
+
+        cx.queue.write_texture(
+            wgpu::ImageCopyTexture {
+                texture: &texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d::ZERO,
+                aspect: wgpu::TextureAspect::All,
+            },
+            data,
+            wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(4 * width),
+                rows_per_image: Some(height),
+            },
+            size,
+        );
+
+        if mip_level_count > 1 {
+            let mipmaps = cx.singleton::<MipmapGenerator>();
+            mipmaps.generate(cx, &texture);
+        }
+
+        Self::from_texture(cx, texture, &options.sampler)
+    }
+
+    /// Wrap an existing texture, its first mip level has to be already filled
+    pub fn from_texture(
+        cx: &mut RenderContext,
+        texture: wgpu::Texture,
+        sampler: &SamplerOptions,
+    ) -> Self {
+        let common = cx.singleton::<Texture2dCommon>();
+
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let sampler = sampler.create_sampler(cx.device);
+
+        let bind_group = cx.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: common.layout(),
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&sampler),
+                },
+            ],
+            label: Some("texture_2d_bind_group"),
+        });
+
+        Self {
+            texture,
+            view,
+            sampler,
+            bind_group: Arc::new(bind_group),
+        }
+    }
+
+    /// Create a texture from a decoded image
+    pub fn from_image(
+        cx: &mut RenderContext,
+        image: &image::DynamicImage,
+        options: &Texture2dOptions,
+    ) -> Self {
+        let rgba = image.to_rgba8();
+        Self::from_rgba8(cx, rgba.width(), rgba.height(), rgba.as_raw(), options)
+    }
+
+    /// Decode an encoded image (PNG or JPEG) from memory
+    pub fn from_bytes(
+        cx: &mut RenderContext,
+        bytes: &[u8],
+        options: &Texture2dOptions,
+    ) -> Result<Self, image::ImageError> {
+        let image = image::load_from_memory(bytes)?;
+        Ok(Self::from_image(cx, &image, options))
+    }
+
+    /// Load an image (PNG or JPEG) from a file
+    pub fn from_path(
+        cx: &mut RenderContext,
+        path: impl AsRef<Path>,
+        options: &Texture2dOptions,
+    ) -> Result<Self, image::ImageError> {
+        let image = image::open(path)?;
+        Ok(Self::from_image(cx, &image, options))
+    }
+
+    /// A 1x1 texture of the given color, useful as a placeholder
+    pub fn solid(
+        cx: &mut RenderContext,
+        color: [u8; 4],
+    ) -> Self {
+        Self::from_rgba8(cx, 1, 1, &color, &Texture2dOptions {
+            mipmaps: false,
+            ..Default::default()
+        })
+    }
+
+    pub fn texture(&self) -> &wgpu::Texture {
+        &self.texture
+    }
+
+    pub fn view(&self) -> &wgpu::TextureView {
+        &self.view
+    }
+
+    pub fn sampler(&self) -> &wgpu::Sampler {
+        &self.sampler
+    }
+
+    /// The bind group with the [`Texture2dCommon`] layout
+    pub fn bind_group(&self) -> &Arc<wgpu::BindGroup> {
+        &self.bind_group
+    }
+
+    pub fn width(&self) -> u32 {
+        self.texture.width()
+    }
+
+    pub fn height(&self) -> u32 {
+        self.texture.height()
+    }
+}
+
+/// Fills the mip chain of textures by repeatedly downsampling the previous level
+pub struct MipmapGenerator {
+    shader: wgpu::ShaderModule,
+    bind_group_layout: wgpu::BindGroupLayout,
+    sampler: wgpu::Sampler,
+    pipelines: Mutex<HashMap<wgpu::TextureFormat, Arc<wgpu::RenderPipeline>>>,
+}
+
+impl SingletonResource for MipmapGenerator {
+    fn init(ctx: &mut RenderContext) -> Self {
+        let shader = ctx.device.create_shader_module(wgpu::include_wgsl!("texture/mipmap.wgsl"));
+
+        let bind_group_layout = ctx.device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+            label: Some("mipmap_bind_group_layout"),
+        });
+
+        let sampler = ctx.device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("mipmap sampler"),
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+
+        Self {
+            shader,
+            bind_group_layout,
+            sampler,
+            pipelines: Mutex::new(HashMap::new()),
+        }
+    }
+}
+
+impl MipmapGenerator {
+    fn pipeline(&self, device: &wgpu::Device, format: wgpu::TextureFormat) -> Arc<wgpu::RenderPipeline> {
+        let mut pipelines = self.pipelines.lock().unwrap();
+        pipelines.entry(format).or_insert_with(|| {
+            let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("mipmap pipeline layout"),
+                bind_group_layouts: &[&self.bind_group_layout],
+                push_constant_ranges: &[],
+            });
+
+            Arc::new(device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some("mipmap pipeline"),
+                layout: Some(&layout),
+                vertex: wgpu::VertexState {
+                    module: &self.shader,
+                    entry_point: "vs_main",
+                    buffers: &[],
+                    compilation_options: Default::default(),
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &self.shader,
+                    entry_point: "fs_main",
+                    targets: &[Some(format.into())],
+                    compilation_options: Default::default(),
+                }),
+                primitive: Default::default(),
+                depth_stencil: None,
+                multisample: Default::default(),
+                multiview: None,
+                cache: None,
+            }))
+        }).clone()
+    }
+
+    /// Record the generation of the mip levels `1..` of `texture` in the [`RenderContext`] encoder.
+    ///
+    /// # Remarks
+    /// `texture` must be a 2D texture with the [`RENDER_ATTACHMENT`](wgpu::TextureUsages::RENDER_ATTACHMENT)
+    /// and [`TEXTURE_BINDING`](wgpu::TextureUsages::TEXTURE_BINDING) usages and a filterable, renderable format.
+    pub fn generate(&self, cx: &mut RenderContext, texture: &wgpu::Texture) {
+        let pipeline = self.pipeline(cx.device, texture.format());
+
+        let views = (0..texture.mip_level_count()).map(|mip| {
+            texture.create_view(&wgpu::TextureViewDescriptor {
+                label: Some("mip level"),
+                base_mip_level: mip,
+                mip_level_count: Some(1),
+                ..Default::default()
+            })
+        }).collect::<Vec<_>>();
+
+        for target in 1..views.len() {
+            let bind_group = cx.device.create_bind_group(&wgpu::BindGroupDescriptor {
+                layout: &self.bind_group_layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: wgpu::BindingResource::TextureView(&views[target - 1]),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: wgpu::BindingResource::Sampler(&self.sampler),
+                    },
+                ],
+                label: None,
+            });
+
+            let mut rp = cx.encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("mipmap pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &views[target],
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: None,
+                timestamp_writes: None,
+                occlusion_query_set: None,
+            });
+
+            rp.set_pipeline(&pipeline);
+            rp.set_bind_group(0, &bind_group, &[]);
+            rp.draw(0..3, 0..1);
+        }
+    }
+}
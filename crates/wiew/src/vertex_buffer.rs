@@ -6,6 +6,13 @@ pub mod instance;
 
 pub trait VertexRawRepr: bytemuck::Pod {
     fn desc() -> wgpu::VertexBufferLayout<'static>;
+
+    /// The name of each field with its number of shader locations, in the order of
+    /// the attributes of [`VertexRawRepr::desc`], used to declare the shader inputs
+    /// of a [`Material`](crate::pipelines::material::Material)
+    fn fields() -> &'static [(&'static str, usize)] {
+        &[]
+    }
 }
 
 pub struct VertexBuffer<T: VertexRawRepr> {
@@ -157,6 +164,12 @@ macro_rules! decl_vertex_raw_repr {
                     attributes: &Self::ATTRIBUTES,
                 }
             }
+
+            fn fields() -> &'static [(&'static str, usize)] {
+                &[$(
+                    (stringify!($field), $crate::decl_vertex_raw_repr!(count $($n),*)),
+                )*]
+            }
         }
     };
     (count ) => { 0 };
@@ -10,6 +10,16 @@ use super::VertexBuffer;
 pub type Instance3dBuffer = VertexBuffer<Instance3d>;
 
 decl_vertex_raw_repr! {
+    /// Per-instance data for 3D meshes.
+    ///
+    /// Besides the model transform, every instance carries a color multiplier,
+    /// an object id (e.g. for picking) and a visibility flag, so that many
+    /// differently styled copies of a mesh can be drawn in a single draw call.
+    ///
+    /// # Remarks
+    /// The instance attributes use the shader locations `0..=6` and `13..=15`,
+    /// vertex types should use the locations in between.
+    #[derive(Debug)]
     struct Instance3d (Instance step mode) {
         pub model: [[f32; 4]; 4] as [
             0 => Float32x4,
@@ -22,16 +32,28 @@ decl_vertex_raw_repr! {
             5 => Float32x3,
             6 => Float32x3,
         ],
+        /// Color multiplier applied to the vertex colors
+        pub color: [f32; 4] as [13 => Float32x4],
+        /// User defined object id, `0` usually means "no object"
+        pub object_id: u32 as [14 => Uint32],
+        /// Whether the instance is drawn (`0` hides it)
+        pub visible: u32 as [15 => Uint32],
     }
 }
 
 impl Instance3d {
+    /// Color multiplier that leaves the vertex colors unchanged
+    pub const WHITE: [f32; 4] = [1.0, 1.0, 1.0, 1.0];
+
     pub fn from_matrix(model: cgmath::Matrix4<f32>) -> Self {
         let model_3x3 = cgmath::Matrix3::from_cols(model.x.truncate(), model.y.truncate(), model.z.truncate());
         let model_3x3_inv_tr = model_3x3.invert().unwrap().transpose();
         Self {
             model: model.into(),
             model_inv_tr: model_3x3_inv_tr.into(),
+            color: Self::WHITE,
+            object_id: 0,
+            visible: 1,
         }
     }
 
@@ -40,17 +62,52 @@ impl Instance3d {
         Self {
             model: cgmath::Matrix4::identity().into(),
             model_inv_tr: cgmath::Matrix3::identity().into(),
+            color: Self::WHITE,
+            object_id: 0,
+            visible: 1,
         }
     }
 
     pub fn translated_x_y_z(self, x: f32, y: f32, z: f32) -> Self {
         let translation = cgmath::Matrix4::from_translation(cgmath::Vector3::new(x, y, z));
-        Self::from_matrix(translation * cgmath::Matrix4::from(self.model))
+        self.with_matrix(translation * cgmath::Matrix4::from(self.model))
     }
 
     pub fn rotated_quaternion(self, q: cgmath::Quaternion<f32>) -> Self {
         let rotation = cgmath::Matrix4::from(q);
-        Self::from_matrix(rotation * cgmath::Matrix4::from(self.model))
+        self.with_matrix(rotation * cgmath::Matrix4::from(self.model))
+    }
+
+    /// Replace the model matrix, keeping color, object id and visibility
+    pub fn with_matrix(self, model: cgmath::Matrix4<f32>) -> Self {
+        Self {
+            color: self.color,
+            object_id: self.object_id,
+            visible: self.visible,
+            ..Self::from_matrix(model)
+        }
+    }
+
+    /// Set the color multiplier
+    pub fn with_color(mut self, color: [f32; 4]) -> Self {
+        self.color = color;
+        self
+    }
+
+    /// Set the object id
+    pub fn with_object_id(mut self, object_id: u32) -> Self {
+        self.object_id = object_id;
+        self
+    }
+
+    /// Show or hide the instance
+    pub fn with_visible(mut self, visible: bool) -> Self {
+        self.visible = visible as u32;
+        self
+    }
+
+    pub fn is_visible(&self) -> bool {
+        self.visible != 0
     }
 
     pub fn from_placement(placement: &Placement3<f32>) -> Self {
@@ -99,6 +156,9 @@ impl Instance3d {
         Self {
             model: model.into(),
             model_inv_tr: model_3x3_inv_tr.into(),
+            color: Self::WHITE,
+            object_id: 0,
+            visible: 1,
         }
     }
 }
\ No newline at end of file